@@ -1,6 +1,9 @@
+use crate::autotag::{Rules, Subject};
+use crate::backend::{BackendResult, PocketAdd, PocketGet};
+use crate::canonical::{self, CanonicalPolicy};
 use crate::models::Item;
 use crate::output::Output;
-use hyper::client::IntoUrl;
+use crate::tag_policy::TagPolicy;
 use pocket::*;
 use std::io::Write;
 use structopt::StructOpt;
@@ -8,31 +11,64 @@ use url::Url;
 
 #[derive(Debug, StructOpt)]
 pub struct AddOpts {
-    url: Url,
+    pub url: Url,
     #[structopt(long)]
-    title: Option<String>,
+    pub title: Option<String>,
     #[structopt(long = "tag")]
-    tags: Option<Vec<String>>,
+    pub tags: Option<Vec<String>>,
     #[structopt(long)]
-    tweet_id: Option<String>,
+    pub tweet_id: Option<String>,
+    /// Add the URL as it is, without canonicalizing it
+    #[structopt(long)]
+    pub raw: bool,
 }
 
-pub fn handle<W: Write>(pocket: &impl PocketAdd, opts: &AddOpts, output: &mut Output<W>) {
-    let tags = opts
-        .tags
+/// Add the item, warning on stderr when its page is already saved unless it's added raw.
+pub fn handle<W: Write>(
+    pocket: &(impl PocketAdd + PocketGet),
+    opts: &AddOpts,
+    policy: &CanonicalPolicy,
+    tag_policy: &TagPolicy,
+    rules: &Rules,
+    output: &mut Output<W>,
+) {
+    if !opts.raw {
+        let saved = canonical::find_saved(pocket, policy, &opts.url).unwrap();
+        canonical::warn_saved(&opts.url, &saved);
+    }
+    let item = add(pocket, opts, policy, tag_policy, rules).unwrap();
+    output.write(item).unwrap();
+}
+
+pub fn add(
+    pocket: &impl PocketAdd,
+    opts: &AddOpts,
+    policy: &CanonicalPolicy,
+    tag_policy: &TagPolicy,
+    rules: &Rules,
+) -> BackendResult<Item> {
+    let url = if opts.raw {
+        opts.url.clone()
+    } else {
+        policy.apply(&opts.url)
+    };
+    let mut tags = opts.tags.clone().unwrap_or_default();
+    tags.extend(rules.tags(&Subject {
+        url: Some(&url),
+        title: opts.title.as_deref(),
+        ..Default::default()
+    }));
+    let tags = Some(tag_policy.apply_all(&tags)).filter(|t| !t.is_empty());
+    let tags = tags
         .as_ref()
         .map(|v| v.iter().map(|s| s.as_ref()).collect::<Vec<&str>>());
 
-    let item: Item = pocket
-        .add(&PocketAddRequest {
-            url: &opts.url,
-            title: opts.title.as_deref(),
-            tags: tags.as_deref(),
-            tweet_id: opts.tweet_id.as_deref(),
-        })
-        .unwrap()
-        .into();
-    output.write(item).unwrap();
+    pocket.add(&PocketAddRequest {
+        url: &url,
+        title: opts.title.as_deref(),
+        tags: tags.as_deref(),
+        tweet_id: opts.tweet_id.as_deref(),
+    })
 }
 
 impl From<PocketAddedItem> for Item {
@@ -78,25 +114,12 @@ impl From<PocketAddedItem> for Item {
     }
 }
 
-pub trait PocketAdd {
-    fn add(&self, request: &PocketAddRequest) -> PocketResult<PocketAddedItem>;
-    fn push<T: IntoUrl>(&self, url: T) -> PocketResult<PocketAddedItem>;
-}
-
-impl PocketAdd for Pocket {
-    fn add(&self, request: &PocketAddRequest) -> PocketResult<PocketAddedItem> {
-        self.add(request)
-    }
-
-    fn push<T: IntoUrl>(&self, url: T) -> PocketResult<PocketAddedItem> {
-        self.push(url)
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::backend::BackendResult;
     use crate::output::OutputFormat;
+    use hyper::client::IntoUrl;
     use hyper::Url;
     use std::io;
 
@@ -123,26 +146,28 @@ mod tests {
         }
     }
 
-    struct PocketAddMock<A, P>
+    struct PocketAddMock<A>
     where
-        A: Fn(&PocketAddRequest) -> PocketResult<PocketAddedItem>,
-        P: Fn(Url) -> PocketResult<PocketAddedItem>,
+        A: Fn(&PocketAddRequest) -> BackendResult<Item>,
     {
         add_mock: A,
-        push_mock: P,
     }
 
-    impl<A, P> PocketAdd for PocketAddMock<A, P>
+    impl<A> PocketAdd for PocketAddMock<A>
     where
-        A: Fn(&PocketAddRequest) -> PocketResult<PocketAddedItem>,
-        P: Fn(Url) -> PocketResult<PocketAddedItem>,
+        A: Fn(&PocketAddRequest) -> BackendResult<Item>,
     {
-        fn add(&self, request: &PocketAddRequest) -> PocketResult<PocketAddedItem> {
+        fn add(&self, request: &PocketAddRequest) -> BackendResult<Item> {
             (self.add_mock)(request)
         }
+    }
 
-        fn push<T: IntoUrl>(&self, url: T) -> PocketResult<PocketAddedItem> {
-            (self.push_mock)(url.into_url().unwrap())
+    impl<A> PocketGet for PocketAddMock<A>
+    where
+        A: Fn(&PocketAddRequest) -> BackendResult<Item>,
+    {
+        fn get(&self, _opts: &crate::get::GetOpts) -> BackendResult<Vec<Item>> {
+            Ok(vec![])
         }
     }
 
@@ -185,21 +210,28 @@ mod tests {
     fn add_writes_item() {
         let raw_url = "https://example.com";
         let pocket = PocketAddMock {
-            add_mock: |r| Ok(added_item(r.url)),
-            push_mock: |_| Err(PocketError::Proto(0, "".to_string())),
+            add_mock: |r| Ok(added_item(r.url).into()),
         };
         let opts = AddOpts {
             url: raw_url.into_url().unwrap(),
             title: None,
             tags: None,
             tweet_id: None,
+            raw: false,
         };
         let writer = Vec::new();
         let mut output = Output::new(OutputFormat::Json, writer);
         let url = "https://example.com".into_url().unwrap();
         let expected_item: Item = added_item(&url).into();
 
-        handle(&pocket, &opts, &mut output);
+        handle(
+            &pocket,
+            &opts,
+            &CanonicalPolicy::default(),
+            &TagPolicy::default(),
+            &Rules::default(),
+            &mut output,
+        );
 
         assert_eq!(
             serde_json::to_string(&expected_item).unwrap(),
@@ -207,24 +239,131 @@ mod tests {
         );
     }
 
+    #[test]
+    fn add_canonicalizes_url_unless_raw() {
+        let pocket = PocketAddMock {
+            add_mock: |r| Ok(added_item(r.url).into()),
+        };
+        let mut opts = AddOpts {
+            url: "http://m.example.com/a?utm_source=x".into_url().unwrap(),
+            title: None,
+            tags: None,
+            tweet_id: None,
+            raw: false,
+        };
+
+        let canonical = add(
+            &pocket,
+            &opts,
+            &CanonicalPolicy::default(),
+            &TagPolicy::default(),
+            &Rules::default(),
+        )
+        .unwrap();
+        opts.raw = true;
+        let raw = add(
+            &pocket,
+            &opts,
+            &CanonicalPolicy::default(),
+            &TagPolicy::default(),
+            &Rules::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            "http://example.com/a",
+            canonical.given_url.unwrap().as_str()
+        );
+        assert_eq!(
+            "http://m.example.com/a?utm_source=x",
+            raw.given_url.unwrap().as_str()
+        );
+    }
+
+    #[test]
+    fn add_normalizes_tags() {
+        let pocket = PocketAddMock {
+            add_mock: |r| {
+                assert_eq!(Some(&["rust", "read-later"][..]), r.tags);
+                Ok(added_item(r.url).into())
+            },
+        };
+        let opts = AddOpts {
+            url: "https://example.com".into_url().unwrap(),
+            title: None,
+            tags: Some(
+                ["Rust", "rust ", "Read Later"]
+                    .iter()
+                    .map(|t| t.to_string())
+                    .collect(),
+            ),
+            tweet_id: None,
+            raw: false,
+        };
+
+        add(
+            &pocket,
+            &opts,
+            &CanonicalPolicy::default(),
+            &TagPolicy::folding(),
+            &Rules::default(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn add_tags_by_rules() {
+        let pocket = PocketAddMock {
+            add_mock: |r| {
+                assert_eq!(Some(&["later", "rust"][..]), r.tags);
+                Ok(added_item(r.url).into())
+            },
+        };
+        let opts = AddOpts {
+            url: "https://blog.rust-lang.org/post".into_url().unwrap(),
+            title: None,
+            tags: Some(vec!["later".to_string()]),
+            tweet_id: None,
+            raw: false,
+        };
+        let rules: Rules =
+            toml::from_str("[[rules]]\ndomain = \"rust-lang.org\"\ntags = [\"Rust\"]").unwrap();
+
+        add(
+            &pocket,
+            &opts,
+            &CanonicalPolicy::default(),
+            &TagPolicy::folding(),
+            &rules,
+        )
+        .unwrap();
+    }
+
     #[test]
     #[should_panic]
     fn add_panics_when_pocket_error() {
         let raw_url = "https://example.com";
         let pocket = PocketAddMock {
-            add_mock: |_| Err(PocketError::Proto(0, "".to_string())),
-            push_mock: |_| Ok(added_item(&raw_url.into_url().unwrap())),
+            add_mock: |_| Err(PocketError::Proto(0, "".to_string()).into()),
         };
         let opts = AddOpts {
             url: raw_url.into_url().unwrap(),
             title: None,
             tags: None,
             tweet_id: None,
+            raw: false,
         };
         let writer = Vec::new();
         let mut output = Output::new(OutputFormat::Json, writer);
 
-        handle(&pocket, &opts, &mut output);
+        handle(
+            &pocket,
+            &opts,
+            &CanonicalPolicy::default(),
+            &TagPolicy::default(),
+            &Rules::default(),
+            &mut output,
+        );
     }
 
     #[test]
@@ -232,14 +371,14 @@ mod tests {
     fn add_panics_when_write_error() {
         let raw_url = "https://example.com";
         let pocket = PocketAddMock {
-            add_mock: |r| Ok(added_item(r.url)),
-            push_mock: |_| Ok(added_item(&raw_url.into_url().unwrap())),
+            add_mock: |r| Ok(added_item(r.url).into()),
         };
         let opts = AddOpts {
             url: raw_url.into_url().unwrap(),
             title: None,
             tags: None,
             tweet_id: None,
+            raw: false,
         };
         let writer = WriteMock {
             flush_mock: || Ok(()),
@@ -247,6 +386,13 @@ mod tests {
         };
         let mut output = Output::new(OutputFormat::Json, writer);
 
-        handle(&pocket, &opts, &mut output);
+        handle(
+            &pocket,
+            &opts,
+            &CanonicalPolicy::default(),
+            &TagPolicy::default(),
+            &Rules::default(),
+            &mut output,
+        );
     }
 }
@@ -1,3 +1,5 @@
+use crate::backend::v3::V3Authentication;
+use crate::backend::BackendResult;
 use crate::output::Output;
 use pocket::*;
 use serde::Serialize;
@@ -18,12 +20,25 @@ pub enum Auth {
     },
 }
 
-pub fn handle<W: Write>(cmd: &Auth, consumer_key: &str, output: &mut Output<W>) {
+pub fn handle<W: Write>(
+    cmd: &Auth,
+    consumer_key: &str,
+    api_base_url: Option<Url>,
+    output: &mut Output<W>,
+) {
     match cmd {
         Auth::Login { save } => {
             let server = TcpAuthServer::new();
-            let pocket = PocketAuthentication::new(&consumer_key, server.addr());
-            login(pocket, *save, server, &open_browser, output)
+            match api_base_url {
+                Some(url) => {
+                    let pocket = V3Authentication::new(url, &consumer_key, server.addr());
+                    login(pocket, *save, server, &open_browser, output)
+                }
+                None => {
+                    let pocket = PocketAuthentication::new(&consumer_key, server.addr());
+                    login(pocket, *save, server, &open_browser, output)
+                }
+            }
         }
     }
 }
@@ -73,21 +88,35 @@ impl From<PocketUser> for User {
 }
 
 trait PocketAuth {
-    fn request(&self, state: Option<&str>) -> PocketResult<String>;
+    fn request(&self, state: Option<&str>) -> BackendResult<String>;
     fn authorize_url(&self, code: &str) -> Url;
-    fn authorize(&self, code: &str, state: Option<&str>) -> PocketResult<PocketUser>;
+    fn authorize(&self, code: &str, state: Option<&str>) -> BackendResult<PocketUser>;
 }
 
 impl PocketAuth for PocketAuthentication {
-    fn request(&self, state: Option<&str>) -> PocketResult<String> {
-        self.request(state)
+    fn request(&self, state: Option<&str>) -> BackendResult<String> {
+        Ok(self.request(state)?)
     }
 
     fn authorize_url(&self, code: &str) -> Url {
         self.authorize_url(code)
     }
 
-    fn authorize(&self, code: &str, state: Option<&str>) -> PocketResult<PocketUser> {
+    fn authorize(&self, code: &str, state: Option<&str>) -> BackendResult<PocketUser> {
+        Ok(self.authorize(code, state)?)
+    }
+}
+
+impl PocketAuth for V3Authentication {
+    fn request(&self, state: Option<&str>) -> BackendResult<String> {
+        self.request(state)
+    }
+
+    fn authorize_url(&self, code: &str) -> Url {
+        self.authorize_url(code).unwrap()
+    }
+
+    fn authorize(&self, code: &str, state: Option<&str>) -> BackendResult<PocketUser> {
         self.authorize(code, state)
     }
 }
@@ -156,7 +185,7 @@ const AUTH_SUCCESS_RESPONSE_BODY: &str = r#"
 mod tests {
     use super::*;
     use crate::output::OutputFormat;
-    use pocket::{PocketResult, PocketUser};
+    use pocket::PocketUser;
     use std::io;
 
     #[test]
@@ -204,9 +233,9 @@ mod tests {
 
     struct PocketAuthMock<R, U, A>
     where
-        R: Fn(Option<&str>) -> PocketResult<String>,
+        R: Fn(Option<&str>) -> BackendResult<String>,
         U: Fn(&str) -> Url,
-        A: Fn(&str, Option<&str>) -> PocketResult<PocketUser>,
+        A: Fn(&str, Option<&str>) -> BackendResult<PocketUser>,
     {
         request_mock: R,
         authorize_url: U,
@@ -215,11 +244,11 @@ mod tests {
 
     impl<R, U, A> PocketAuth for PocketAuthMock<R, U, A>
     where
-        R: Fn(Option<&str>) -> PocketResult<String>,
+        R: Fn(Option<&str>) -> BackendResult<String>,
         U: Fn(&str) -> Url,
-        A: Fn(&str, Option<&str>) -> PocketResult<PocketUser>,
+        A: Fn(&str, Option<&str>) -> BackendResult<PocketUser>,
     {
-        fn request(&self, state: Option<&str>) -> PocketResult<String> {
+        fn request(&self, state: Option<&str>) -> BackendResult<String> {
             (self.request_mock)(state)
         }
 
@@ -227,7 +256,7 @@ mod tests {
             (self.authorize_url)(code)
         }
 
-        fn authorize(&self, code: &str, state: Option<&str>) -> PocketResult<PocketUser> {
+        fn authorize(&self, code: &str, state: Option<&str>) -> BackendResult<PocketUser> {
             (self.authorize_mock)(code, state)
         }
     }
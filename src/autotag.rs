@@ -0,0 +1,440 @@
+use crate::backend::{send_all, BackendResult, PocketGet, PocketSend};
+use crate::get::{self, GetOpts};
+use crate::models::{IndividualSendResponse, Item};
+use crate::output::Output;
+use crate::tag_policy::TagPolicy;
+use directories::ProjectDirs;
+use pocket::{PocketGetDetail, PocketSendAction};
+use regex::Regex;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+use url::Url;
+
+#[derive(Debug, StructOpt)]
+pub struct AutotagOpts {
+    #[structopt(flatten)]
+    pub get: GetOpts,
+    /// Show which rules match each item and why, without tagging anything
+    #[structopt(long)]
+    pub explain: bool,
+}
+
+/// Rules tagging items by what they are, read from a TOML file of `[[rules]]`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Rules {
+    pub rules: Vec<Rule>,
+}
+
+/// Tags to give items matching every condition set. Conditions on what Pocket
+/// learns by parsing a page, such as `is_article`, never match when adding it.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Rule {
+    /// Name to explain matches with, instead of the number of the rule
+    pub name: Option<String>,
+    /// Domain of the URL, or a domain above it
+    pub domain: Option<String>,
+    /// Regular expression matching the URL
+    #[serde(deserialize_with = "regex")]
+    pub url: Option<Regex>,
+    /// Words of which the title has to contain at least one as whole words, ignoring case
+    pub title: Vec<String>,
+    pub is_article: Option<bool>,
+    pub has_video: Option<bool>,
+    pub min_words: Option<usize>,
+    pub max_words: Option<usize>,
+    pub tags: Vec<String>,
+}
+
+/// What rules are matched against; `None` is what isn't known yet.
+#[derive(Debug, Default)]
+pub struct Subject<'a> {
+    pub url: Option<&'a Url>,
+    pub title: Option<&'a str>,
+    pub is_article: Option<bool>,
+    pub has_video: Option<bool>,
+    pub word_count: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RuleMatch {
+    pub rule: String,
+    pub tags: Vec<String>,
+    /// The conditions that matched
+    pub reasons: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Autotagged {
+    pub items: Vec<AutotaggedItem>,
+    /// Responses to tagging the items, unless explaining
+    pub actions: Vec<IndividualSendResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AutotaggedItem {
+    pub item_id: u64,
+    pub title: Option<String>,
+    /// Tags the item gets that it lacks
+    pub tags_added: Vec<String>,
+    /// The rules that matched, with --explain
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub matches: Vec<RuleMatch>,
+}
+
+impl Rules {
+    pub fn default_path() -> PathBuf {
+        ProjectDirs::from("", "", env!("CARGO_PKG_NAME"))
+            .expect("No home directory for autotag rules.")
+            .config_dir()
+            .join("autotag.toml")
+    }
+
+    /// The rules in the file at `path`, or none when there is no such file.
+    pub fn load(path: &Path) -> io::Result<Rules> {
+        match fs::read_to_string(path) {
+            Ok(s) => toml::from_str(&s).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Rules::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The rules `subject` matches, in the order of the file.
+    pub fn matches(&self, subject: &Subject) -> Vec<RuleMatch> {
+        self.rules
+            .iter()
+            .enumerate()
+            .filter_map(|(i, rule)| {
+                rule.reasons(subject).map(|reasons| RuleMatch {
+                    rule: rule
+                        .name
+                        .clone()
+                        .unwrap_or_else(|| format!("rule {}", i + 1)),
+                    tags: rule.tags.clone(),
+                    reasons,
+                })
+            })
+            .collect()
+    }
+
+    /// Tags of every rule `subject` matches, without repeats.
+    pub fn tags(&self, subject: &Subject) -> Vec<String> {
+        let mut tags: Vec<String> = Vec::new();
+        for tag in self.matches(subject).into_iter().flat_map(|m| m.tags) {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        tags
+    }
+}
+
+impl Rule {
+    /// Why `subject` matches, or `None` when a condition doesn't hold. A rule
+    /// without conditions matches nothing, as it would tag every item.
+    fn reasons(&self, subject: &Subject) -> Option<Vec<String>> {
+        let mut reasons = Vec::new();
+
+        if let Some(domain) = &self.domain {
+            let host = subject.url?.host_str()?;
+            if host != domain && !host.ends_with(&format!(".{}", domain)) {
+                return None;
+            }
+            reasons.push(format!("domain {}", domain));
+        }
+        if let Some(pattern) = &self.url {
+            if !pattern.is_match(subject.url?.as_str()) {
+                return None;
+            }
+            reasons.push(format!("URL matches {}", pattern));
+        }
+        if !self.title.is_empty() {
+            let title = words(subject.title?);
+            let word = self.title.iter().find(|w| {
+                let w = words(w);
+                !w.is_empty() && title.windows(w.len()).any(|t| *t == w[..])
+            })?;
+            reasons.push(format!("title contains {}", word));
+        }
+        if let Some(is_article) = self.is_article {
+            if subject.is_article? != is_article {
+                return None;
+            }
+            reasons.push(format!("is_article = {}", is_article));
+        }
+        if let Some(has_video) = self.has_video {
+            if subject.has_video? != has_video {
+                return None;
+            }
+            reasons.push(format!("has_video = {}", has_video));
+        }
+        if let Some(min) = self.min_words {
+            if subject.word_count? < min {
+                return None;
+            }
+            reasons.push(format!("at least {} words", min));
+        }
+        if let Some(max) = self.max_words {
+            if subject.word_count? > max {
+                return None;
+            }
+            reasons.push(format!("at most {} words", max));
+        }
+
+        Some(reasons).filter(|r| !r.is_empty())
+    }
+}
+
+/// The lowercase words of `text`, split on anything but letters and digits.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+impl<'a> From<&'a Item> for Subject<'a> {
+    fn from(item: &'a Item) -> Self {
+        Subject {
+            url: item.url(),
+            title: item.title(),
+            is_article: Some(item.is_article),
+            has_video: Some(item.has_video()),
+            // Pocket reports 0 words for pages it hasn't parsed
+            word_count: Some(item.word_count).filter(|w| *w > 0),
+        }
+    }
+}
+
+fn regex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Regex>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| Regex::new(&s).map_err(de::Error::custom))
+        .transpose()
+}
+
+pub fn handle<W: Write>(
+    pocket: &(impl PocketGet + PocketSend),
+    opts: &AutotagOpts,
+    rules: &Rules,
+    policy: &TagPolicy,
+    output: &mut Output<W>,
+) {
+    let autotagged = autotag(pocket, opts, rules, policy).unwrap();
+    output.write(autotagged).unwrap();
+}
+
+/// Tag the items `opts` get with the tags of the rules they match.
+pub fn autotag(
+    pocket: &(impl PocketGet + PocketSend),
+    opts: &AutotagOpts,
+    rules: &Rules,
+    policy: &TagPolicy,
+) -> BackendResult<Autotagged> {
+    let items = get::get(
+        pocket,
+        &GetOpts {
+            detail_type: Some(PocketGetDetail::Complete),
+            ..opts.get.clone()
+        },
+    )?;
+
+    let mut tagged = Vec::new();
+    for item in &items {
+        let subject = Subject::from(item);
+        let matches = rules.matches(&subject);
+        if matches.is_empty() {
+            continue;
+        }
+        let tags = item.tag_names();
+        let tags_added = policy
+            .apply_all(&rules.tags(&subject))
+            .into_iter()
+            .filter(|t| !tags.contains(&t.as_str()))
+            .collect::<Vec<_>>();
+        if tags_added.is_empty() && !opts.explain {
+            continue;
+        }
+        tagged.push(AutotaggedItem {
+            item_id: item.item_id,
+            title: item.title().map(str::to_string),
+            tags_added,
+            matches: if opts.explain { matches } else { vec![] },
+        });
+    }
+
+    let actions = tagged
+        .iter()
+        .filter(|i| !i.tags_added.is_empty())
+        .map(|i| PocketSendAction::TagsAdd {
+            item_id: i.item_id,
+            tags: i.tags_added.join(","),
+            time: None,
+        })
+        .collect::<Vec<_>>();
+    let actions = if opts.explain || actions.is_empty() {
+        vec![]
+    } else {
+        IndividualSendResponse::each(send_all(pocket, &actions)?)
+    };
+
+    Ok(Autotagged {
+        items: tagged,
+        actions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::{succeed, unused_add, unused_send, PocketMock};
+
+    fn rules() -> Rules {
+        toml::from_str(
+            r#"
+            [[rules]]
+            name = "rust"
+            domain = "rust-lang.org"
+            title = ["Rust"]
+            tags = ["rust"]
+
+            [[rules]]
+            url = "/watch\\?v="
+            tags = ["video"]
+
+            [[rules]]
+            is_article = true
+            min_words = 3000
+            tags = ["longread"]
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn opts(explain: bool) -> AutotagOpts {
+        AutotagOpts {
+            get: GetOpts::default(),
+            explain,
+        }
+    }
+
+    fn article(item_id: u64, url: &str, words: usize) -> Item {
+        let mut item = Item::fixture(item_id, url);
+        item.given_title = Some("Rust 2018".to_string());
+        item.is_article = true;
+        item.word_count = words;
+        item
+    }
+
+    fn titled<'a>(url: &'a Url, title: &'a str) -> Subject<'a> {
+        Subject {
+            url: Some(url),
+            title: Some(title),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rules_match_every_condition() {
+        let rules = rules();
+        let url = Url::parse("https://blog.rust-lang.org/post").unwrap();
+
+        assert_eq!(
+            vec!["rust"],
+            rules.tags(&titled(&url, "Announcing Rust 1.50"))
+        );
+        assert!(rules.tags(&titled(&url, "Release notes")).is_empty());
+        assert!(rules.tags(&titled(&url, "Trustworthy releases")).is_empty());
+    }
+
+    #[test]
+    fn rules_on_parsed_content_skip_unknown_subjects() {
+        let rules = rules();
+        let url = Url::parse("https://example.com/essay").unwrap();
+        let item = article(1, "https://example.com/essay", 4000);
+
+        assert!(rules
+            .tags(&Subject {
+                url: Some(&url),
+                ..Default::default()
+            })
+            .is_empty());
+        assert_eq!(vec!["longread"], rules.tags(&Subject::from(&item)));
+    }
+
+    #[test]
+    fn matches_explain_conditions() {
+        let item = article(1, "https://www.rust-lang.org/learn", 4000);
+
+        let matches = rules().matches(&Subject::from(&item));
+
+        assert_eq!(
+            vec!["rust", "rule 3"],
+            matches.iter().map(|m| m.rule.as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["domain rust-lang.org", "title contains Rust"],
+            matches[0].reasons
+        );
+    }
+
+    #[test]
+    fn invalid_regex_fails_to_load() {
+        assert!(toml::from_str::<Rules>("[[rules]]\nurl = \"(\"").is_err());
+    }
+
+    #[test]
+    fn autotag_adds_missing_tags_in_one_send() {
+        let pocket = PocketMock {
+            get_mock: |opts| {
+                assert!(matches!(opts.detail_type, Some(PocketGetDetail::Complete)));
+                Ok(vec![
+                    article(1, "https://youtube.com/watch?v=x", 0),
+                    article(2, "https://www.rust-lang.org/", 100).with_tags(&["rust"]),
+                    article(3, "https://example.com/", 5000),
+                ])
+            },
+            send_mock: |request| {
+                assert!(matches!(
+                    request.actions,
+                    [
+                        PocketSendAction::TagsAdd { item_id: 1, tags: video, .. },
+                        PocketSendAction::TagsAdd { item_id: 3, tags: longread, .. }
+                    ] if video == "video" && longread == "longread"
+                ));
+                Ok(succeed(request))
+            },
+            add_mock: unused_add,
+        };
+
+        let autotagged = autotag(&pocket, &opts(false), &rules(), &TagPolicy::default()).unwrap();
+
+        assert_eq!(
+            vec![1, 3],
+            autotagged
+                .items
+                .iter()
+                .map(|i| i.item_id)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(2, autotagged.actions.len());
+    }
+
+    #[test]
+    fn autotag_explains_without_sending() {
+        let pocket = PocketMock {
+            get_mock: |_| Ok(vec![article(1, "https://www.rust-lang.org/", 100)]),
+            send_mock: unused_send,
+            add_mock: unused_add,
+        };
+
+        let autotagged = autotag(&pocket, &opts(true), &rules(), &TagPolicy::default()).unwrap();
+
+        assert_eq!(vec!["rust"], autotagged.items[0].tags_added);
+        assert_eq!("rust", autotagged.items[0].matches[0].rule);
+        assert!(autotagged.actions.is_empty());
+    }
+}
@@ -0,0 +1,82 @@
+use crate::get::GetOpts;
+use crate::models::{Item, ItemHas, ItemStatus};
+use pocket::{PocketGetDetail, PocketGetSort, PocketGetState, PocketGetType};
+
+/// Apply every `GetOpts` filter, the sort and the page to items the way the Pocket
+/// API does, for backends that cannot do it themselves.
+pub fn apply(items: impl IntoIterator<Item = Item>, opts: &GetOpts) -> Vec<Item> {
+    let mut items = items
+        .into_iter()
+        .filter(|i| matches(i, opts))
+        .collect::<Vec<_>>();
+
+    match opts.sort.unwrap_or(PocketGetSort::Newest) {
+        PocketGetSort::Newest => items.sort_by(|a, b| b.time_added.cmp(&a.time_added)),
+        PocketGetSort::Oldest => items.sort_by(|a, b| a.time_added.cmp(&b.time_added)),
+        PocketGetSort::Title => items.sort_by_key(sort_title),
+        PocketGetSort::Site => items.sort_by_key(|i| i.domain().unwrap_or_default().to_string()),
+    }
+
+    let complete = matches!(opts.detail_type, Some(PocketGetDetail::Complete));
+    items
+        .into_iter()
+        .skip(opts.offset.unwrap_or(0))
+        .take(opts.count.unwrap_or(usize::MAX))
+        .map(|mut i| {
+            // Pocket only includes tags in complete detail
+            if !complete {
+                i.tags = None;
+            }
+            i
+        })
+        .collect()
+}
+
+pub fn matches(item: &Item, opts: &GetOpts) -> bool {
+    let archived = matches!(item.status, Some(ItemStatus::Archived));
+    let state = match opts.state.unwrap_or(PocketGetState::Unread) {
+        PocketGetState::Unread => !archived,
+        PocketGetState::Archive => archived,
+        PocketGetState::All => true,
+    };
+    let favorite = opts
+        .favorite
+        .map_or(true, |f| f == item.favorite.unwrap_or_default());
+    let tags = item.tag_names();
+    let tag = match (&opts.tag, opts.untagged) {
+        (Some(_), true) => panic!("Cannot set tag and untagged"),
+        (Some(tag), false) => tags.contains(&tag.as_str()),
+        (None, true) => tags.is_empty(),
+        (None, false) => true,
+    };
+    let content_type = opts.content_type.map_or(true, |t| match t {
+        PocketGetType::Article => item.is_article,
+        PocketGetType::Video => matches!(item.has_video, ItemHas::Yes | ItemHas::Is),
+        PocketGetType::Image => matches!(item.has_image, ItemHas::Is),
+    });
+    let domain = opts.domain.as_ref().map_or(true, |d| {
+        let domain = item.domain().unwrap_or_default();
+        domain == d.as_str() || domain.ends_with(&format!(".{}", d))
+    });
+    let search = opts.search.as_ref().map_or(true, |s| {
+        let s = s.to_lowercase();
+        sort_title(item).contains(&s)
+            || item
+                .url()
+                .map_or(false, |u| u.as_str().to_lowercase().contains(&s))
+    });
+    let since = opts.since.map_or(true, |since| {
+        item.time_updated
+            .or(item.time_added)
+            .map_or(false, |t| t >= since)
+    });
+
+    state && favorite && tag && content_type && domain && search && since
+}
+
+fn sort_title(item: &Item) -> String {
+    item.title()
+        .or_else(|| item.url().map(|u| u.as_str()))
+        .unwrap_or_default()
+        .to_lowercase()
+}
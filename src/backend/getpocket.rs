@@ -0,0 +1,80 @@
+use super::{BackendResult, PocketAdd, PocketGet, PocketSend, SendResponse};
+use crate::get::GetOpts;
+use crate::models::Item;
+use pocket::{Pocket, PocketAddRequest, PocketGetTag, PocketSendRequest};
+
+impl PocketAdd for Pocket {
+    fn add(&self, request: &PocketAddRequest) -> BackendResult<Item> {
+        Ok(self.add(request)?.into())
+    }
+}
+
+impl PocketGet for Pocket {
+    fn get(&self, opts: &GetOpts) -> BackendResult<Vec<Item>> {
+        let mut f = self.filter();
+
+        if let Some(search) = &opts.search {
+            f.search(search);
+        }
+
+        // domain
+        if let Some(domain) = &opts.domain {
+            f.domain(domain);
+        }
+
+        // tag match
+        match (&opts.tag, opts.untagged) {
+            (Some(_), true) => panic!("Cannot set tag and untagged"),
+            (Some(tag), false) => {
+                f.tag(PocketGetTag::Tagged(tag));
+            }
+            (None, true) => {
+                f.tag(PocketGetTag::Untagged);
+            }
+            (None, false) => {}
+        }
+
+        // state
+        if let Some(state) = opts.state {
+            f.state(state);
+        }
+
+        // content_type
+        if let Some(content_type) = opts.content_type {
+            f.content_type(content_type);
+        }
+
+        // detail_type
+        if let Some(detail_type) = opts.detail_type {
+            f.detail_type(detail_type);
+        }
+
+        // favorite
+        if let Some(favorite) = opts.favorite {
+            f.favorite(favorite);
+        }
+
+        // sort
+        if let Some(sort) = opts.sort {
+            f.sort(sort);
+        }
+
+        // offset
+        if let Some(offset) = opts.offset {
+            f.offset(offset);
+        }
+
+        // count
+        if let Some(count) = opts.count {
+            f.count(count);
+        }
+
+        Ok(self.get(&f)?.into_iter().map(|i| i.into()).collect())
+    }
+}
+
+impl PocketSend for Pocket {
+    fn send(&self, request: &PocketSendRequest) -> BackendResult<SendResponse> {
+        Ok(self.send(request)?.into())
+    }
+}
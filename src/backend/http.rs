@@ -0,0 +1,202 @@
+use super::{BackendError, BackendResult};
+use hyper::client::Client;
+use hyper::header::{Authorization, ContentType};
+use hyper::method::Method;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::Read;
+use url::form_urlencoded;
+use url::Url;
+
+/// JSON over HTTP for backends that talk to a REST API.
+pub struct Http {
+    client: Client,
+    base_url: Url,
+}
+
+pub enum Body {
+    Empty,
+    Json(String),
+    Form(String),
+}
+
+impl Body {
+    pub fn json<T: Serialize>(value: &T) -> BackendResult<Body> {
+        Ok(Body::Json(serde_json::to_string(value)?))
+    }
+
+    pub fn form<'a>(pairs: impl IntoIterator<Item = (&'a str, &'a str)>) -> Body {
+        Body::Form(
+            form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(pairs)
+                .finish(),
+        )
+    }
+}
+
+impl Http {
+    pub fn new(base_url: Url) -> Http {
+        Http {
+            client: Client::new(),
+            base_url,
+        }
+    }
+
+    /// `path` is relative to the base URL, which keeps any path prefix it has.
+    pub fn url(&self, path: &str, query: &[(&str, String)]) -> BackendResult<Url> {
+        let mut base_url = self.base_url.clone();
+        if !base_url.path().ends_with('/') {
+            let path = format!("{}/", base_url.path());
+            base_url.set_path(&path);
+        }
+        let mut url = base_url
+            .join(path.trim_start_matches('/'))
+            .map_err(|e| BackendError::Response(0, e.to_string()))?;
+        if !query.is_empty() {
+            url.query_pairs_mut()
+                .extend_pairs(query.iter().map(|(k, v)| (*k, v.as_str())));
+        }
+        Ok(url)
+    }
+
+    pub fn json<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        url: Url,
+        authorization: Option<&str>,
+        body: Body,
+    ) -> BackendResult<T> {
+        let body = self.request(method, url, authorization, body)?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    pub fn request(
+        &self,
+        method: Method,
+        url: Url,
+        authorization: Option<&str>,
+        body: Body,
+    ) -> BackendResult<String> {
+        let mut request = self.client.request(method, url);
+        if let Some(authorization) = authorization {
+            request = request.header(Authorization(authorization.to_string()));
+        }
+        let mut response = match &body {
+            Body::Empty => request.send(),
+            Body::Json(json) => request
+                .header(ContentType::json())
+                .body(json.as_str())
+                .send(),
+            Body::Form(form) => request
+                .header(ContentType::form_url_encoded())
+                .body(form.as_str())
+                .send(),
+        }?;
+
+        let mut body = String::new();
+        response.read_to_string(&mut body)?;
+        if response.status.is_success() {
+            Ok(body)
+        } else {
+            Err(BackendError::Response(response.status.to_u16(), body))
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod mock {
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    #[derive(Clone, Debug)]
+    pub struct Request {
+        pub method: String,
+        pub path: String,
+        pub headers: HashMap<String, String>,
+        pub body: String,
+    }
+
+    /// A local HTTP server answering every request with `handler`.
+    pub struct MockServer {
+        pub url: String,
+        requests: Arc<Mutex<Vec<Request>>>,
+    }
+
+    impl MockServer {
+        pub fn start<H>(handler: H) -> MockServer
+        where
+            H: Fn(&Request) -> (u16, String) + Send + 'static,
+        {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let recorded = requests.clone();
+
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let mut stream = stream.unwrap();
+                    let request = read_request(&mut stream);
+                    let (status, body) = handler(&request);
+                    recorded.lock().unwrap().push(request);
+                    write!(
+                        stream,
+                        "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    )
+                    .unwrap();
+                    stream.flush().unwrap();
+                }
+            });
+
+            MockServer { url, requests }
+        }
+
+        pub fn requests(&self) -> Vec<Request> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    fn read_request(stream: &mut impl Read) -> Request {
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let mut parts = line.split_whitespace();
+        let method = parts.next().unwrap_or_default().to_string();
+        let path = parts.next().unwrap_or_default().to_string();
+
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(colon) = line.find(':') {
+                headers.insert(
+                    line[..colon].to_lowercase(),
+                    line[colon + 1..].trim().to_string(),
+                );
+            }
+        }
+
+        let length = headers
+            .get("content-length")
+            .and_then(|l| l.parse().ok())
+            .unwrap_or(0);
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+
+        Request {
+            method,
+            path,
+            headers,
+            body: String::from_utf8(body).unwrap(),
+        }
+    }
+}
@@ -0,0 +1,543 @@
+use super::http::{Body, Http};
+use super::{
+    filter, on_item, split_tags, BackendError, BackendResult, PocketAdd, PocketGet, PocketSend,
+    SendResponse,
+};
+use crate::get::GetOpts;
+use crate::models::{Item, ItemHas, ItemStatus, Tag};
+use chrono::{DateTime, Utc};
+use hyper::method::Method;
+use pocket::{PocketAddRequest, PocketGetState, PocketSendAction, PocketSendRequest};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use url::Url;
+
+const PAGE_LIMIT: &str = "100";
+const BOOKMARKS: &str = "api/bookmarks/";
+const ARCHIVED_BOOKMARKS: &str = "api/bookmarks/archived/";
+
+/// A linkding instance, through its REST API.
+pub struct LinkdingBackend {
+    http: Http,
+    authorization: String,
+    /// Whether the URL was seen to point at linkding
+    url_checked: Cell<bool>,
+}
+
+#[derive(Deserialize)]
+struct Bookmarks {
+    next: Option<String>,
+    results: Vec<Bookmark>,
+}
+
+#[derive(Deserialize)]
+struct Bookmark {
+    id: u64,
+    url: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    description: String,
+    website_title: Option<String>,
+    website_description: Option<String>,
+    #[serde(default)]
+    is_archived: bool,
+    #[serde(default)]
+    unread: bool,
+    #[serde(default)]
+    tag_names: Vec<String>,
+    date_added: Option<DateTime<Utc>>,
+    date_modified: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct NewBookmark<'a> {
+    url: &'a str,
+    title: &'a str,
+    unread: bool,
+    tag_names: Vec<&'a str>,
+}
+
+#[derive(Default, Serialize)]
+struct BookmarkPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unread: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tag_names: Option<Vec<String>>,
+}
+
+impl LinkdingBackend {
+    pub fn new(url: Url, token: &str) -> LinkdingBackend {
+        LinkdingBackend {
+            http: Http::new(url),
+            authorization: format!("Token {}", token),
+            url_checked: Cell::new(false),
+        }
+    }
+
+    /// Lists a bookmark to tell a missing bookmark from a wrong URL, which 404s too.
+    fn check_url(&self) -> BackendResult<()> {
+        if !self.url_checked.get() {
+            let url = self.http.url(BOOKMARKS, &[("limit", "1".to_string())])?;
+            let _: Bookmarks = self.call(Method::Get, url, Body::Empty)?;
+            self.url_checked.set(true);
+        }
+        Ok(())
+    }
+
+    fn call<T: serde::de::DeserializeOwned>(
+        &self,
+        method: Method,
+        url: Url,
+        body: Body,
+    ) -> BackendResult<T> {
+        self.http.json(method, url, Some(&self.authorization), body)
+    }
+
+    /// Follows `next` links until every matching bookmark is listed.
+    fn bookmarks(&self, path: &str, query: Option<&str>) -> BackendResult<Vec<Bookmark>> {
+        let mut params = vec![("limit", PAGE_LIMIT.to_string())];
+        if let Some(query) = query {
+            params.push(("q", query.to_string()));
+        }
+        let mut url = Some(self.http.url(path, &params)?);
+        let mut bookmarks = Vec::new();
+
+        while let Some(next) = url {
+            let page: Bookmarks = self.call(Method::Get, next, Body::Empty)?;
+            bookmarks.extend(page.results);
+            url = page.next.and_then(|n| Url::parse(&n).ok());
+        }
+
+        Ok(bookmarks)
+    }
+
+    /// Unread and archived bookmarks carrying `tag`.
+    fn tagged(&self, tag: &str) -> BackendResult<Vec<Bookmark>> {
+        let query = format!("#{}", tag);
+        let mut bookmarks = self.bookmarks(BOOKMARKS, Some(&query))?;
+        bookmarks.extend(self.bookmarks(ARCHIVED_BOOKMARKS, Some(&query))?);
+        // Search also matches tags that only start with `tag`
+        bookmarks.retain(|b| b.tag_names.iter().any(|t| t == tag));
+        Ok(bookmarks)
+    }
+
+    fn bookmark(&self, item_id: u64) -> BackendResult<Bookmark> {
+        let url = self.http.url(&format!("{}{}/", BOOKMARKS, item_id), &[])?;
+        self.call(Method::Get, url, Body::Empty)
+    }
+
+    fn patch(&self, item_id: u64, patch: &BookmarkPatch) -> BackendResult<()> {
+        let url = self.http.url(&format!("{}{}/", BOOKMARKS, item_id), &[])?;
+        let _: Bookmark = self.call(Method::Patch, url, Body::json(patch)?)?;
+        Ok(())
+    }
+
+    fn set_tags(&self, item_id: u64, tags: Vec<String>) -> BackendResult<()> {
+        self.patch(
+            item_id,
+            &BookmarkPatch {
+                tag_names: Some(tags),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn edit_tags(&self, item_id: u64, f: impl FnOnce(&mut Vec<String>)) -> BackendResult<()> {
+        let mut tags = self.bookmark(item_id)?.tag_names;
+        f(&mut tags);
+        self.set_tags(item_id, tags)
+    }
+
+    fn post(&self, path: String) -> BackendResult<()> {
+        let url = self.http.url(&path, &[])?;
+        self.http
+            .request(Method::Post, url, Some(&self.authorization), Body::Empty)?;
+        Ok(())
+    }
+
+    fn add_bookmark(&self, url: &str, title: Option<&str>, tags: Vec<&str>) -> BackendResult<Item> {
+        let bookmark: Bookmark = self.call(
+            Method::Post,
+            self.http.url(BOOKMARKS, &[])?,
+            Body::json(&NewBookmark {
+                url,
+                title: title.unwrap_or_default(),
+                unread: true,
+                tag_names: tags,
+            })?,
+        )?;
+        Ok(bookmark.into())
+    }
+
+    /// Whether the action applied; actions linkding has no equivalent for don't.
+    fn apply(&self, action: &PocketSendAction) -> BackendResult<bool> {
+        match action {
+            PocketSendAction::Add {
+                url, title, tags, ..
+            } => match url {
+                Some(url) => {
+                    let tags = tags.as_deref().map(split_tags).unwrap_or_default();
+                    self.add_bookmark(url.as_str(), title.as_deref(), tags)
+                        .map(|_| ())
+                }
+                None => return Ok(false),
+            },
+            PocketSendAction::Archive { item_id, .. } => {
+                self.patch(
+                    *item_id,
+                    &BookmarkPatch {
+                        unread: Some(false),
+                        ..Default::default()
+                    },
+                )?;
+                self.post(format!("{}{}/archive/", BOOKMARKS, item_id))
+            }
+            PocketSendAction::Readd { item_id, .. } => {
+                self.post(format!("{}{}/unarchive/", BOOKMARKS, item_id))?;
+                self.patch(
+                    *item_id,
+                    &BookmarkPatch {
+                        unread: Some(true),
+                        ..Default::default()
+                    },
+                )
+            }
+            // linkding has no favorites
+            PocketSendAction::Favorite { .. } | PocketSendAction::Unfavorite { .. } => {
+                return Ok(false)
+            }
+            PocketSendAction::Delete { item_id, .. } => {
+                let url = self.http.url(&format!("{}{}/", BOOKMARKS, item_id), &[])?;
+                self.http
+                    .request(Method::Delete, url, Some(&self.authorization), Body::Empty)?;
+                Ok(())
+            }
+            PocketSendAction::TagsAdd { item_id, tags, .. } => self.edit_tags(*item_id, |t| {
+                for tag in split_tags(tags) {
+                    if !t.iter().any(|existing| existing == tag) {
+                        t.push(tag.to_string());
+                    }
+                }
+            }),
+            PocketSendAction::TagsRemove { item_id, tags, .. } => {
+                let remove = split_tags(tags);
+                self.edit_tags(*item_id, |t| {
+                    t.retain(|tag| !remove.contains(&tag.as_str()))
+                })
+            }
+            PocketSendAction::TagsReplace { item_id, tags, .. } => self.set_tags(
+                *item_id,
+                split_tags(tags).into_iter().map(String::from).collect(),
+            ),
+            PocketSendAction::TagsClear { item_id, .. } => self.set_tags(*item_id, Vec::new()),
+            PocketSendAction::TagRename {
+                old_tag, new_tag, ..
+            } => {
+                // linkding has no tag endpoint to rename with, so every bookmark is retagged
+                for bookmark in self.tagged(old_tag)? {
+                    let mut tags = bookmark
+                        .tag_names
+                        .into_iter()
+                        .filter(|t| t != old_tag)
+                        .collect::<Vec<_>>();
+                    if !tags.contains(new_tag) {
+                        tags.push(new_tag.clone());
+                    }
+                    self.set_tags(bookmark.id, tags)?;
+                }
+                Ok(())
+            }
+            PocketSendAction::TagDelete { tag, .. } => {
+                for bookmark in self.tagged(tag)? {
+                    let tags = bookmark
+                        .tag_names
+                        .into_iter()
+                        .filter(|t| t != tag)
+                        .collect();
+                    self.set_tags(bookmark.id, tags)?;
+                }
+                Ok(())
+            }
+        }?;
+        Ok(true)
+    }
+}
+
+impl PocketAdd for LinkdingBackend {
+    fn add(&self, request: &PocketAddRequest) -> BackendResult<Item> {
+        self.add_bookmark(
+            request.url.as_str(),
+            request.title,
+            request.tags.map(|t| t.to_vec()).unwrap_or_default(),
+        )
+    }
+}
+
+impl PocketGet for LinkdingBackend {
+    fn get(&self, opts: &GetOpts) -> BackendResult<Vec<Item>> {
+        let query = opts.tag.as_ref().map(|t| format!("#{}", t));
+        let mut bookmarks = Vec::new();
+        let state = opts.state.unwrap_or(PocketGetState::Unread);
+        if let PocketGetState::Unread | PocketGetState::All = state {
+            bookmarks.extend(self.bookmarks(BOOKMARKS, query.as_deref())?);
+        }
+        if let PocketGetState::Archive | PocketGetState::All = state {
+            bookmarks.extend(self.bookmarks(ARCHIVED_BOOKMARKS, query.as_deref())?);
+        }
+
+        Ok(filter::apply(bookmarks.into_iter().map(Item::from), opts))
+    }
+}
+
+impl PocketSend for LinkdingBackend {
+    fn send(&self, request: &PocketSendRequest) -> BackendResult<SendResponse> {
+        let mut action_results = Vec::new();
+        for action in request.actions {
+            action_results.push(match self.apply(action) {
+                Ok(applied) => applied,
+                // The bookmark is gone; anything else, such as a bad token, is an error
+                Err(BackendError::Response(404, _)) if on_item(action) => {
+                    self.check_url()?;
+                    false
+                }
+                Err(e) => return Err(e),
+            });
+        }
+
+        Ok(SendResponse {
+            status: 1,
+            action_results,
+        })
+    }
+}
+
+impl From<Bookmark> for Item {
+    fn from(b: Bookmark) -> Self {
+        let title = Some(b.title).filter(|t| !t.is_empty()).or(b.website_title);
+        let excerpt = Some(b.description)
+            .filter(|d| !d.is_empty())
+            .or(b.website_description)
+            .unwrap_or_default();
+        let url = Url::parse(&b.url).ok();
+
+        Item {
+            item_id: b.id,
+            given_url: url.clone(),
+            given_title: title.clone(),
+            word_count: 0,
+            excerpt,
+            time_added: b.date_added,
+            // linkding does not record when a bookmark was read, so a read bookmark's
+            // last change stands in
+            time_read: if b.unread { None } else { b.date_modified },
+            time_updated: b.date_modified,
+            time_favorited: None,
+            favorite: Some(false),
+            is_index: false,
+            is_article: true,
+            has_image: ItemHas::No,
+            has_video: ItemHas::No,
+            resolved_id: b.id,
+            resolved_title: title,
+            resolved_url: url,
+            sort_id: None,
+            status: Some(if b.is_archived {
+                ItemStatus::Archived
+            } else {
+                ItemStatus::Normal
+            }),
+            tags: if b.tag_names.is_empty() {
+                None
+            } else {
+                Some(b.tag_names.into_iter().map(Tag).collect())
+            },
+            images: None,
+            videos: None,
+            authors: None,
+            lang: None,
+            time_to_read: None,
+            domain_metadata: None,
+            listen_duration_estimate: None,
+            image: None,
+            amp_url: None,
+            top_image_url: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::http::mock::MockServer;
+
+    const BOOKMARK: &str = r#"{
+        "id": 7,
+        "url": "https://example.com/post",
+        "title": "",
+        "description": "",
+        "notes": "",
+        "website_title": "Post",
+        "website_description": "Hello linkding",
+        "is_archived": true,
+        "unread": false,
+        "shared": false,
+        "tag_names": ["rust"],
+        "date_added": "2020-01-02T03:04:05.123456Z",
+        "date_modified": "2020-01-03T03:04:05.123456Z"
+    }"#;
+
+    fn backend(server: &MockServer) -> LinkdingBackend {
+        LinkdingBackend::new(Url::parse(&server.url).unwrap(), "token")
+    }
+
+    fn page(bookmarks: &str) -> String {
+        format!(
+            r#"{{"count":1,"next":null,"previous":null,"results":[{}]}}"#,
+            bookmarks
+        )
+    }
+
+    #[test]
+    fn get_maps_bookmarks_to_items() {
+        let server = MockServer::start(|r| {
+            if r.path.starts_with("/api/bookmarks/archived/") {
+                (200, page(BOOKMARK))
+            } else {
+                (200, page(""))
+            }
+        });
+
+        let items = backend(&server)
+            .get(&GetOpts {
+                state: Some(PocketGetState::All),
+                detail_type: Some(pocket::PocketGetDetail::Complete),
+                ..Default::default()
+            })
+            .unwrap();
+
+        let requests = server.requests();
+        assert_eq!(2, requests.len());
+        assert!(requests[0].path.starts_with("/api/bookmarks/?limit=100"));
+        assert!(requests[1]
+            .path
+            .starts_with("/api/bookmarks/archived/?limit=100"));
+        assert_eq!(
+            Some("Token token"),
+            requests[0].headers.get("authorization").map(|h| h.as_str())
+        );
+        assert_eq!(1, items.len());
+        let item = &items[0];
+        assert_eq!(7, item.item_id);
+        assert_eq!(Some("Post"), item.title());
+        assert_eq!("Hello linkding", item.excerpt);
+        assert!(matches!(item.status, Some(ItemStatus::Archived)));
+        assert_eq!(vec!["rust"], item.tag_names());
+        assert_eq!(item.time_updated, item.time_read);
+    }
+
+    #[test]
+    fn get_leaves_unread_bookmarks_unread() {
+        let server = MockServer::start(|r| {
+            if r.path.starts_with("/api/bookmarks/archived/") {
+                (200, page(""))
+            } else {
+                let bookmark = BOOKMARK
+                    .replace(r#""is_archived": true"#, r#""is_archived": false"#)
+                    .replace(r#""unread": false"#, r#""unread": true"#);
+                (200, page(&bookmark))
+            }
+        });
+
+        let items = backend(&server).get(&GetOpts::default()).unwrap();
+
+        assert_eq!(None, items[0].time_read);
+    }
+
+    #[test]
+    fn send_archives_bookmarks_and_reports_failures() {
+        let server = MockServer::start(|r| match (r.method.as_str(), r.path.as_str()) {
+            ("PATCH", "/api/bookmarks/7/") => (200, BOOKMARK.to_string()),
+            ("POST", "/api/bookmarks/7/archive/") => (204, "".to_string()),
+            ("GET", "/api/bookmarks/?limit=1") => (200, page("")),
+            _ => (404, "{}".to_string()),
+        });
+
+        let response = backend(&server)
+            .send(&PocketSendRequest {
+                actions: &[
+                    &PocketSendAction::Archive {
+                        item_id: 7,
+                        time: None,
+                    },
+                    &PocketSendAction::Favorite {
+                        item_id: 7,
+                        time: None,
+                    },
+                    &PocketSendAction::Delete {
+                        item_id: 8,
+                        time: None,
+                    },
+                ],
+            })
+            .unwrap();
+
+        assert_eq!(vec![true, false, false], response.action_results);
+        let requests = server.requests();
+        assert_eq!(r#"{"unread":false}"#, requests[0].body);
+        assert_eq!("/api/bookmarks/7/archive/", requests[1].path);
+    }
+
+    #[test]
+    fn send_fails_on_wrong_url() {
+        let server = MockServer::start(|_| (404, "Not Found".to_string()));
+
+        let response = backend(&server).send(&PocketSendRequest {
+            actions: &[&PocketSendAction::Delete {
+                item_id: 7,
+                time: None,
+            }],
+        });
+
+        assert!(matches!(response, Err(BackendError::Response(404, _))));
+    }
+
+    #[test]
+    fn send_fails_on_rejected_token() {
+        let server = MockServer::start(|_| (401, r#"{"detail":"Invalid token."}"#.to_string()));
+
+        let response = backend(&server).send(&PocketSendRequest {
+            actions: &[&PocketSendAction::Archive {
+                item_id: 7,
+                time: None,
+            }],
+        });
+
+        assert!(matches!(response, Err(BackendError::Response(401, _))));
+    }
+
+    #[test]
+    fn tag_rename_retags_bookmarks() {
+        let server = MockServer::start(|r| match (r.method.as_str(), r.path.as_str()) {
+            ("GET", path) if path.starts_with("/api/bookmarks/archived/") => (200, page(BOOKMARK)),
+            ("GET", _) => (200, page("")),
+            ("PATCH", "/api/bookmarks/7/") => (200, BOOKMARK.to_string()),
+            _ => (404, "{}".to_string()),
+        });
+
+        let response = backend(&server)
+            .send(&PocketSendRequest {
+                actions: &[&PocketSendAction::TagRename {
+                    old_tag: "rust".to_string(),
+                    new_tag: "rust-lang".to_string(),
+                    time: None,
+                }],
+            })
+            .unwrap();
+
+        assert_eq!(vec![true], response.action_results);
+        let requests = server.requests();
+        assert!(requests[0].path.contains("q=%23rust"));
+        assert_eq!(r#"{"tag_names":["rust-lang"]}"#, requests[2].body);
+    }
+}
@@ -0,0 +1,598 @@
+use super::{filter, split_tags, BackendResult, PocketAdd, PocketGet, PocketSend, SendResponse};
+use crate::get::GetOpts;
+use crate::models::{Item, ItemHas, ItemStatus, Tag};
+use chrono::{DateTime, TimeZone, Utc};
+use directories::ProjectDirs;
+use pocket::{PocketAddRequest, PocketSendAction, PocketSendRequest};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use url::Url;
+
+const IMAGE_EXTENSIONS: &[&str] = &[".gif", ".jpeg", ".jpg", ".png", ".svg", ".webp"];
+const VIDEO_EXTENSIONS: &[&str] = &[".mp4", ".mov", ".webm"];
+const VIDEO_HOSTS: &[&str] = &["youtube.com", "youtu.be", "vimeo.com"];
+
+/// Items stored in a JSON file, for use without any server.
+pub struct LocalBackend {
+    path: PathBuf,
+}
+
+impl LocalBackend {
+    pub fn new(path: PathBuf) -> LocalBackend {
+        LocalBackend { path }
+    }
+
+    pub fn default_path() -> PathBuf {
+        ProjectDirs::from("", "", env!("CARGO_PKG_NAME"))
+            .expect("No home directory for local items.")
+            .data_dir()
+            .join("items.json")
+    }
+
+    fn load(&self) -> BackendResult<Store> {
+        match fs::read_to_string(&self.path) {
+            Ok(s) => Ok(serde_json::from_str(&s)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Store::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, store: &Store) -> BackendResult<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Write then rename so an interrupted save leaves the old items intact
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec(store)?)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+impl PocketAdd for LocalBackend {
+    fn add(&self, request: &PocketAddRequest) -> BackendResult<Item> {
+        let mut store = self.load()?;
+        let item = store.add(request, Utc::now());
+        self.save(&store)?;
+        Ok(item)
+    }
+}
+
+impl PocketGet for LocalBackend {
+    fn get(&self, opts: &GetOpts) -> BackendResult<Vec<Item>> {
+        Ok(self.load()?.get(opts))
+    }
+}
+
+impl PocketSend for LocalBackend {
+    fn send(&self, request: &PocketSendRequest) -> BackendResult<SendResponse> {
+        let mut store = self.load()?;
+        let response = store.send(request, Utc::now());
+        self.save(&store)?;
+        Ok(response)
+    }
+}
+
+/// Items and the id to give the next one, applying requests the way the Pocket API does.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Store {
+    next_item_id: u64,
+    items: Vec<Record>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Record {
+    item_id: u64,
+    #[serde(with = "url_serde")]
+    url: Url,
+    title: Option<String>,
+    tags: BTreeSet<String>,
+    archived: bool,
+    favorite: bool,
+    time_added: DateTime<Utc>,
+    time_updated: DateTime<Utc>,
+    time_read: Option<DateTime<Utc>>,
+    time_favorited: Option<DateTime<Utc>>,
+}
+
+impl Store {
+    pub fn add(&mut self, request: &PocketAddRequest, now: DateTime<Utc>) -> Item {
+        let tags = request.tags.unwrap_or_default();
+        let record = self.add_url(request.url, request.title, tags.iter().copied(), now);
+        record.to_item()
+    }
+
+    pub fn get(&self, opts: &GetOpts) -> Vec<Item> {
+        filter::apply(self.items.iter().map(|r| r.to_item()), opts)
+    }
+
+    pub fn send(&mut self, request: &PocketSendRequest, now: DateTime<Utc>) -> SendResponse {
+        let action_results = request
+            .actions
+            .iter()
+            .map(|action| self.apply(action, now))
+            .collect();
+
+        SendResponse {
+            status: 1,
+            action_results,
+        }
+    }
+
+    fn apply(&mut self, action: &PocketSendAction, now: DateTime<Utc>) -> bool {
+        let at = |time: &Option<u64>| time.map_or(now, |t| Utc.timestamp(t as i64, 0));
+
+        match action {
+            PocketSendAction::Add {
+                url, title, tags, ..
+            } => match url {
+                Some(url) => {
+                    let tags = tags.as_deref().map(split_tags).unwrap_or_default();
+                    self.add_url(url, title.as_deref(), tags.into_iter(), now);
+                    true
+                }
+                None => false,
+            },
+            PocketSendAction::Archive { item_id, time } => {
+                self.update(*item_id, at(time), |r, t| {
+                    r.archived = true;
+                    r.time_read = Some(t);
+                })
+            }
+            PocketSendAction::Readd { item_id, time } => self.update(*item_id, at(time), |r, _| {
+                r.archived = false;
+                r.time_read = None;
+            }),
+            PocketSendAction::Favorite { item_id, time } => {
+                self.update(*item_id, at(time), |r, t| {
+                    r.favorite = true;
+                    r.time_favorited = Some(t);
+                })
+            }
+            PocketSendAction::Unfavorite { item_id, time } => {
+                self.update(*item_id, at(time), |r, _| {
+                    r.favorite = false;
+                    r.time_favorited = None;
+                })
+            }
+            PocketSendAction::Delete { item_id, .. } => {
+                let len = self.items.len();
+                self.items.retain(|r| r.item_id != *item_id);
+                self.items.len() != len
+            }
+            PocketSendAction::TagsAdd {
+                item_id,
+                tags,
+                time,
+            } => self.update(*item_id, at(time), |r, _| {
+                r.tags
+                    .extend(split_tags(tags).into_iter().map(String::from));
+            }),
+            PocketSendAction::TagsRemove {
+                item_id,
+                tags,
+                time,
+            } => self.update(*item_id, at(time), |r, _| {
+                for tag in split_tags(tags) {
+                    r.tags.remove(tag);
+                }
+            }),
+            PocketSendAction::TagsReplace {
+                item_id,
+                tags,
+                time,
+            } => self.update(*item_id, at(time), |r, _| {
+                r.tags = split_tags(tags).into_iter().map(String::from).collect();
+            }),
+            PocketSendAction::TagsClear { item_id, time } => {
+                self.update(*item_id, at(time), |r, _| r.tags.clear())
+            }
+            PocketSendAction::TagRename {
+                old_tag,
+                new_tag,
+                time,
+            } => self.update_tagged(old_tag, at(time), |r| {
+                r.tags.remove(old_tag.as_str());
+                r.tags.insert(new_tag.clone());
+            }),
+            PocketSendAction::TagDelete { tag, time } => self.update_tagged(tag, at(time), |r| {
+                r.tags.remove(tag.as_str());
+            }),
+        }
+    }
+
+    /// Saving a URL that is already saved re-adds it to the list, as Pocket does.
+    fn add_url<'a>(
+        &mut self,
+        url: &Url,
+        title: Option<&str>,
+        tags: impl Iterator<Item = &'a str>,
+        now: DateTime<Utc>,
+    ) -> &Record {
+        let index = match self.items.iter().position(|r| &r.url == url) {
+            Some(index) => index,
+            None => {
+                self.next_item_id += 1;
+                self.items.push(Record {
+                    item_id: self.next_item_id,
+                    url: url.clone(),
+                    title: None,
+                    tags: BTreeSet::new(),
+                    archived: false,
+                    favorite: false,
+                    time_added: now,
+                    time_updated: now,
+                    time_read: None,
+                    time_favorited: None,
+                });
+                self.items.len() - 1
+            }
+        };
+
+        let record = &mut self.items[index];
+        if let Some(title) = title {
+            record.title = Some(title.to_string());
+        }
+        record.tags.extend(tags.map(String::from));
+        record.archived = false;
+        record.time_read = None;
+        record.time_updated = now;
+        record
+    }
+
+    fn update(
+        &mut self,
+        item_id: u64,
+        time: DateTime<Utc>,
+        f: impl FnOnce(&mut Record, DateTime<Utc>),
+    ) -> bool {
+        match self.items.iter_mut().find(|r| r.item_id == item_id) {
+            Some(record) => {
+                f(record, time);
+                record.time_updated = time;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn update_tagged(&mut self, tag: &str, time: DateTime<Utc>, f: impl Fn(&mut Record)) -> bool {
+        let mut found = false;
+        for record in self.items.iter_mut().filter(|r| r.tags.contains(tag)) {
+            f(record);
+            record.time_updated = time;
+            found = true;
+        }
+        found
+    }
+}
+
+impl Record {
+    fn domain(&self) -> &str {
+        self.url
+            .host_str()
+            .unwrap_or_default()
+            .trim_start_matches("www.")
+    }
+
+    /// Nothing is fetched, so the content type is guessed from the URL.
+    fn content_type(&self) -> ContentType {
+        let path = self.url.path().to_lowercase();
+        if IMAGE_EXTENSIONS.iter().any(|e| path.ends_with(e)) {
+            ContentType::Image
+        } else if VIDEO_EXTENSIONS.iter().any(|e| path.ends_with(e))
+            || VIDEO_HOSTS.contains(&self.domain())
+        {
+            ContentType::Video
+        } else {
+            ContentType::Article
+        }
+    }
+
+    fn to_item(&self) -> Item {
+        let content_type = self.content_type();
+        Item {
+            item_id: self.item_id,
+            given_url: Some(self.url.clone()),
+            given_title: self.title.clone(),
+            word_count: 0,
+            excerpt: "".to_string(),
+            time_added: Some(self.time_added),
+            time_read: self.time_read,
+            time_updated: Some(self.time_updated),
+            time_favorited: self.time_favorited,
+            favorite: Some(self.favorite),
+            is_index: false,
+            is_article: content_type == ContentType::Article,
+            has_image: match content_type {
+                ContentType::Image => ItemHas::Is,
+                _ => ItemHas::No,
+            },
+            has_video: match content_type {
+                ContentType::Video => ItemHas::Is,
+                _ => ItemHas::No,
+            },
+            resolved_id: self.item_id,
+            resolved_title: self.title.clone(),
+            resolved_url: Some(self.url.clone()),
+            sort_id: None,
+            status: Some(if self.archived {
+                ItemStatus::Archived
+            } else {
+                ItemStatus::Normal
+            }),
+            tags: if !self.tags.is_empty() {
+                Some(self.tags.iter().map(|t| Tag(t.clone())).collect())
+            } else {
+                None
+            },
+            images: None,
+            videos: None,
+            authors: None,
+            lang: None,
+            time_to_read: None,
+            domain_metadata: None,
+            listen_duration_estimate: None,
+            image: None,
+            amp_url: None,
+            top_image_url: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ContentType {
+    Article,
+    Image,
+    Video,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pocket::{PocketGetDetail, PocketGetSort, PocketGetState, PocketGetType};
+
+    fn store() -> Store {
+        let mut store = Store::default();
+        let now = Utc.ymd(2020, 1, 1).and_hms(0, 0, 0);
+        for (i, url) in [
+            "https://www.example.com/a",
+            "https://blog.example.org/b.png",
+        ]
+        .iter()
+        .enumerate()
+        {
+            let url = Url::parse(url).unwrap();
+            store.add(
+                &PocketAddRequest {
+                    url: &url,
+                    title: Some(if i == 0 { "Alpha" } else { "Beta" }),
+                    tags: Some(&["rust"][..]),
+                    tweet_id: None,
+                },
+                now + chrono::Duration::days(i as i64),
+            );
+        }
+        store
+    }
+
+    fn ids(items: Vec<Item>) -> Vec<u64> {
+        items.into_iter().map(|i| i.item_id).collect()
+    }
+
+    fn send(store: &mut Store, action: PocketSendAction) -> bool {
+        let response = store.send(
+            &PocketSendRequest {
+                actions: &[&action],
+            },
+            Utc::now(),
+        );
+        response.action_results[0]
+    }
+
+    #[test]
+    fn add_assigns_ids_and_readds_existing_urls() {
+        let mut store = store();
+        let url = Url::parse("https://www.example.com/a").unwrap();
+
+        let item = store.add(
+            &PocketAddRequest {
+                url: &url,
+                title: None,
+                tags: None,
+                tweet_id: None,
+            },
+            Utc::now(),
+        );
+
+        assert_eq!(1, item.item_id);
+        assert_eq!(Some("Alpha"), item.title());
+        assert_eq!(2, store.items.len());
+    }
+
+    #[test]
+    fn get_sorts_and_paginates() {
+        let store = store();
+        let opts = |sort, offset, count| GetOpts {
+            sort: Some(sort),
+            offset,
+            count,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            vec![2, 1],
+            ids(store.get(&opts(PocketGetSort::Newest, None, None)))
+        );
+        assert_eq!(
+            vec![1, 2],
+            ids(store.get(&opts(PocketGetSort::Oldest, None, None)))
+        );
+        assert_eq!(
+            vec![2],
+            ids(store.get(&opts(PocketGetSort::Site, None, Some(1))))
+        );
+        assert_eq!(
+            vec![2],
+            ids(store.get(&opts(PocketGetSort::Title, Some(1), None)))
+        );
+    }
+
+    #[test]
+    fn get_filters() {
+        let mut store = store();
+        send(
+            &mut store,
+            PocketSendAction::Archive {
+                item_id: 2,
+                time: None,
+            },
+        );
+        send(
+            &mut store,
+            PocketSendAction::TagsClear {
+                item_id: 1,
+                time: None,
+            },
+        );
+
+        let get = |opts: GetOpts| ids(store.get(&opts));
+
+        assert_eq!(vec![1], get(GetOpts::default()));
+        assert_eq!(
+            vec![2],
+            get(GetOpts {
+                state: Some(PocketGetState::Archive),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            vec![2],
+            get(GetOpts {
+                state: Some(PocketGetState::All),
+                tag: Some("rust".to_string()),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            vec![1],
+            get(GetOpts {
+                untagged: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            vec![2],
+            get(GetOpts {
+                state: Some(PocketGetState::All),
+                domain: Some("example.org".to_string()),
+                content_type: Some(PocketGetType::Image),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            vec![1],
+            get(GetOpts {
+                search: Some("alp".to_string()),
+                ..Default::default()
+            })
+        );
+        assert!(get(GetOpts {
+            favorite: Some(true),
+            ..Default::default()
+        })
+        .is_empty());
+    }
+
+    #[test]
+    fn get_includes_tags_only_with_complete_detail() {
+        let store = store();
+
+        let simple = store.get(&GetOpts::default());
+        let complete = store.get(&GetOpts {
+            detail_type: Some(PocketGetDetail::Complete),
+            ..Default::default()
+        });
+
+        assert!(simple[0].tags.is_none());
+        assert_eq!(vec!["rust"], complete[0].tag_names());
+    }
+
+    #[test]
+    fn send_applies_tag_actions() {
+        let mut store = store();
+
+        assert!(send(
+            &mut store,
+            PocketSendAction::TagRename {
+                old_tag: "rust".to_string(),
+                new_tag: "rust-lang".to_string(),
+                time: None,
+            }
+        ));
+        assert!(send(
+            &mut store,
+            PocketSendAction::TagsAdd {
+                item_id: 1,
+                tags: "a, b".to_string(),
+                time: None,
+            }
+        ));
+        assert!(send(
+            &mut store,
+            PocketSendAction::TagsRemove {
+                item_id: 1,
+                tags: "a".to_string(),
+                time: None,
+            }
+        ));
+        assert!(send(
+            &mut store,
+            PocketSendAction::TagDelete {
+                tag: "rust-lang".to_string(),
+                time: None,
+            }
+        ));
+
+        let tags = |item_id| {
+            store
+                .items
+                .iter()
+                .find(|r| r.item_id == item_id)
+                .map(|r| r.tags.iter().cloned().collect::<Vec<_>>())
+                .unwrap()
+        };
+        assert_eq!(vec!["b"], tags(1));
+        assert!(tags(2).is_empty());
+    }
+
+    #[test]
+    fn send_fails_for_unknown_items() {
+        let mut store = store();
+
+        assert!(!send(
+            &mut store,
+            PocketSendAction::Favorite {
+                item_id: 9,
+                time: None
+            }
+        ));
+        assert!(send(
+            &mut store,
+            PocketSendAction::Delete {
+                item_id: 1,
+                time: None
+            }
+        ));
+        assert!(!send(
+            &mut store,
+            PocketSendAction::Delete {
+                item_id: 1,
+                time: None
+            }
+        ));
+    }
+}
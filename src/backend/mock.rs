@@ -0,0 +1,69 @@
+use super::{BackendResult, PocketAdd, PocketGet, PocketSend, SendResponse};
+use crate::get::GetOpts;
+use crate::models::Item;
+use pocket::{PocketAddRequest, PocketSendRequest};
+
+/// A backend answering every call with a closure, for testing commands.
+pub struct PocketMock<G, S, A>
+where
+    G: Fn(&GetOpts) -> BackendResult<Vec<Item>>,
+    S: Fn(&PocketSendRequest) -> BackendResult<SendResponse>,
+    A: Fn(&PocketAddRequest) -> BackendResult<Item>,
+{
+    pub get_mock: G,
+    pub send_mock: S,
+    pub add_mock: A,
+}
+
+impl<G, S, A> PocketGet for PocketMock<G, S, A>
+where
+    G: Fn(&GetOpts) -> BackendResult<Vec<Item>>,
+    S: Fn(&PocketSendRequest) -> BackendResult<SendResponse>,
+    A: Fn(&PocketAddRequest) -> BackendResult<Item>,
+{
+    fn get(&self, opts: &GetOpts) -> BackendResult<Vec<Item>> {
+        (self.get_mock)(opts)
+    }
+}
+
+impl<G, S, A> PocketSend for PocketMock<G, S, A>
+where
+    G: Fn(&GetOpts) -> BackendResult<Vec<Item>>,
+    S: Fn(&PocketSendRequest) -> BackendResult<SendResponse>,
+    A: Fn(&PocketAddRequest) -> BackendResult<Item>,
+{
+    fn send(&self, request: &PocketSendRequest) -> BackendResult<SendResponse> {
+        (self.send_mock)(request)
+    }
+}
+
+impl<G, S, A> PocketAdd for PocketMock<G, S, A>
+where
+    G: Fn(&GetOpts) -> BackendResult<Vec<Item>>,
+    S: Fn(&PocketSendRequest) -> BackendResult<SendResponse>,
+    A: Fn(&PocketAddRequest) -> BackendResult<Item>,
+{
+    fn add(&self, request: &PocketAddRequest) -> BackendResult<Item> {
+        (self.add_mock)(request)
+    }
+}
+
+pub fn unused_get(_: &GetOpts) -> BackendResult<Vec<Item>> {
+    panic!("nothing should be fetched");
+}
+
+pub fn unused_send(_: &PocketSendRequest) -> BackendResult<SendResponse> {
+    panic!("nothing should be sent");
+}
+
+pub fn unused_add(_: &PocketAddRequest) -> BackendResult<Item> {
+    panic!("nothing should be added");
+}
+
+/// A response to `request` in which every action succeeded.
+pub fn succeed(request: &PocketSendRequest) -> SendResponse {
+    SendResponse {
+        status: 1,
+        action_results: vec![true; request.actions.len()],
+    }
+}
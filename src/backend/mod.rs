@@ -0,0 +1,284 @@
+use crate::config::Config;
+use crate::get::GetOpts;
+use crate::models::Item;
+use pocket::{
+    Pocket, PocketAddRequest, PocketError, PocketSendAction, PocketSendRequest, PocketSendResponse,
+};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use url::Url;
+
+pub mod filter;
+mod getpocket;
+mod http;
+pub mod linkding;
+pub mod local;
+#[cfg(test)]
+pub mod mock;
+pub mod v3;
+pub mod wallabag;
+
+pub type BackendResult<T> = Result<T, BackendError>;
+
+pub trait PocketAdd {
+    fn add(&self, request: &PocketAddRequest) -> BackendResult<Item>;
+}
+
+pub trait PocketGet {
+    fn get(&self, opts: &GetOpts) -> BackendResult<Vec<Item>>;
+}
+
+pub trait PocketSend {
+    fn send(&self, request: &PocketSendRequest) -> BackendResult<SendResponse>;
+}
+
+/// Everything the CLI needs from the service it stores items in.
+pub trait Backend: PocketAdd + PocketGet + PocketSend {}
+
+impl<T: PocketAdd + PocketGet + PocketSend> Backend for T {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    Pocket,
+    Local,
+    Wallabag,
+    Linkding,
+}
+
+impl Default for BackendKind {
+    fn default() -> Self {
+        BackendKind::Pocket
+    }
+}
+
+impl FromStr for BackendKind {
+    type Err = ParseBackendKindError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pocket" => Ok(BackendKind::Pocket),
+            "local" => Ok(BackendKind::Local),
+            "wallabag" => Ok(BackendKind::Wallabag),
+            "linkding" => Ok(BackendKind::Linkding),
+            s => Err(ParseBackendKindError(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let display = match self {
+            BackendKind::Pocket => "pocket",
+            BackendKind::Local => "local",
+            BackendKind::Wallabag => "wallabag",
+            BackendKind::Linkding => "linkding",
+        };
+        fmt::Display::fmt(display, f)
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseBackendKindError(String);
+
+impl fmt::Display for ParseBackendKindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "provided invalid backend: {}", self.0)
+    }
+}
+
+/// The backend selected by configuration.
+pub enum AnyBackend {
+    Pocket(Pocket),
+    V3(v3::V3Backend),
+    Local(local::LocalBackend),
+    Wallabag(wallabag::WallabagBackend),
+    Linkding(linkding::LinkdingBackend),
+}
+
+pub fn connect(
+    kind: BackendKind,
+    cfg: &Config,
+    api_base_url: Option<Url>,
+    consumer_key: impl FnOnce() -> String,
+    access_token: Option<String>,
+) -> AnyBackend {
+    match kind {
+        BackendKind::Pocket => {
+            let access_token = access_token.expect("Access token missing.");
+            match api_base_url {
+                Some(url) => AnyBackend::V3(v3::V3Backend::new(url, consumer_key(), access_token)),
+                None => AnyBackend::Pocket(Pocket::new(&consumer_key(), &access_token)),
+            }
+        }
+        BackendKind::Local => AnyBackend::Local(local::LocalBackend::new(
+            cfg.local_path
+                .clone()
+                .unwrap_or_else(local::LocalBackend::default_path),
+        )),
+        BackendKind::Wallabag => AnyBackend::Wallabag(wallabag::WallabagBackend::new(
+            required(&cfg.wallabag_url, "wallabag_url"),
+            wallabag::Credentials {
+                client_id: required(&cfg.wallabag_client_id, "wallabag_client_id"),
+                client_secret: required(&cfg.wallabag_client_secret, "wallabag_client_secret"),
+                username: cfg.wallabag_username.clone(),
+                password: cfg.wallabag_password.clone(),
+            },
+        )),
+        BackendKind::Linkding => AnyBackend::Linkding(linkding::LinkdingBackend::new(
+            required(&cfg.linkding_url, "linkding_url"),
+            &required(&cfg.linkding_token, "linkding_token"),
+        )),
+    }
+}
+
+fn required<T: Clone>(value: &Option<T>, key: &str) -> T {
+    value
+        .clone()
+        .unwrap_or_else(|| panic!("Config `{}` missing.", key))
+}
+
+impl PocketAdd for AnyBackend {
+    fn add(&self, request: &PocketAddRequest) -> BackendResult<Item> {
+        match self {
+            AnyBackend::Pocket(b) => PocketAdd::add(b, request),
+            AnyBackend::V3(b) => b.add(request),
+            AnyBackend::Local(b) => b.add(request),
+            AnyBackend::Wallabag(b) => b.add(request),
+            AnyBackend::Linkding(b) => b.add(request),
+        }
+    }
+}
+
+impl PocketGet for AnyBackend {
+    fn get(&self, opts: &GetOpts) -> BackendResult<Vec<Item>> {
+        match self {
+            AnyBackend::Pocket(b) => PocketGet::get(b, opts),
+            AnyBackend::V3(b) => b.get(opts),
+            AnyBackend::Local(b) => b.get(opts),
+            AnyBackend::Wallabag(b) => b.get(opts),
+            AnyBackend::Linkding(b) => b.get(opts),
+        }
+    }
+}
+
+impl PocketSend for AnyBackend {
+    fn send(&self, request: &PocketSendRequest) -> BackendResult<SendResponse> {
+        match self {
+            AnyBackend::Pocket(b) => PocketSend::send(b, request),
+            AnyBackend::V3(b) => b.send(request),
+            AnyBackend::Local(b) => b.send(request),
+            AnyBackend::Wallabag(b) => b.send(request),
+            AnyBackend::Linkding(b) => b.send(request),
+        }
+    }
+}
+
+/// Pocket rejects requests with too many actions, so larger sends are split up.
+pub const SEND_BATCH_SIZE: usize = 100;
+
+/// Send `actions` in as many requests as it takes, with the results of all of them.
+pub fn send_all(
+    pocket: &impl PocketSend,
+    actions: &[PocketSendAction],
+) -> BackendResult<SendResponse> {
+    let mut response = SendResponse {
+        status: 1,
+        action_results: Vec::with_capacity(actions.len()),
+    };
+    for batch in actions.chunks(SEND_BATCH_SIZE) {
+        let batch = batch.iter().collect::<Vec<_>>();
+        let batch_response = pocket.send(&PocketSendRequest { actions: &batch })?;
+        response.status = batch_response.status;
+        response
+            .action_results
+            .extend(batch_response.action_results);
+    }
+    Ok(response)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SendResponse {
+    pub status: u16,
+    pub action_results: Vec<bool>,
+}
+
+impl From<PocketSendResponse> for SendResponse {
+    fn from(p: PocketSendResponse) -> Self {
+        SendResponse {
+            status: p.status,
+            action_results: p.action_results,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum BackendError {
+    Pocket(PocketError),
+    Io(io::Error),
+    Json(serde_json::Error),
+    Http(hyper::Error),
+    /// A response with an unsuccessful status and its body
+    Response(u16, String),
+}
+
+impl Error for BackendError {}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendError::Pocket(e) => write!(f, "pocket error: {:?}", e),
+            BackendError::Io(e) => write!(f, "io error: {}", e),
+            BackendError::Json(e) => write!(f, "json error: {}", e),
+            BackendError::Http(e) => write!(f, "http error: {}", e),
+            BackendError::Response(status, body) => {
+                write!(f, "unexpected response ({}): {}", status, body)
+            }
+        }
+    }
+}
+
+impl From<PocketError> for BackendError {
+    fn from(e: PocketError) -> Self {
+        BackendError::Pocket(e)
+    }
+}
+
+impl From<io::Error> for BackendError {
+    fn from(e: io::Error) -> Self {
+        BackendError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for BackendError {
+    fn from(e: serde_json::Error) -> Self {
+        BackendError::Json(e)
+    }
+}
+
+impl From<hyper::Error> for BackendError {
+    fn from(e: hyper::Error) -> Self {
+        BackendError::Http(e)
+    }
+}
+
+/// Split the comma separated tags of a send action.
+fn split_tags(tags: &str) -> Vec<&str> {
+    tags.split(',')
+        .map(|t| t.trim())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+/// Whether `action` changes one saved item, so that a 404 for it means the item is gone.
+fn on_item(action: &PocketSendAction) -> bool {
+    !matches!(
+        action,
+        PocketSendAction::Add { .. }
+            | PocketSendAction::TagRename { .. }
+            | PocketSendAction::TagDelete { .. }
+    )
+}
@@ -0,0 +1,860 @@
+use super::http::{Body, Http};
+use super::{split_tags, BackendResult, PocketAdd, PocketGet, PocketSend, SendResponse};
+use crate::get::{self, GetOpts};
+use crate::models::{Item, ItemHas, ItemStatus, Tag};
+use chrono::{DateTime, TimeZone, Utc};
+use hyper::method::Method;
+use pocket::{
+    PocketAddRequest, PocketGetDetail, PocketGetSort, PocketGetState, PocketGetType,
+    PocketSendAction, PocketSendRequest, PocketUser,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use url::Url;
+
+const UNTAGGED: &str = "_untagged_";
+
+/// The Pocket v3 API at any base URL, such as a compatible clone or `pocket serve-mock`.
+pub struct V3Backend {
+    http: Http,
+    consumer_key: String,
+    access_token: String,
+}
+
+impl V3Backend {
+    pub fn new(base_url: Url, consumer_key: String, access_token: String) -> V3Backend {
+        V3Backend {
+            http: Http::new(base_url),
+            consumer_key,
+            access_token,
+        }
+    }
+
+    fn call<T: Serialize, R: DeserializeOwned>(&self, path: &str, request: T) -> BackendResult<R> {
+        let body = Body::json(&Authenticated {
+            consumer_key: self.consumer_key.clone(),
+            access_token: self.access_token.clone(),
+            request,
+        })?;
+        self.http
+            .json(Method::Post, self.http.url(path, &[])?, None, body)
+    }
+}
+
+impl PocketAdd for V3Backend {
+    fn add(&self, request: &PocketAddRequest) -> BackendResult<Item> {
+        let response: AddResponse = self.call(
+            "v3/add",
+            AddRequest {
+                url: request.url.to_string(),
+                title: request.title.map(String::from),
+                tags: request.tags.map(|t| t.join(",")),
+                tweet_id: request.tweet_id.map(String::from),
+            },
+        )?;
+        Ok(response.item.into())
+    }
+}
+
+impl PocketGet for V3Backend {
+    fn get(&self, opts: &GetOpts) -> BackendResult<Vec<Item>> {
+        let response: GetResponse = self.call("v3/get", GetRequest::from(opts))?;
+        let mut items = match response.list {
+            List::Map(items) => items.into_iter().map(|(_, item)| item).collect(),
+            List::Seq(items) => items,
+        };
+        // The list is keyed by item id, so the requested order only survives in `sort_id`
+        items.sort_by_key(|item| item.sort_id);
+        Ok(items.into_iter().map(Item::from).collect())
+    }
+}
+
+impl PocketSend for V3Backend {
+    fn send(&self, request: &PocketSendRequest) -> BackendResult<SendResponse> {
+        let response: SendResult = self.call(
+            "v3/send",
+            SendRequest {
+                actions: request
+                    .actions
+                    .iter()
+                    .map(|a| WireAction::from(*a))
+                    .collect(),
+            },
+        )?;
+        Ok(SendResponse {
+            status: response.status,
+            // Pocket answers an add action with the added item rather than `true`
+            action_results: response
+                .action_results
+                .iter()
+                .map(|r| !matches!(r, serde_json::Value::Bool(false) | serde_json::Value::Null))
+                .collect(),
+        })
+    }
+}
+
+/// The OAuth flow of the Pocket v3 API at any base URL.
+pub struct V3Authentication {
+    http: Http,
+    consumer_key: String,
+    redirect_uri: String,
+}
+
+impl V3Authentication {
+    pub fn new(base_url: Url, consumer_key: &str, redirect_uri: &str) -> V3Authentication {
+        V3Authentication {
+            http: Http::new(base_url),
+            consumer_key: consumer_key.to_string(),
+            redirect_uri: redirect_uri.to_string(),
+        }
+    }
+
+    pub fn request(&self, state: Option<&str>) -> BackendResult<String> {
+        let response: OAuthCode = self.oauth(
+            "v3/oauth/request",
+            &OAuthRequest {
+                consumer_key: self.consumer_key.clone(),
+                redirect_uri: Some(self.redirect_uri.clone()),
+                code: None,
+                state: state.map(String::from),
+            },
+        )?;
+        Ok(response.code)
+    }
+
+    pub fn authorize_url(&self, code: &str) -> BackendResult<Url> {
+        self.http.url(
+            "auth/authorize",
+            &[
+                ("request_token", code.to_string()),
+                ("redirect_uri", self.redirect_uri.clone()),
+            ],
+        )
+    }
+
+    pub fn authorize(&self, code: &str, state: Option<&str>) -> BackendResult<PocketUser> {
+        let response: OAuthUser = self.oauth(
+            "v3/oauth/authorize",
+            &OAuthRequest {
+                consumer_key: self.consumer_key.clone(),
+                redirect_uri: None,
+                code: Some(code.to_string()),
+                state: state.map(String::from),
+            },
+        )?;
+        Ok(PocketUser {
+            consumer_key: self.consumer_key.clone(),
+            access_token: response.access_token,
+            username: response.username,
+        })
+    }
+
+    /// Pocket answers OAuth calls with a form unless asked for JSON, so both are accepted.
+    fn oauth<R: DeserializeOwned>(&self, path: &str, request: &OAuthRequest) -> BackendResult<R> {
+        let body = self.http.request(
+            Method::Post,
+            self.http.url(path, &[])?,
+            None,
+            Body::json(request)?,
+        )?;
+        match serde_json::from_str(&body) {
+            Ok(response) => Ok(response),
+            Err(e) => {
+                let form: BTreeMap<String, String> = url::form_urlencoded::parse(body.as_bytes())
+                    .into_owned()
+                    .collect();
+                serde_json::to_value(form)
+                    .and_then(serde_json::from_value)
+                    .map_err(|_| e.into())
+            }
+        }
+    }
+}
+
+/// A request body with the credentials every call carries.
+#[derive(Serialize, Deserialize)]
+pub struct Authenticated<T> {
+    pub consumer_key: String,
+    pub access_token: String,
+    #[serde(flatten)]
+    pub request: T,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OAuthRequest {
+    pub consumer_key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redirect_uri: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OAuthCode {
+    pub code: String,
+    pub state: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OAuthUser {
+    pub access_token: String,
+    pub username: String,
+    pub state: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AddRequest {
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tweet_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AddResponse {
+    pub item: WireItem,
+    pub status: u16,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GetRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    #[serde(rename = "contentType", skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    #[serde(rename = "detailType", skip_serializing_if = "Option::is_none")]
+    pub detail_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub favorite: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+}
+
+impl From<&GetOpts> for GetRequest {
+    fn from(opts: &GetOpts) -> Self {
+        GetRequest {
+            search: opts.search.clone(),
+            domain: opts.domain.clone(),
+            tag: if opts.untagged {
+                Some(UNTAGGED.to_string())
+            } else {
+                opts.tag.clone()
+            },
+            state: opts.state.map(|s| {
+                match s {
+                    PocketGetState::Unread => "unread",
+                    PocketGetState::Archive => "archive",
+                    PocketGetState::All => "all",
+                }
+                .to_string()
+            }),
+            content_type: opts.content_type.map(|t| {
+                match t {
+                    PocketGetType::Article => "article",
+                    PocketGetType::Video => "video",
+                    PocketGetType::Image => "image",
+                }
+                .to_string()
+            }),
+            detail_type: opts.detail_type.map(|d| {
+                match d {
+                    PocketGetDetail::Simple => "simple",
+                    PocketGetDetail::Complete => "complete",
+                }
+                .to_string()
+            }),
+            favorite: opts.favorite.map(|f| flag(f).to_string()),
+            since: opts.since.map(|s| s.timestamp()),
+            sort: opts.sort.map(|s| {
+                match s {
+                    PocketGetSort::Newest => "newest",
+                    PocketGetSort::Oldest => "oldest",
+                    PocketGetSort::Title => "title",
+                    PocketGetSort::Site => "site",
+                }
+                .to_string()
+            }),
+            count: opts.count,
+            offset: opts.offset,
+        }
+    }
+}
+
+impl GetRequest {
+    pub fn to_opts(&self) -> Result<GetOpts, io::Error> {
+        let untagged = self.tag.as_deref() == Some(UNTAGGED);
+        Ok(GetOpts {
+            search: self.search.clone(),
+            domain: self.domain.clone(),
+            tag: self.tag.clone().filter(|_| !untagged),
+            untagged,
+            state: self
+                .state
+                .as_deref()
+                .map(get::parse_get_state)
+                .transpose()?,
+            content_type: self
+                .content_type
+                .as_deref()
+                .map(get::parse_get_content_type)
+                .transpose()?,
+            detail_type: self
+                .detail_type
+                .as_deref()
+                .map(get::parse_get_detail_type)
+                .transpose()?,
+            favorite: self.favorite.as_deref().map(|f| f == "1"),
+            since: self.since.map(|s| Utc.timestamp(s, 0)),
+            sort: self.sort.as_deref().map(get::parse_get_sort).transpose()?,
+            count: self.count,
+            offset: self.offset,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub list: List,
+}
+
+/// Pocket sends an empty array rather than an empty object when nothing matches.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum List {
+    Map(BTreeMap<String, WireItem>),
+    Seq(Vec<WireItem>),
+}
+
+impl Default for List {
+    fn default() -> Self {
+        List::Seq(Vec::new())
+    }
+}
+
+/// An item as the v3 API encodes it, with numbers and flags as strings.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WireItem {
+    #[serde(with = "number")]
+    pub item_id: u64,
+    #[serde(with = "number")]
+    pub resolved_id: u64,
+    pub given_url: String,
+    pub given_title: String,
+    pub resolved_url: String,
+    pub resolved_title: String,
+    pub excerpt: String,
+    #[serde(with = "number")]
+    pub word_count: u64,
+    #[serde(with = "number")]
+    pub favorite: u64,
+    #[serde(with = "number")]
+    pub status: u64,
+    #[serde(with = "number")]
+    pub is_index: u64,
+    #[serde(with = "number")]
+    pub is_article: u64,
+    #[serde(with = "number")]
+    pub has_image: u64,
+    #[serde(with = "number")]
+    pub has_video: u64,
+    #[serde(with = "number")]
+    pub time_added: u64,
+    #[serde(with = "number")]
+    pub time_updated: u64,
+    #[serde(with = "number")]
+    pub time_read: u64,
+    #[serde(with = "number")]
+    pub time_favorited: u64,
+    #[serde(with = "number")]
+    pub sort_id: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<BTreeMap<String, WireTag>>,
+    pub lang: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_to_read: Option<u64>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct WireTag {
+    pub item_id: String,
+    pub tag: String,
+}
+
+impl WireItem {
+    pub fn new(item: &Item, sort_id: u64) -> WireItem {
+        let url = |url: &Option<Url>| url.as_ref().map(Url::to_string).unwrap_or_default();
+
+        WireItem {
+            item_id: item.item_id,
+            resolved_id: item.resolved_id,
+            given_url: url(&item.given_url),
+            given_title: item.given_title.clone().unwrap_or_default(),
+            resolved_url: url(&item.resolved_url),
+            resolved_title: item.resolved_title.clone().unwrap_or_default(),
+            excerpt: item.excerpt.clone(),
+            word_count: item.word_count as u64,
+            favorite: flag(item.favorite.unwrap_or(false)),
+            status: match item.status {
+                Some(ItemStatus::Archived) => 1,
+                Some(ItemStatus::Deleted) => 2,
+                _ => 0,
+            },
+            is_index: flag(item.is_index),
+            is_article: flag(item.is_article),
+            has_image: has(&item.has_image),
+            has_video: has(&item.has_video),
+            time_added: timestamp(item.time_added),
+            time_updated: timestamp(item.time_updated),
+            time_read: timestamp(item.time_read),
+            time_favorited: timestamp(item.time_favorited),
+            sort_id,
+            tags: item.tags.as_ref().map(|tags| {
+                tags.iter()
+                    .map(|t| {
+                        let tag = WireTag {
+                            item_id: item.item_id.to_string(),
+                            tag: t.0.clone(),
+                        };
+                        (t.0.clone(), tag)
+                    })
+                    .collect()
+            }),
+            lang: item.lang.clone().unwrap_or_default(),
+            time_to_read: item.time_to_read,
+        }
+    }
+}
+
+impl From<WireItem> for Item {
+    fn from(w: WireItem) -> Self {
+        let non_empty = |s: String| Some(s).filter(|s| !s.is_empty());
+        let given_url = Url::parse(&w.given_url).ok();
+
+        Item {
+            item_id: w.item_id,
+            resolved_url: Url::parse(&w.resolved_url)
+                .ok()
+                .or_else(|| given_url.clone()),
+            given_url,
+            given_title: non_empty(w.given_title),
+            word_count: w.word_count as usize,
+            excerpt: w.excerpt,
+            time_added: time(w.time_added),
+            time_read: time(w.time_read),
+            time_updated: time(w.time_updated),
+            time_favorited: time(w.time_favorited),
+            favorite: Some(w.favorite == 1),
+            is_index: w.is_index == 1,
+            is_article: w.is_article == 1,
+            has_image: item_has(w.has_image),
+            has_video: item_has(w.has_video),
+            resolved_id: if w.resolved_id == 0 {
+                w.item_id
+            } else {
+                w.resolved_id
+            },
+            resolved_title: non_empty(w.resolved_title),
+            sort_id: Some(w.sort_id),
+            status: Some(match w.status {
+                1 => ItemStatus::Archived,
+                2 => ItemStatus::Deleted,
+                _ => ItemStatus::Normal,
+            }),
+            tags: w
+                .tags
+                .filter(|tags| !tags.is_empty())
+                .map(|tags| tags.into_iter().map(|(name, _)| Tag(name)).collect()),
+            images: None,
+            videos: None,
+            authors: None,
+            lang: non_empty(w.lang),
+            time_to_read: w.time_to_read,
+            domain_metadata: None,
+            listen_duration_estimate: None,
+            image: None,
+            amp_url: None,
+            top_image_url: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SendRequest {
+    pub actions: Vec<WireAction>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SendResult {
+    pub status: u16,
+    pub action_results: Vec<serde_json::Value>,
+}
+
+/// A send action as the v3 API encodes it, named by `action` with only its own fields set.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WireAction {
+    pub action: String,
+    #[serde(with = "optional_number", skip_serializing_if = "Option::is_none")]
+    pub item_id: Option<u64>,
+    #[serde(with = "optional_number", skip_serializing_if = "Option::is_none")]
+    pub time: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+impl From<&PocketSendAction> for WireAction {
+    fn from(action: &PocketSendAction) -> Self {
+        let item = |name: &str, item_id: &u64, time: &Option<u64>| WireAction {
+            action: name.to_string(),
+            item_id: Some(*item_id),
+            time: *time,
+            ..Default::default()
+        };
+        let item_tags = |name: &str, item_id: &u64, tags: &String, time: &Option<u64>| WireAction {
+            tags: Some(tags.clone()),
+            ..item(name, item_id, time)
+        };
+
+        match action {
+            PocketSendAction::Add {
+                url, title, tags, ..
+            } => WireAction {
+                action: "add".to_string(),
+                url: url.as_ref().map(Url::to_string),
+                title: title.clone(),
+                tags: tags.clone(),
+                ..Default::default()
+            },
+            PocketSendAction::Archive { item_id, time } => item("archive", item_id, time),
+            PocketSendAction::Readd { item_id, time } => item("readd", item_id, time),
+            PocketSendAction::Favorite { item_id, time } => item("favorite", item_id, time),
+            PocketSendAction::Unfavorite { item_id, time } => item("unfavorite", item_id, time),
+            PocketSendAction::Delete { item_id, time } => item("delete", item_id, time),
+            PocketSendAction::TagsAdd {
+                item_id,
+                tags,
+                time,
+            } => item_tags("tags_add", item_id, tags, time),
+            PocketSendAction::TagsRemove {
+                item_id,
+                tags,
+                time,
+            } => item_tags("tags_remove", item_id, tags, time),
+            PocketSendAction::TagsReplace {
+                item_id,
+                tags,
+                time,
+            } => item_tags("tags_replace", item_id, tags, time),
+            PocketSendAction::TagsClear { item_id, time } => item("tags_clear", item_id, time),
+            PocketSendAction::TagRename {
+                old_tag,
+                new_tag,
+                time,
+            } => WireAction {
+                action: "tag_rename".to_string(),
+                old_tag: Some(old_tag.clone()),
+                new_tag: Some(new_tag.clone()),
+                time: *time,
+                ..Default::default()
+            },
+            PocketSendAction::TagDelete { tag, time } => WireAction {
+                action: "tag_delete".to_string(),
+                tag: Some(tag.clone()),
+                time: *time,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl WireAction {
+    /// The action to apply, or `None` for an add, unknown action or missing field.
+    pub fn to_action(&self) -> Option<PocketSendAction> {
+        let time = self.time;
+        let item_id = self.item_id;
+        let tags = || self.tags.clone();
+
+        Some(match self.action.as_str() {
+            "archive" => PocketSendAction::Archive {
+                item_id: item_id?,
+                time,
+            },
+            "readd" => PocketSendAction::Readd {
+                item_id: item_id?,
+                time,
+            },
+            "favorite" => PocketSendAction::Favorite {
+                item_id: item_id?,
+                time,
+            },
+            "unfavorite" => PocketSendAction::Unfavorite {
+                item_id: item_id?,
+                time,
+            },
+            "delete" => PocketSendAction::Delete {
+                item_id: item_id?,
+                time,
+            },
+            "tags_add" => PocketSendAction::TagsAdd {
+                item_id: item_id?,
+                tags: tags()?,
+                time,
+            },
+            "tags_remove" => PocketSendAction::TagsRemove {
+                item_id: item_id?,
+                tags: tags()?,
+                time,
+            },
+            "tags_replace" => PocketSendAction::TagsReplace {
+                item_id: item_id?,
+                tags: tags()?,
+                time,
+            },
+            "tags_clear" => PocketSendAction::TagsClear {
+                item_id: item_id?,
+                time,
+            },
+            "tag_rename" => PocketSendAction::TagRename {
+                old_tag: self.old_tag.clone()?,
+                new_tag: self.new_tag.clone()?,
+                time,
+            },
+            "tag_delete" => PocketSendAction::TagDelete {
+                tag: self.tag.clone()?,
+                time,
+            },
+            _ => return None,
+        })
+    }
+
+    /// The tags of an add action.
+    pub fn tag_list(&self) -> Vec<&str> {
+        self.tags.as_deref().map(split_tags).unwrap_or_default()
+    }
+}
+
+fn flag(b: bool) -> u64 {
+    b as u64
+}
+
+fn has(h: &ItemHas) -> u64 {
+    match h {
+        ItemHas::No => 0,
+        ItemHas::Yes => 1,
+        ItemHas::Is => 2,
+    }
+}
+
+fn item_has(n: u64) -> ItemHas {
+    match n {
+        1 => ItemHas::Yes,
+        2 => ItemHas::Is,
+        _ => ItemHas::No,
+    }
+}
+
+fn timestamp(time: Option<DateTime<Utc>>) -> u64 {
+    time.map_or(0, |t| t.timestamp() as u64)
+}
+
+/// Pocket sends `0` for times that never happened.
+fn time(timestamp: u64) -> Option<DateTime<Utc>> {
+    Some(timestamp)
+        .filter(|t| *t != 0)
+        .map(|t| Utc.timestamp(t as i64, 0))
+}
+
+/// Numbers sent as strings, some of them empty, and read back from either.
+mod number {
+    use serde::de::{self, Deserializer};
+    use serde::{Deserialize, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    pub enum Number {
+        Int(u64),
+        Str(String),
+    }
+
+    impl Number {
+        pub fn parse<E: de::Error>(self) -> Result<u64, E> {
+            match self {
+                Number::Int(n) => Ok(n),
+                Number::Str(s) if s.is_empty() => Ok(0),
+                Number::Str(s) => s.parse().map_err(E::custom),
+            }
+        }
+    }
+
+    pub fn serialize<S: Serializer>(n: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&n.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        Number::deserialize(deserializer)?.parse()
+    }
+}
+
+mod optional_number {
+    use super::number::Number;
+    use serde::de::Deserializer;
+    use serde::{Deserialize, Serializer};
+
+    pub fn serialize<S: Serializer>(n: &Option<u64>, serializer: S) -> Result<S::Ok, S::Error> {
+        match n {
+            Some(n) => super::number::serialize(n, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<u64>, D::Error> {
+        Option::<Number>::deserialize(deserializer)?
+            .map(Number::parse)
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serve_mock::MockPocket;
+
+    fn backend(url: &str) -> V3Backend {
+        V3Backend::new(
+            Url::parse(url).unwrap(),
+            "consumer".to_string(),
+            "token".to_string(),
+        )
+    }
+
+    #[test]
+    fn add_get_and_send_through_mock_server() {
+        let mut listening = MockPocket::new(Some("token".to_string()))
+            .listen("127.0.0.1:0")
+            .unwrap();
+        let pocket = backend(&format!("http://{}", listening.socket));
+        let url = Url::parse("https://example.com/post").unwrap();
+
+        let added = pocket
+            .add(&PocketAddRequest {
+                url: &url,
+                title: Some("Post"),
+                tags: Some(&["rust"][..]),
+                tweet_id: None,
+            })
+            .unwrap();
+        let response = pocket
+            .send(&PocketSendRequest {
+                actions: &[
+                    &PocketSendAction::Archive {
+                        item_id: added.item_id,
+                        time: Some(1_600_000_000),
+                    },
+                    &PocketSendAction::Favorite {
+                        item_id: added.item_id + 1,
+                        time: None,
+                    },
+                ],
+            })
+            .unwrap();
+        let unread = pocket.get(&GetOpts::default()).unwrap();
+        let archived = pocket
+            .get(&GetOpts {
+                state: Some(PocketGetState::Archive),
+                detail_type: Some(PocketGetDetail::Complete),
+                ..Default::default()
+            })
+            .unwrap();
+        listening.close().unwrap();
+
+        assert_eq!(vec![true, false], response.action_results);
+        assert!(unread.is_empty());
+        assert_eq!(1, archived.len());
+        let item = &archived[0];
+        assert_eq!(added.item_id, item.item_id);
+        assert_eq!(Some(&url), item.url());
+        assert_eq!(Some("Post"), item.title());
+        assert_eq!(vec!["rust"], item.tag_names());
+        assert!(matches!(item.status, Some(ItemStatus::Archived)));
+        assert_eq!(Some(Utc.timestamp(1_600_000_000, 0)), item.time_read);
+    }
+
+    #[test]
+    fn oauth_issues_accepted_access_token() {
+        let mut listening = MockPocket::new(None).listen("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listening.socket);
+        let auth =
+            V3Authentication::new(Url::parse(&url).unwrap(), "consumer", "http://localhost/");
+
+        let code = auth.request(None).unwrap();
+        let user = auth.authorize(&code, None).unwrap();
+        let pocket = V3Backend::new(
+            Url::parse(&url).unwrap(),
+            "consumer".to_string(),
+            user.access_token,
+        );
+        let items = pocket.get(&GetOpts::default());
+        let rejected = backend(&url).get(&GetOpts::default());
+        listening.close().unwrap();
+
+        assert!(items.unwrap().is_empty());
+        assert!(matches!(
+            rejected,
+            Err(crate::backend::BackendError::Response(401, _))
+        ));
+    }
+
+    #[test]
+    fn wire_item_reads_string_encoded_numbers() {
+        let item: WireItem = serde_json::from_str(
+            r#"{"item_id":"229279689","resolved_id":"","given_url":"http://example.com",
+                "favorite":"1","status":"1","time_added":"1346378756","word_count":3,
+                "tags":{"rust":{"item_id":"229279689","tag":"rust"}}}"#,
+        )
+        .unwrap();
+        let item = Item::from(item);
+
+        assert_eq!(229_279_689, item.item_id);
+        assert_eq!(229_279_689, item.resolved_id);
+        assert_eq!(Some(true), item.favorite);
+        assert!(matches!(item.status, Some(ItemStatus::Archived)));
+        assert_eq!(Some(Utc.timestamp(1_346_378_756, 0)), item.time_added);
+        assert_eq!(None, item.time_read);
+        assert_eq!(vec!["rust"], item.tag_names());
+    }
+}
@@ -0,0 +1,640 @@
+use super::http::{Body, Http};
+use super::{
+    filter, on_item, split_tags, BackendError, BackendResult, PocketAdd, PocketGet, PocketSend,
+    SendResponse,
+};
+use crate::get::GetOpts;
+use crate::models::{Author, Item, ItemHas, ItemStatus, Tag};
+use chrono::{DateTime, Utc};
+use hyper::method::Method;
+use pocket::{PocketAddRequest, PocketGetState, PocketSendAction, PocketSendRequest};
+use serde::de::{DeserializeOwned, Deserializer};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use url::Url;
+
+const PER_PAGE: &str = "100";
+const EXCERPT_LENGTH: usize = 200;
+
+pub struct Credentials {
+    pub client_id: String,
+    pub client_secret: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+/// A self-hosted Wallabag instance, through its REST API.
+pub struct WallabagBackend {
+    http: Http,
+    credentials: Credentials,
+    token: RefCell<Option<String>>,
+}
+
+#[derive(Deserialize)]
+struct Token {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct Entries {
+    pages: u64,
+    #[serde(rename = "_embedded")]
+    embedded: Embedded,
+}
+
+#[derive(Deserialize)]
+struct Embedded {
+    items: Vec<Entry>,
+}
+
+#[derive(Deserialize)]
+struct Entry {
+    id: u64,
+    url: Option<String>,
+    given_url: Option<String>,
+    title: Option<String>,
+    content: Option<String>,
+    #[serde(default, deserialize_with = "flag")]
+    is_archived: bool,
+    #[serde(default, deserialize_with = "flag")]
+    is_starred: bool,
+    #[serde(default)]
+    tags: Vec<EntryTag>,
+    created_at: Option<String>,
+    updated_at: Option<String>,
+    archived_at: Option<String>,
+    starred_at: Option<String>,
+    reading_time: Option<u64>,
+    mimetype: Option<String>,
+    language: Option<String>,
+    preview_picture: Option<String>,
+    published_by: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct EntryTag {
+    id: u64,
+    label: String,
+}
+
+#[derive(Serialize)]
+struct NewEntry<'a> {
+    url: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tags: Option<String>,
+}
+
+#[derive(Default, Serialize)]
+struct EntryPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    archive: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    starred: Option<u8>,
+}
+
+#[derive(Serialize)]
+struct EntryTags<'a> {
+    tags: &'a str,
+}
+
+impl WallabagBackend {
+    pub fn new(url: Url, credentials: Credentials) -> WallabagBackend {
+        WallabagBackend {
+            http: Http::new(url),
+            credentials,
+            token: RefCell::new(None),
+        }
+    }
+
+    /// Uses the password grant when a username and password are configured, and the
+    /// client credentials grant otherwise.
+    fn authorization(&self) -> BackendResult<String> {
+        if let Some(token) = self.token.borrow().as_ref() {
+            return Ok(format!("Bearer {}", token));
+        }
+
+        let credentials = &self.credentials;
+        let mut form = vec![
+            ("client_id", credentials.client_id.as_str()),
+            ("client_secret", credentials.client_secret.as_str()),
+        ];
+        match (&credentials.username, &credentials.password) {
+            (Some(username), Some(password)) => form.extend(vec![
+                ("grant_type", "password"),
+                ("username", username.as_str()),
+                ("password", password.as_str()),
+            ]),
+            _ => form.push(("grant_type", "client_credentials")),
+        }
+
+        let token: Token = self.http.json(
+            Method::Post,
+            self.http.url("oauth/v2/token", &[])?,
+            None,
+            Body::form(form),
+        )?;
+        let authorization = format!("Bearer {}", token.access_token);
+        self.token.replace(Some(token.access_token));
+        Ok(authorization)
+    }
+
+    fn call<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, String)],
+        body: Body,
+    ) -> BackendResult<T> {
+        let url = self.http.url(path, query)?;
+        self.http
+            .json(method, url, Some(&self.authorization()?), body)
+    }
+
+    fn entries(&self, query: &[(&str, String)]) -> BackendResult<Vec<Entry>> {
+        let mut entries = Vec::new();
+        let mut page = 1;
+
+        loop {
+            let mut query = query.to_vec();
+            query.push(("perPage", PER_PAGE.to_string()));
+            query.push(("page", page.to_string()));
+            let response: Entries =
+                self.call(Method::Get, "api/entries.json", &query, Body::Empty)?;
+            entries.extend(response.embedded.items);
+            if page >= response.pages {
+                return Ok(entries);
+            }
+            page += 1;
+        }
+    }
+
+    fn patch(&self, item_id: u64, patch: EntryPatch) -> BackendResult<()> {
+        let _: Entry = self.call(
+            Method::Patch,
+            &format!("api/entries/{}.json", item_id),
+            &[],
+            Body::json(&patch)?,
+        )?;
+        Ok(())
+    }
+
+    fn add_tags(&self, item_id: u64, tags: &str) -> BackendResult<()> {
+        let _: Entry = self.call(
+            Method::Post,
+            &format!("api/entries/{}/tags.json", item_id),
+            &[],
+            Body::json(&EntryTags { tags })?,
+        )?;
+        Ok(())
+    }
+
+    /// Remove the tags of an entry matching `remove`.
+    fn remove_tags(&self, item_id: u64, remove: impl Fn(&str) -> bool) -> BackendResult<()> {
+        let tags: Vec<EntryTag> = self.call(
+            Method::Get,
+            &format!("api/entries/{}/tags.json", item_id),
+            &[],
+            Body::Empty,
+        )?;
+        for tag in tags.into_iter().filter(|t| remove(&t.label)) {
+            let _: Entry = self.call(
+                Method::Delete,
+                &format!("api/entries/{}/tags/{}.json", item_id, tag.id),
+                &[],
+                Body::Empty,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn delete_tag(&self, tag: &str) -> BackendResult<()> {
+        let _: serde_json::Value = self.call(
+            Method::Delete,
+            "api/tag/label.json",
+            &[("tag", tag.to_string())],
+            Body::Empty,
+        )?;
+        Ok(())
+    }
+
+    fn add_entry(
+        &self,
+        url: &str,
+        title: Option<&str>,
+        tags: Option<String>,
+    ) -> BackendResult<Item> {
+        let entry: Entry = self.call(
+            Method::Post,
+            "api/entries.json",
+            &[],
+            Body::json(&NewEntry { url, title, tags })?,
+        )?;
+        Ok(entry.into())
+    }
+
+    /// Whether the action applied; it doesn't when its entry is missing.
+    fn apply(&self, action: &PocketSendAction) -> BackendResult<bool> {
+        let applied = match action {
+            PocketSendAction::Add {
+                url, title, tags, ..
+            } => match url {
+                Some(url) => self
+                    .add_entry(url.as_str(), title.as_deref(), tags.clone())
+                    .map(|_| ()),
+                None => return Ok(false),
+            },
+            PocketSendAction::Archive { item_id, .. } => self.patch(
+                *item_id,
+                EntryPatch {
+                    archive: Some(1),
+                    ..Default::default()
+                },
+            ),
+            PocketSendAction::Readd { item_id, .. } => self.patch(
+                *item_id,
+                EntryPatch {
+                    archive: Some(0),
+                    ..Default::default()
+                },
+            ),
+            PocketSendAction::Favorite { item_id, .. } => self.patch(
+                *item_id,
+                EntryPatch {
+                    starred: Some(1),
+                    ..Default::default()
+                },
+            ),
+            PocketSendAction::Unfavorite { item_id, .. } => self.patch(
+                *item_id,
+                EntryPatch {
+                    starred: Some(0),
+                    ..Default::default()
+                },
+            ),
+            PocketSendAction::Delete { item_id, .. } => {
+                let _: Entry = self.call(
+                    Method::Delete,
+                    &format!("api/entries/{}.json", item_id),
+                    &[],
+                    Body::Empty,
+                )?;
+                Ok(())
+            }
+            PocketSendAction::TagsAdd { item_id, tags, .. } => self.add_tags(*item_id, tags),
+            PocketSendAction::TagsRemove { item_id, tags, .. } => {
+                let tags = split_tags(tags);
+                self.remove_tags(*item_id, |t| tags.contains(&t))
+            }
+            PocketSendAction::TagsReplace { item_id, tags, .. } => {
+                self.remove_tags(*item_id, |_| true)?;
+                self.add_tags(*item_id, tags)
+            }
+            PocketSendAction::TagsClear { item_id, .. } => self.remove_tags(*item_id, |_| true),
+            PocketSendAction::TagRename {
+                old_tag, new_tag, ..
+            } => {
+                // Without an `archive` filter both unread and archived entries are listed
+                for entry in self.entries(&[("tags", old_tag.clone())])? {
+                    self.add_tags(entry.id, new_tag)?;
+                }
+                self.delete_tag(old_tag)
+            }
+            PocketSendAction::TagDelete { tag, .. } => self.delete_tag(tag),
+        };
+        match applied {
+            Ok(()) => Ok(true),
+            Err(BackendError::Response(404, _)) if on_item(action) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl PocketAdd for WallabagBackend {
+    fn add(&self, request: &PocketAddRequest) -> BackendResult<Item> {
+        self.add_entry(
+            request.url.as_str(),
+            request.title,
+            request.tags.map(|t| t.join(",")),
+        )
+    }
+}
+
+impl PocketGet for WallabagBackend {
+    fn get(&self, opts: &GetOpts) -> BackendResult<Vec<Item>> {
+        let mut query = Vec::new();
+        match opts.state.unwrap_or(PocketGetState::Unread) {
+            PocketGetState::Unread => query.push(("archive", "0".to_string())),
+            PocketGetState::Archive => query.push(("archive", "1".to_string())),
+            PocketGetState::All => {}
+        }
+        if let Some(favorite) = opts.favorite {
+            query.push(("starred", (favorite as u8).to_string()));
+        }
+        if let Some(tag) = &opts.tag {
+            query.push(("tags", tag.clone()));
+        }
+        if let Some(since) = opts.since {
+            query.push(("since", since.timestamp().to_string()));
+        }
+
+        // Wallabag has no equivalent for the remaining filters, so they apply here
+        let items = self.entries(&query)?.into_iter().map(Item::from);
+        Ok(filter::apply(items, opts))
+    }
+}
+
+impl PocketSend for WallabagBackend {
+    fn send(&self, request: &PocketSendRequest) -> BackendResult<SendResponse> {
+        // With a token, the URL is known to point at wallabag, so a 404 later on
+        // means an entry is gone rather than that every action failed
+        self.authorization()?;
+        let mut action_results = Vec::new();
+        for action in request.actions {
+            action_results.push(self.apply(action)?);
+        }
+
+        Ok(SendResponse {
+            status: 1,
+            action_results,
+        })
+    }
+}
+
+impl From<Entry> for Item {
+    fn from(e: Entry) -> Self {
+        let text = e.content.as_deref().map(strip_html).unwrap_or_default();
+        let mimetype = e.mimetype.as_deref().unwrap_or("text/html");
+        let resolved_url = e.url.as_deref().and_then(|u| Url::parse(u).ok());
+        let item_id = e.id;
+
+        Item {
+            item_id,
+            given_url: e
+                .given_url
+                .as_deref()
+                .and_then(|u| Url::parse(u).ok())
+                .or_else(|| resolved_url.clone()),
+            given_title: e.title.clone(),
+            word_count: text.split_whitespace().count(),
+            excerpt: text.chars().take(EXCERPT_LENGTH).collect(),
+            time_added: date(&e.created_at),
+            time_read: date(&e.archived_at),
+            time_updated: date(&e.updated_at),
+            time_favorited: date(&e.starred_at),
+            favorite: Some(e.is_starred),
+            is_index: false,
+            is_article: mimetype.starts_with("text/"),
+            has_image: if mimetype.starts_with("image/") {
+                ItemHas::Is
+            } else {
+                ItemHas::No
+            },
+            has_video: if mimetype.starts_with("video/") {
+                ItemHas::Is
+            } else {
+                ItemHas::No
+            },
+            resolved_id: item_id,
+            resolved_title: e.title,
+            resolved_url,
+            sort_id: None,
+            status: Some(if e.is_archived {
+                ItemStatus::Archived
+            } else {
+                ItemStatus::Normal
+            }),
+            tags: if e.tags.is_empty() {
+                None
+            } else {
+                Some(e.tags.into_iter().map(|t| Tag(t.label)).collect())
+            },
+            images: None,
+            videos: None,
+            authors: e.published_by.map(|authors| {
+                authors
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, name)| Author {
+                        item_id,
+                        author_id: i as u64,
+                        name,
+                        url: "".to_string(),
+                    })
+                    .collect()
+            }),
+            lang: e.language,
+            time_to_read: e.reading_time,
+            domain_metadata: None,
+            listen_duration_estimate: None,
+            image: None,
+            amp_url: None,
+            top_image_url: e
+                .preview_picture
+                .as_deref()
+                .and_then(|u| Url::parse(u).ok()),
+        }
+    }
+}
+
+/// Wallabag versions differ on whether flags are booleans or integers.
+fn flag<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    Ok(match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Bool(b) => b,
+        serde_json::Value::Number(n) => n.as_u64().map_or(false, |n| n != 0),
+        _ => false,
+    })
+}
+
+/// Wallabag dates omit the colon in their offset, e.g. `2020-01-01T10:00:00+0000`.
+fn date(s: &Option<String>) -> Option<DateTime<Utc>> {
+    let s = s.as_deref()?;
+    DateTime::parse_from_rfc3339(s)
+        .or_else(|_| DateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%z"))
+        .ok()
+        .map(|d| d.with_timezone(&Utc))
+}
+
+fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::http::mock::MockServer;
+
+    const TOKEN: &str = r#"{"access_token":"token","expires_in":3600,"token_type":"bearer"}"#;
+    const ENTRY: &str = r#"{
+        "id": 7,
+        "url": "https://example.com/post",
+        "title": "Post",
+        "content": "<p>Hello <b>wallabag</b> world</p>",
+        "is_archived": 1,
+        "is_starred": true,
+        "tags": [{"id": 3, "label": "rust", "slug": "rust"}],
+        "created_at": "2020-01-02T03:04:05+0000",
+        "updated_at": "2020-01-03T03:04:05+00:00",
+        "archived_at": null,
+        "starred_at": null,
+        "reading_time": 4,
+        "mimetype": "text/html",
+        "language": "en",
+        "preview_picture": null,
+        "published_by": ["Jane"]
+    }"#;
+
+    fn backend(server: &MockServer) -> WallabagBackend {
+        WallabagBackend::new(
+            Url::parse(&server.url).unwrap(),
+            Credentials {
+                client_id: "id".to_string(),
+                client_secret: "secret".to_string(),
+                username: Some("user".to_string()),
+                password: Some("pass".to_string()),
+            },
+        )
+    }
+
+    #[test]
+    fn get_maps_entries_to_items() {
+        let server = MockServer::start(|r| match r.path.as_str() {
+            "/oauth/v2/token" => (200, TOKEN.to_string()),
+            _ => (
+                200,
+                format!(
+                    r#"{{"page":1,"pages":1,"_embedded":{{"items":[{}]}}}}"#,
+                    ENTRY
+                ),
+            ),
+        });
+
+        let items = backend(&server)
+            .get(&GetOpts {
+                state: Some(PocketGetState::Archive),
+                detail_type: Some(pocket::PocketGetDetail::Complete),
+                ..Default::default()
+            })
+            .unwrap();
+
+        let requests = server.requests();
+        assert!(requests[0].body.contains("grant_type=password"));
+        assert!(requests[1].path.starts_with("/api/entries.json?archive=1&"));
+        assert_eq!(
+            Some("Bearer token"),
+            requests[1].headers.get("authorization").map(|h| h.as_str())
+        );
+        assert_eq!(1, items.len());
+        let item = &items[0];
+        assert_eq!(7, item.item_id);
+        assert_eq!("Hello wallabag world", item.excerpt);
+        assert_eq!(3, item.word_count);
+        assert!(matches!(item.status, Some(ItemStatus::Archived)));
+        assert_eq!(Some(true), item.favorite);
+        assert_eq!(vec!["rust"], item.tag_names());
+        assert_eq!(vec!["Jane"], item.author_names());
+        assert_eq!(
+            "2020-01-02T03:04:05+00:00",
+            item.time_added.unwrap().to_rfc3339()
+        );
+    }
+
+    #[test]
+    fn send_patches_entries_and_reports_failures() {
+        let server = MockServer::start(|r| match (r.method.as_str(), r.path.as_str()) {
+            ("POST", "/oauth/v2/token") => (200, TOKEN.to_string()),
+            ("PATCH", "/api/entries/7.json") => (200, ENTRY.to_string()),
+            _ => (404, "{}".to_string()),
+        });
+
+        let response = backend(&server)
+            .send(&PocketSendRequest {
+                actions: &[
+                    &PocketSendAction::Archive {
+                        item_id: 7,
+                        time: None,
+                    },
+                    &PocketSendAction::Favorite {
+                        item_id: 8,
+                        time: None,
+                    },
+                ],
+            })
+            .unwrap();
+
+        assert_eq!(vec![true, false], response.action_results);
+        assert_eq!(r#"{"archive":1}"#, server.requests()[1].body);
+    }
+
+    #[test]
+    fn send_fails_on_rejected_credentials() {
+        let server = MockServer::start(|_| (401, r#"{"error":"invalid_grant"}"#.to_string()));
+
+        let response = backend(&server).send(&PocketSendRequest {
+            actions: &[&PocketSendAction::Archive {
+                item_id: 7,
+                time: None,
+            }],
+        });
+
+        assert!(matches!(response, Err(BackendError::Response(401, _))));
+    }
+
+    #[test]
+    fn send_fails_on_wrong_url() {
+        let server = MockServer::start(|_| (404, "Not Found".to_string()));
+
+        let response = backend(&server).send(&PocketSendRequest {
+            actions: &[&PocketSendAction::Archive {
+                item_id: 7,
+                time: None,
+            }],
+        });
+
+        assert!(matches!(response, Err(BackendError::Response(404, _))));
+    }
+
+    #[test]
+    fn tag_rename_retags_entries_and_deletes_old_tag() {
+        let server = MockServer::start(|r| match (r.method.as_str(), r.path.as_str()) {
+            ("POST", "/oauth/v2/token") => (200, TOKEN.to_string()),
+            ("GET", _) => (
+                200,
+                format!(
+                    r#"{{"page":1,"pages":1,"_embedded":{{"items":[{}]}}}}"#,
+                    ENTRY
+                ),
+            ),
+            ("POST", "/api/entries/7/tags.json") => (200, ENTRY.to_string()),
+            ("DELETE", "/api/tag/label.json?tag=rust") => (200, "[]".to_string()),
+            _ => (404, "{}".to_string()),
+        });
+
+        let response = backend(&server)
+            .send(&PocketSendRequest {
+                actions: &[&PocketSendAction::TagRename {
+                    old_tag: "rust".to_string(),
+                    new_tag: "rust-lang".to_string(),
+                    time: None,
+                }],
+            })
+            .unwrap();
+
+        assert_eq!(vec![true], response.action_results);
+        assert_eq!(r#"{"tags":"rust-lang"}"#, server.requests()[2].body);
+    }
+}
@@ -0,0 +1,623 @@
+use crate::backend::{send_all, BackendResult, PocketAdd, PocketGet, PocketSend};
+use crate::get::GetOpts;
+use crate::models::{Item, ItemStatus};
+use crate::output::Output;
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use pocket::{PocketAddRequest, PocketGetDetail, PocketGetState, PocketSendAction};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+use url::Url;
+
+const BACKUP_VERSION: u32 = 1;
+
+#[derive(Debug, StructOpt)]
+pub struct BackupOpts {
+    /// Archive to write
+    #[structopt(parse(from_os_str))]
+    pub file: PathBuf,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct RestoreOpts {
+    /// Archive to restore from
+    #[structopt(parse(from_os_str))]
+    pub file: PathBuf,
+    /// Show the operations a restore would perform without performing them
+    #[structopt(long)]
+    pub dry_run: bool,
+    /// Show how the account differs from the archive
+    #[structopt(long, conflicts_with = "dry-run")]
+    pub diff: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Backup {
+    version: u32,
+    created: DateTime<Utc>,
+    tags: BTreeSet<String>,
+    items: Vec<BackupItem>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BackupItem {
+    item_id: u64,
+    #[serde(with = "url_serde")]
+    given_url: Option<Url>,
+    #[serde(with = "url_serde")]
+    resolved_url: Option<Url>,
+    title: Option<String>,
+    tags: BTreeSet<String>,
+    favorite: bool,
+    archived: bool,
+    time_added: Option<DateTime<Utc>>,
+    time_read: Option<DateTime<Utc>>,
+    time_favorited: Option<DateTime<Utc>>,
+}
+
+impl From<&Item> for BackupItem {
+    fn from(i: &Item) -> Self {
+        BackupItem {
+            item_id: i.item_id,
+            given_url: i.given_url.clone(),
+            resolved_url: i.resolved_url.clone(),
+            title: i.title().map(|t| t.to_string()),
+            tags: i.tag_names().into_iter().map(|t| t.to_string()).collect(),
+            favorite: i.favorite.unwrap_or_default(),
+            archived: matches!(i.status, Some(ItemStatus::Archived)),
+            time_added: i.time_added,
+            time_read: i.time_read,
+            time_favorited: i.time_favorited,
+        }
+    }
+}
+
+impl BackupItem {
+    fn url(&self) -> Option<&Url> {
+        self.given_url
+            .as_ref()
+            .or_else(|| self.resolved_url.as_ref())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BackupSummary {
+    pub file: PathBuf,
+    pub items: usize,
+    pub tags: usize,
+}
+
+/// A change restoring an archive makes to the account.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Operation {
+    Add {
+        #[serde(with = "url_serde")]
+        url: Url,
+        title: Option<String>,
+        tags: Vec<String>,
+        archive: bool,
+        favorite: bool,
+        /// When the item was read, to archive it at
+        time_read: Option<DateTime<Utc>>,
+        time_favorited: Option<DateTime<Utc>>,
+    },
+    Archive {
+        item_id: u64,
+        time: Option<DateTime<Utc>>,
+    },
+    Readd {
+        item_id: u64,
+        time: Option<DateTime<Utc>>,
+    },
+    Favorite {
+        item_id: u64,
+        time: Option<DateTime<Utc>>,
+    },
+    Unfavorite {
+        item_id: u64,
+    },
+    TagsAdd {
+        item_id: u64,
+        tags: Vec<String>,
+    },
+}
+
+#[derive(Debug, Serialize)]
+pub struct Diff {
+    pub only_in_backup: Vec<String>,
+    pub only_in_account: Vec<String>,
+    pub changed: Vec<Changed>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Changed {
+    pub url: String,
+    pub item_id: u64,
+    pub operations: Vec<Operation>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RestoreSummary {
+    pub added: usize,
+    /// URLs that could not be added, with the error for each
+    pub failed_adds: Vec<FailedAdd>,
+    pub actions: usize,
+    pub failed_actions: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FailedAdd {
+    #[serde(with = "url_serde")]
+    pub url: Url,
+    pub error: String,
+}
+
+pub fn handle_backup<W: Write>(pocket: &impl PocketGet, opts: &BackupOpts, output: &mut Output<W>) {
+    let summary = backup(pocket, opts).unwrap();
+    output.write(summary).unwrap();
+}
+
+pub fn backup(pocket: &impl PocketGet, opts: &BackupOpts) -> BackendResult<BackupSummary> {
+    let items = account_items(pocket)?;
+    let backup = Backup {
+        version: BACKUP_VERSION,
+        created: Utc::now(),
+        tags: items
+            .iter()
+            .flat_map(|i| i.tag_names())
+            .map(|t| t.to_string())
+            .collect(),
+        items: items.iter().map(BackupItem::from).collect(),
+    };
+
+    let file = BufWriter::new(File::create(&opts.file)?);
+    let mut encoder = GzEncoder::new(file, Compression::default());
+    serde_json::to_writer(&mut encoder, &backup)?;
+    encoder.finish()?.flush()?;
+
+    Ok(BackupSummary {
+        file: opts.file.clone(),
+        items: backup.items.len(),
+        tags: backup.tags.len(),
+    })
+}
+
+pub fn handle_restore<W: Write>(
+    pocket: &(impl PocketAdd + PocketGet + PocketSend),
+    opts: &RestoreOpts,
+    output: &mut Output<W>,
+) {
+    match restore(pocket, opts).unwrap() {
+        Restored::Diff(diff) => output.write(diff).unwrap(),
+        Restored::Planned(operations) => output.write(operations).unwrap(),
+        Restored::Applied(summary) => output.write(summary).unwrap(),
+    }
+}
+
+/// What a restore produced, depending on whether it was a diff, a dry run or the real thing.
+#[derive(Debug)]
+pub enum Restored {
+    Diff(Diff),
+    Planned(Vec<Operation>),
+    Applied(RestoreSummary),
+}
+
+pub fn restore(
+    pocket: &(impl PocketAdd + PocketGet + PocketSend),
+    opts: &RestoreOpts,
+) -> BackendResult<Restored> {
+    let backup = read(&opts.file)?;
+    let account = account_items(pocket)?;
+
+    if opts.diff {
+        return Ok(Restored::Diff(diff(&backup, &account)));
+    }
+
+    let operations = plan(&backup, &account);
+    if opts.dry_run {
+        Ok(Restored::Planned(operations))
+    } else {
+        Ok(Restored::Applied(apply(pocket, operations)?))
+    }
+}
+
+fn read(file: &Path) -> BackendResult<Backup> {
+    let decoder = GzDecoder::new(BufReader::new(File::open(file)?));
+    let backup: Backup = serde_json::from_reader(decoder)?;
+    if backup.version > BACKUP_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unsupported backup version: {}", backup.version),
+        )
+        .into());
+    }
+    Ok(backup)
+}
+
+fn account_items(pocket: &impl PocketGet) -> BackendResult<Vec<Item>> {
+    let opts = GetOpts {
+        state: Some(PocketGetState::All),
+        detail_type: Some(PocketGetDetail::Complete),
+        ..Default::default()
+    };
+    pocket.get(&opts)
+}
+
+/// URLs are compared without their trailing slash so either form matches.
+fn key(url: &Url) -> String {
+    url.as_str().trim_end_matches('/').to_string()
+}
+
+fn index(account: &[Item]) -> HashMap<String, &Item> {
+    let mut index = HashMap::new();
+    for item in account {
+        for url in item.given_url.iter().chain(item.resolved_url.iter()) {
+            index.entry(key(url)).or_insert(item);
+        }
+    }
+    index
+}
+
+fn plan(backup: &Backup, account: &[Item]) -> Vec<Operation> {
+    let index = index(account);
+
+    backup
+        .items
+        .iter()
+        .filter_map(|b| Some((b, b.url()?)))
+        .flat_map(|(b, url)| match index.get(&key(url)) {
+            Some(item) => operations(b, item),
+            None => vec![Operation::Add {
+                url: url.clone(),
+                title: b.title.clone(),
+                tags: b.tags.iter().cloned().collect(),
+                archive: b.archived,
+                favorite: b.favorite,
+                time_read: b.time_read,
+                time_favorited: b.time_favorited,
+            }],
+        })
+        .collect()
+}
+
+fn operations(b: &BackupItem, item: &Item) -> Vec<Operation> {
+    let item_id = item.item_id;
+    let mut operations = Vec::new();
+    let archived = matches!(item.status, Some(ItemStatus::Archived));
+    let favorite = item.favorite.unwrap_or_default();
+    let tags = item.tag_names();
+
+    match (b.archived, archived) {
+        (true, false) => operations.push(Operation::Archive {
+            item_id,
+            time: b.time_read,
+        }),
+        // An unread item was last made unread when it was added
+        (false, true) => operations.push(Operation::Readd {
+            item_id,
+            time: b.time_added,
+        }),
+        _ => {}
+    }
+    match (b.favorite, favorite) {
+        (true, false) => operations.push(Operation::Favorite {
+            item_id,
+            time: b.time_favorited,
+        }),
+        (false, true) => operations.push(Operation::Unfavorite { item_id }),
+        _ => {}
+    }
+    let missing = b
+        .tags
+        .iter()
+        .filter(|t| !tags.contains(&t.as_str()))
+        .cloned()
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        operations.push(Operation::TagsAdd {
+            item_id,
+            tags: missing,
+        });
+    }
+
+    operations
+}
+
+fn diff(backup: &Backup, account: &[Item]) -> Diff {
+    let index = index(account);
+    let mut diff = Diff {
+        only_in_backup: Vec::new(),
+        only_in_account: Vec::new(),
+        changed: Vec::new(),
+    };
+    let mut restored = Vec::new();
+
+    for (b, url) in backup.items.iter().filter_map(|b| Some((b, b.url()?))) {
+        match index.get(&key(url)) {
+            Some(item) => {
+                restored.push(item.item_id);
+                let operations = operations(b, item);
+                if !operations.is_empty() {
+                    diff.changed.push(Changed {
+                        url: url.to_string(),
+                        item_id: item.item_id,
+                        operations,
+                    });
+                }
+            }
+            None => diff.only_in_backup.push(url.to_string()),
+        }
+    }
+
+    diff.only_in_account = account
+        .iter()
+        .filter(|i| !restored.contains(&i.item_id))
+        .filter_map(|i| i.url().map(|u| u.to_string()))
+        .collect();
+    diff
+}
+
+/// Adds go out one by one and everything else in batches at the end. A failed add
+/// is recorded and skipped, so the actions for the items restored before it are
+/// still sent.
+fn apply(
+    pocket: &(impl PocketAdd + PocketSend),
+    operations: Vec<Operation>,
+) -> BackendResult<RestoreSummary> {
+    let mut added = 0;
+    let mut failed_adds = Vec::new();
+    let mut actions = Vec::new();
+
+    for operation in operations {
+        match operation {
+            Operation::Add {
+                url,
+                title,
+                tags,
+                archive,
+                favorite,
+                time_read,
+                time_favorited,
+            } => {
+                let tags = tags.iter().map(|t| t.as_str()).collect::<Vec<_>>();
+                let item = match pocket.add(&PocketAddRequest {
+                    url: &url,
+                    title: title.as_deref(),
+                    tags: Some(tags.as_slice()).filter(|t| !t.is_empty()),
+                    tweet_id: None,
+                }) {
+                    Ok(item) => item,
+                    Err(e) => {
+                        failed_adds.push(FailedAdd {
+                            url,
+                            error: e.to_string(),
+                        });
+                        continue;
+                    }
+                };
+                added += 1;
+                if archive {
+                    actions.push(PocketSendAction::Archive {
+                        item_id: item.item_id,
+                        time: timestamp(time_read),
+                    });
+                }
+                if favorite {
+                    actions.push(PocketSendAction::Favorite {
+                        item_id: item.item_id,
+                        time: timestamp(time_favorited),
+                    });
+                }
+            }
+            Operation::Archive { item_id, time } => {
+                actions.push(PocketSendAction::Archive {
+                    item_id,
+                    time: timestamp(time),
+                });
+            }
+            Operation::Readd { item_id, time } => {
+                actions.push(PocketSendAction::Readd {
+                    item_id,
+                    time: timestamp(time),
+                });
+            }
+            Operation::Favorite { item_id, time } => {
+                actions.push(PocketSendAction::Favorite {
+                    item_id,
+                    time: timestamp(time),
+                });
+            }
+            Operation::Unfavorite { item_id } => {
+                actions.push(PocketSendAction::Unfavorite {
+                    item_id,
+                    time: None,
+                });
+            }
+            Operation::TagsAdd { item_id, tags } => {
+                actions.push(PocketSendAction::TagsAdd {
+                    item_id,
+                    tags: tags.join(","),
+                    time: None,
+                });
+            }
+        }
+    }
+
+    let response = send_all(pocket, &actions)?;
+    let failed_actions = response.action_results.iter().filter(|r| !**r).count();
+
+    Ok(RestoreSummary {
+        added,
+        failed_adds,
+        actions: actions.len(),
+        failed_actions,
+    })
+}
+
+fn timestamp(time: Option<DateTime<Utc>>) -> Option<u64> {
+    time.map(|t| t.timestamp() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::{succeed, unused_get, PocketMock};
+    use crate::backend::BackendError;
+    use chrono::TimeZone;
+
+    fn backup(items: Vec<BackupItem>) -> Backup {
+        Backup {
+            version: BACKUP_VERSION,
+            created: Utc::now(),
+            tags: BTreeSet::new(),
+            items,
+        }
+    }
+
+    fn tagged(item_id: u64, url: &str, tags: &[&str]) -> Item {
+        let mut item = Item::fixture(item_id, url).with_tags(tags);
+        item.favorite = Some(false);
+        item.status = Some(ItemStatus::Normal);
+        item
+    }
+
+    #[test]
+    fn plan_adds_missing_items() {
+        let mut saved = BackupItem::from(&tagged(1, "https://example.com/a", &["x"]));
+        saved.archived = true;
+
+        let operations = plan(&backup(vec![saved]), &[]);
+
+        assert_eq!(
+            vec![Operation::Add {
+                url: Url::parse("https://example.com/a").unwrap(),
+                title: None,
+                tags: vec!["x".to_string()],
+                archive: true,
+                favorite: false,
+                time_read: None,
+                time_favorited: None,
+            }],
+            operations
+        );
+    }
+
+    #[test]
+    fn plan_updates_existing_items_by_url() {
+        let mut saved = BackupItem::from(&tagged(1, "https://example.com/a/", &["x", "y"]));
+        saved.favorite = true;
+        let account = [tagged(9, "https://example.com/a", &["x"])];
+
+        let operations = plan(&backup(vec![saved]), &account);
+
+        assert_eq!(
+            vec![
+                Operation::Favorite {
+                    item_id: 9,
+                    time: None,
+                },
+                Operation::TagsAdd {
+                    item_id: 9,
+                    tags: vec!["y".to_string()],
+                },
+            ],
+            operations
+        );
+    }
+
+    #[test]
+    fn diff_reports_both_sides() {
+        let saved = BackupItem::from(&tagged(1, "https://example.com/a", &[]));
+        let account = [tagged(2, "https://example.com/b", &[])];
+
+        let diff = diff(&backup(vec![saved]), &account);
+
+        assert_eq!(vec!["https://example.com/a"], diff.only_in_backup);
+        assert_eq!(vec!["https://example.com/b"], diff.only_in_account);
+        assert!(diff.changed.is_empty());
+    }
+
+    #[test]
+    fn apply_keeps_read_and_favorite_times() {
+        let read = Utc.ymd(2020, 1, 2).and_hms(0, 0, 0);
+        let favorited = Utc.ymd(2020, 1, 3).and_hms(0, 0, 0);
+        let added = Utc.ymd(2020, 1, 1).and_hms(0, 0, 0);
+        let mut saved = BackupItem::from(&tagged(1, "https://example.com/a", &[]));
+        saved.archived = true;
+        saved.favorite = true;
+        saved.time_read = Some(read);
+        saved.time_favorited = Some(favorited);
+        let mut unread = BackupItem::from(&tagged(2, "https://example.com/b", &[]));
+        unread.time_added = Some(added);
+        let mut archived = tagged(9, "https://example.com/b", &[]);
+        archived.status = Some(ItemStatus::Archived);
+        let operations = plan(&backup(vec![saved, unread]), &[archived]);
+        let pocket = PocketMock {
+            get_mock: unused_get,
+            send_mock: |request| {
+                let (read, favorited, added) = (
+                    read.timestamp() as u64,
+                    favorited.timestamp() as u64,
+                    added.timestamp() as u64,
+                );
+                assert!(matches!(
+                    request.actions,
+                    [
+                        PocketSendAction::Archive { item_id: 5, time: Some(r) },
+                        PocketSendAction::Favorite { item_id: 5, time: Some(f) },
+                        PocketSendAction::Readd { item_id: 9, time: Some(a) },
+                    ] if *r == read && *f == favorited && *a == added
+                ));
+                Ok(succeed(request))
+            },
+            add_mock: |_| Ok(Item::fixture(5, "https://example.com/a")),
+        };
+
+        let summary = apply(&pocket, operations).unwrap();
+
+        assert_eq!(1, summary.added);
+        assert_eq!(0, summary.failed_actions);
+    }
+
+    #[test]
+    fn apply_sends_actions_of_items_added_before_a_failed_add() {
+        let mut archived = BackupItem::from(&tagged(1, "https://example.com/a", &[]));
+        archived.archived = true;
+        let failing = BackupItem::from(&tagged(2, "https://example.com/b", &[]));
+        let operations = plan(&backup(vec![archived, failing]), &[]);
+        let pocket = PocketMock {
+            get_mock: unused_get,
+            send_mock: |request| {
+                assert!(matches!(
+                    request.actions,
+                    [PocketSendAction::Archive { item_id: 5, .. }]
+                ));
+                Ok(succeed(request))
+            },
+            add_mock: |request| match request.url.as_str() {
+                "https://example.com/a" => Ok(Item::fixture(5, "https://example.com/a")),
+                _ => Err(BackendError::Response(500, "unavailable".to_string())),
+            },
+        };
+
+        let summary = apply(&pocket, operations).unwrap();
+
+        assert_eq!(1, summary.added);
+        assert_eq!(1, summary.actions);
+        assert_eq!(
+            vec!["https://example.com/b"],
+            summary
+                .failed_adds
+                .iter()
+                .map(|f| f.url.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,300 @@
+use crate::backend::{BackendResult, PocketGet};
+use crate::get::GetOpts;
+use crate::item_ref::{normalize, search_term};
+use crate::models::Item;
+use pocket::PocketGetState;
+use serde::{Deserialize, Serialize};
+use url::{form_urlencoded, Url};
+
+/// How URLs are rewritten before they are saved, so the same page saved from
+/// different links ends up as one item. Read from the `canonical` table of the config.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CanonicalPolicy {
+    /// Query parameters to remove; a trailing `*` matches any suffix
+    pub strip_params: Vec<String>,
+    /// Host prefixes of mobile and AMP sites to remove, such as `m.`
+    pub strip_host_prefixes: Vec<String>,
+    /// Use `https` for `http` URLs
+    pub upgrade_https: bool,
+    pub trailing_slash: TrailingSlash,
+    /// Rules for a domain and its subdomains, applied after the ones above
+    pub domains: Vec<DomainRule>,
+}
+
+impl Default for CanonicalPolicy {
+    fn default() -> Self {
+        CanonicalPolicy {
+            strip_params: [
+                "utm_*", "fbclid", "gclid", "dclid", "msclkid", "mc_cid", "mc_eid", "igshid",
+                "ref", "ref_src", "ref_url",
+            ]
+            .iter()
+            .map(|p| p.to_string())
+            .collect(),
+            strip_host_prefixes: ["m.", "mobile.", "amp."]
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+            upgrade_https: false,
+            trailing_slash: TrailingSlash::Keep,
+            domains: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrailingSlash {
+    Keep,
+    Remove,
+    Add,
+}
+
+impl Default for TrailingSlash {
+    fn default() -> Self {
+        TrailingSlash::Keep
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DomainRule {
+    pub domain: String,
+    /// Host to use instead, such as `twitter.com` for `x.com`
+    pub host: Option<String>,
+    /// Further query parameters to remove
+    pub strip_params: Vec<String>,
+    /// The only query parameters to keep, such as `v` on YouTube
+    pub keep_params: Option<Vec<String>>,
+}
+
+impl CanonicalPolicy {
+    /// The canonical form of `url`.
+    pub fn apply(&self, url: &Url) -> Url {
+        let mut url = url.clone();
+        if self.upgrade_https && url.scheme() == "http" {
+            let _ = url.set_scheme("https");
+        }
+
+        let host = url.host_str().unwrap_or_default().to_lowercase();
+        let stripped = self
+            .strip_host_prefixes
+            .iter()
+            .filter_map(|prefix| host.strip_prefix(prefix.as_str()))
+            // Keep at least a domain and its top level
+            .find(|rest| rest.contains('.'));
+        if let Some(rest) = stripped {
+            let _ = url.set_host(Some(rest));
+        }
+
+        let mut strip = self.strip_params.iter().collect::<Vec<_>>();
+        let mut keep = None;
+        let rules = self
+            .domains
+            .iter()
+            .filter(|r| r.matches(&url))
+            .collect::<Vec<_>>();
+        for rule in rules {
+            if let Some(host) = &rule.host {
+                let _ = url.set_host(Some(host));
+            }
+            strip.extend(&rule.strip_params);
+            keep = rule.keep_params.as_ref().or(keep);
+        }
+        // Kept parameters are copied as they were written, not decoded and encoded again
+        let query = url.query().unwrap_or_default().to_string();
+        let segments = query.split('&').filter(|s| !s.is_empty());
+        let params = segments
+            .clone()
+            .filter(|segment| {
+                let name = form_urlencoded::parse(segment.as_bytes())
+                    .next()
+                    .map(|(name, _)| name.into_owned())
+                    .unwrap_or_default();
+                !strip.iter().any(|p| param_matches(p, &name))
+                    && keep.map_or(true, |keep| keep.contains(&name))
+            })
+            .collect::<Vec<_>>();
+        if params.is_empty() {
+            url.set_query(None);
+        } else if segments.count() != params.len() {
+            url.set_query(Some(&params.join("&")));
+        }
+
+        let path = url.path().to_string();
+        match self.trailing_slash {
+            TrailingSlash::Keep => {}
+            TrailingSlash::Remove if path.len() > 1 && path.ends_with('/') => {
+                url.set_path(path.trim_end_matches('/'));
+            }
+            // A slash after a file name would point somewhere else
+            TrailingSlash::Add if !path.ends_with('/') && !last_segment(&path).contains('.') => {
+                url.set_path(&format!("{}/", path));
+            }
+            TrailingSlash::Remove | TrailingSlash::Add => {}
+        }
+
+        url
+    }
+}
+
+impl DomainRule {
+    fn matches(&self, url: &Url) -> bool {
+        let host = url.host_str().unwrap_or_default();
+        host == self.domain || host.ends_with(&format!(".{}", self.domain))
+    }
+}
+
+fn param_matches(pattern: &str, name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => name == pattern,
+    }
+}
+
+fn last_segment(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or_default()
+}
+
+/// Ids of the items already saved from the page `url` canonicalizes to.
+pub fn find_saved(
+    pocket: &impl PocketGet,
+    policy: &CanonicalPolicy,
+    url: &Url,
+) -> BackendResult<Vec<u64>> {
+    let items = pocket.get(&GetOpts {
+        search: Some(search_term(&policy.apply(url))),
+        state: Some(PocketGetState::All),
+        ..Default::default()
+    })?;
+    Ok(saved_as(&items, policy, url))
+}
+
+/// Ids of those of `items` saved from the page `url` canonicalizes to.
+pub fn saved_as(items: &[Item], policy: &CanonicalPolicy, url: &Url) -> Vec<u64> {
+    let key = normalize(&policy.apply(url));
+    items
+        .iter()
+        .filter(|i| {
+            i.given_url
+                .iter()
+                .chain(i.resolved_url.iter())
+                .any(|u| normalize(&policy.apply(u)) == key)
+        })
+        .map(|i| i.item_id)
+        .collect()
+}
+
+/// Warn on stderr that `url` is already saved, when it is.
+pub fn warn_saved(url: &Url, saved: &[u64]) {
+    if !saved.is_empty() {
+        let item_ids = saved.iter().map(u64::to_string).collect::<Vec<_>>();
+        eprintln!(
+            "warning: {} is already saved as item {}",
+            url,
+            item_ids.join(", ")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(policy: &CanonicalPolicy, url: &str) -> String {
+        policy.apply(&Url::parse(url).unwrap()).to_string()
+    }
+
+    #[test]
+    fn default_policy_strips_tracking_and_mobile_hosts() {
+        let policy = CanonicalPolicy::default();
+
+        assert_eq!(
+            "http://example.com/post?id=3",
+            apply(
+                &policy,
+                "http://m.example.com/post?utm_source=x&id=3&fbclid=y&ref=z"
+            )
+        );
+        assert_eq!(
+            "https://example.com/post",
+            apply(&policy, "https://amp.example.com/post?utm_medium=feed")
+        );
+        assert_eq!("https://m.com/", apply(&policy, "https://m.com/"));
+    }
+
+    #[test]
+    fn upgrade_https_when_asked_to() {
+        let policy = CanonicalPolicy {
+            upgrade_https: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            "https://example.com/post",
+            apply(&policy, "http://example.com/post")
+        );
+    }
+
+    #[test]
+    fn kept_params_are_copied_as_written() {
+        assert_eq!(
+            "https://example.com/s?q=a%20b&tag=c+d&x=%2F",
+            apply(
+                &CanonicalPolicy::default(),
+                "https://example.com/s?q=a%20b&utm_source=x&tag=c+d&x=%2F"
+            )
+        );
+    }
+
+    #[test]
+    fn domain_rules_keep_only_listed_params() {
+        let policy = CanonicalPolicy {
+            domains: vec![DomainRule {
+                domain: "youtube.com".to_string(),
+                host: None,
+                strip_params: vec![],
+                keep_params: Some(vec!["v".to_string()]),
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            "https://www.youtube.com/watch?v=abc",
+            apply(&policy, "https://www.youtube.com/watch?v=abc&list=x&t=3")
+        );
+    }
+
+    #[test]
+    fn trailing_slash_policy() {
+        let mut policy = CanonicalPolicy::default();
+        policy.trailing_slash = TrailingSlash::Remove;
+        assert_eq!(
+            "https://example.com/a",
+            apply(&policy, "https://example.com/a/")
+        );
+        assert_eq!(
+            "https://example.com/",
+            apply(&policy, "https://example.com/")
+        );
+
+        policy.trailing_slash = TrailingSlash::Add;
+        assert_eq!(
+            "https://example.com/a/",
+            apply(&policy, "https://example.com/a")
+        );
+        assert_eq!(
+            "https://example.com/a.pdf",
+            apply(&policy, "https://example.com/a.pdf")
+        );
+    }
+
+    #[test]
+    fn policy_reads_partial_config() {
+        let policy: CanonicalPolicy = toml::from_str("trailing_slash = \"remove\"").unwrap();
+
+        assert_eq!(TrailingSlash::Remove, policy.trailing_slash);
+        assert!(!policy.upgrade_https);
+    }
+}
@@ -1,7 +1,12 @@
+use crate::backend::BackendKind;
+use crate::canonical::CanonicalPolicy;
 use crate::output::Output;
+use crate::tag_policy::TagPolicy;
 use serde::{Deserialize, Serialize};
 use std::io::Write;
+use std::path::PathBuf;
 use structopt::StructOpt;
+use url::Url;
 
 #[derive(Debug, StructOpt)]
 pub enum ConfigOpts {
@@ -17,6 +22,27 @@ pub enum ConfigOpts {
 pub struct Config {
     pub consumer_key: Option<String>,
     pub access_token: Option<String>,
+    pub backend: Option<BackendKind>,
+    #[serde(default, with = "url_serde")]
+    pub api_base_url: Option<Url>,
+    pub local_path: Option<PathBuf>,
+    #[serde(default, with = "url_serde")]
+    pub wallabag_url: Option<Url>,
+    pub wallabag_client_id: Option<String>,
+    pub wallabag_client_secret: Option<String>,
+    pub wallabag_username: Option<String>,
+    pub wallabag_password: Option<String>,
+    #[serde(default, with = "url_serde")]
+    pub linkding_url: Option<Url>,
+    pub linkding_token: Option<String>,
+    /// How URLs are canonicalized before they are added
+    #[serde(default)]
+    pub canonical: Option<CanonicalPolicy>,
+    /// How tags are normalized before they are saved
+    #[serde(default)]
+    pub tag_policy: Option<TagPolicy>,
+    /// File of rules tagging items on `add` and `autotag`
+    pub autotag_rules: Option<PathBuf>,
 }
 
 impl Default for Config {
@@ -24,6 +50,19 @@ impl Default for Config {
         Config {
             consumer_key: None,
             access_token: None,
+            backend: None,
+            api_base_url: None,
+            local_path: None,
+            wallabag_url: None,
+            wallabag_client_id: None,
+            wallabag_client_secret: None,
+            wallabag_username: None,
+            wallabag_password: None,
+            linkding_url: None,
+            linkding_token: None,
+            canonical: None,
+            tag_policy: None,
+            autotag_rules: None,
         }
     }
 }
@@ -40,6 +79,17 @@ pub fn store(cfg: Config) {
 
 const CFG_KEY_CONSUMER_KEY: &str = "consumer_key";
 const CFG_KEY_ACCESS_TOKEN: &str = "access_token";
+const CFG_KEY_BACKEND: &str = "backend";
+const CFG_KEY_API_BASE_URL: &str = "api_base_url";
+const CFG_KEY_LOCAL_PATH: &str = "local_path";
+const CFG_KEY_WALLABAG_URL: &str = "wallabag_url";
+const CFG_KEY_WALLABAG_CLIENT_ID: &str = "wallabag_client_id";
+const CFG_KEY_WALLABAG_CLIENT_SECRET: &str = "wallabag_client_secret";
+const CFG_KEY_WALLABAG_USERNAME: &str = "wallabag_username";
+const CFG_KEY_WALLABAG_PASSWORD: &str = "wallabag_password";
+const CFG_KEY_LINKDING_URL: &str = "linkding_url";
+const CFG_KEY_LINKDING_TOKEN: &str = "linkding_token";
+const CFG_KEY_AUTOTAG_RULES: &str = "autotag_rules";
 
 pub fn handle<W: Write>(opts: &ConfigOpts, output: &mut Output<W>) {
     let mut cfg = load();
@@ -49,6 +99,17 @@ pub fn handle<W: Write>(opts: &ConfigOpts, output: &mut Output<W>) {
             let value = match key.as_str() {
                 CFG_KEY_CONSUMER_KEY => cfg.consumer_key,
                 CFG_KEY_ACCESS_TOKEN => cfg.access_token,
+                CFG_KEY_BACKEND => cfg.backend.map(|b| b.to_string()),
+                CFG_KEY_API_BASE_URL => cfg.api_base_url.map(|u| u.to_string()),
+                CFG_KEY_LOCAL_PATH => cfg.local_path.map(|p| p.display().to_string()),
+                CFG_KEY_WALLABAG_URL => cfg.wallabag_url.map(|u| u.to_string()),
+                CFG_KEY_WALLABAG_CLIENT_ID => cfg.wallabag_client_id,
+                CFG_KEY_WALLABAG_CLIENT_SECRET => cfg.wallabag_client_secret,
+                CFG_KEY_WALLABAG_USERNAME => cfg.wallabag_username,
+                CFG_KEY_WALLABAG_PASSWORD => cfg.wallabag_password,
+                CFG_KEY_LINKDING_URL => cfg.linkding_url.map(|u| u.to_string()),
+                CFG_KEY_LINKDING_TOKEN => cfg.linkding_token,
+                CFG_KEY_AUTOTAG_RULES => cfg.autotag_rules.map(|p| p.display().to_string()),
                 _ => panic!(format!("Invalid key: `{}`", key)),
             }
             .unwrap_or_default();
@@ -58,6 +119,25 @@ pub fn handle<W: Write>(opts: &ConfigOpts, output: &mut Output<W>) {
             match key.as_str() {
                 CFG_KEY_CONSUMER_KEY => cfg.consumer_key = value.clone(),
                 CFG_KEY_ACCESS_TOKEN => cfg.access_token = value.clone(),
+                CFG_KEY_BACKEND => {
+                    cfg.backend = value.as_ref().map(|v| v.parse().unwrap());
+                }
+                CFG_KEY_API_BASE_URL => {
+                    cfg.api_base_url = value.as_ref().map(|v| Url::parse(v).unwrap());
+                }
+                CFG_KEY_LOCAL_PATH => cfg.local_path = value.as_ref().map(PathBuf::from),
+                CFG_KEY_WALLABAG_URL => {
+                    cfg.wallabag_url = value.as_ref().map(|v| Url::parse(v).unwrap());
+                }
+                CFG_KEY_WALLABAG_CLIENT_ID => cfg.wallabag_client_id = value.clone(),
+                CFG_KEY_WALLABAG_CLIENT_SECRET => cfg.wallabag_client_secret = value.clone(),
+                CFG_KEY_WALLABAG_USERNAME => cfg.wallabag_username = value.clone(),
+                CFG_KEY_WALLABAG_PASSWORD => cfg.wallabag_password = value.clone(),
+                CFG_KEY_LINKDING_URL => {
+                    cfg.linkding_url = value.as_ref().map(|v| Url::parse(v).unwrap());
+                }
+                CFG_KEY_LINKDING_TOKEN => cfg.linkding_token = value.clone(),
+                CFG_KEY_AUTOTAG_RULES => cfg.autotag_rules = value.as_ref().map(PathBuf::from),
                 _ => panic!(format!("Invalid key: `{}`", key)),
             };
             store(cfg);
@@ -0,0 +1,403 @@
+use crate::backend::{send_all, BackendResult, PocketGet, PocketSend};
+use crate::canonical::CanonicalPolicy;
+use crate::get::GetOpts;
+use crate::item_ref::normalize;
+use crate::models::{IndividualSendResponse, Item};
+use crate::output::Output;
+use chrono::{DateTime, Utc};
+use pocket::{PocketGetDetail, PocketGetState, PocketSendAction};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::io::Write;
+use structopt::StructOpt;
+use url::Url;
+
+/// Titles with fewer words are too generic to tell items apart
+const MIN_TITLE_WORDS: usize = 3;
+
+#[derive(Debug, StructOpt)]
+pub struct DedupeOpts {
+    /// Keep one item of each group, tag it with the tags of the others and delete them
+    #[structopt(long)]
+    pub apply: bool,
+    /// Also list items with nearly the same title. These are never deleted
+    #[structopt(long)]
+    pub titles: bool,
+    /// Share of title words two items need in common to be listed with --titles
+    #[structopt(long, default_value = "0.9")]
+    pub title_similarity: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Deduped {
+    pub groups: Vec<Group>,
+    /// Items with nearly the same title but not the same page, with --titles
+    pub similar_titles: Vec<Vec<GroupItem>>,
+    /// Responses to tagging the kept items and deleting the rest, with --apply
+    pub actions: Vec<IndividualSendResponse>,
+}
+
+/// Items saved more than once, the one to keep first.
+#[derive(Debug, Serialize)]
+pub struct Group {
+    pub keep: u64,
+    pub delete: Vec<u64>,
+    /// Tags of the deleted items the kept one lacks
+    pub tags_added: Vec<String>,
+    pub items: Vec<GroupItem>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GroupItem {
+    pub item_id: u64,
+    pub title: Option<String>,
+    #[serde(with = "url_serde")]
+    pub url: Option<Url>,
+    pub time_added: Option<DateTime<Utc>>,
+    pub favorite: bool,
+}
+
+pub fn handle<W: Write>(
+    pocket: &(impl PocketGet + PocketSend),
+    opts: &DedupeOpts,
+    policy: &CanonicalPolicy,
+    output: &mut Output<W>,
+) {
+    let deduped = dedupe(pocket, opts, policy).unwrap();
+    output.write(deduped).unwrap();
+}
+
+pub fn dedupe(
+    pocket: &(impl PocketGet + PocketSend),
+    opts: &DedupeOpts,
+    policy: &CanonicalPolicy,
+) -> BackendResult<Deduped> {
+    let items = pocket.get(&GetOpts {
+        state: Some(PocketGetState::All),
+        detail_type: Some(PocketGetDetail::Complete),
+        ..Default::default()
+    })?;
+    let groups = groups(&items, policy);
+    let similar_titles = if opts.titles {
+        similar_titles(&items, &groups, opts.title_similarity)
+    } else {
+        vec![]
+    };
+
+    let mut actions = Vec::new();
+    if opts.apply {
+        for group in &groups {
+            if !group.tags_added.is_empty() {
+                actions.push(PocketSendAction::TagsAdd {
+                    item_id: group.keep,
+                    tags: group.tags_added.join(","),
+                    time: None,
+                });
+            }
+            actions.extend(group.delete.iter().map(|item_id| PocketSendAction::Delete {
+                item_id: *item_id,
+                time: None,
+            }));
+        }
+    }
+    let actions = if actions.is_empty() {
+        vec![]
+    } else {
+        IndividualSendResponse::each(send_all(pocket, &actions)?)
+    };
+
+    Ok(Deduped {
+        groups,
+        similar_titles,
+        actions,
+    })
+}
+
+/// Group items sharing a resolved id or a canonical URL.
+pub fn groups(items: &[Item], policy: &CanonicalPolicy) -> Vec<Group> {
+    let mut sets = DisjointSets::new(items.len());
+
+    let mut first_with_key = HashMap::new();
+    for (i, item) in items.iter().enumerate() {
+        for key in keys(item, policy) {
+            let first = *first_with_key.entry(key).or_insert(i);
+            sets.union(first, i);
+        }
+    }
+
+    let mut groups = sets
+        .members(items)
+        .into_iter()
+        .map(group)
+        .collect::<Vec<_>>();
+    groups.sort_by_key(|g| g.keep);
+    groups
+}
+
+/// Sets of items with nearly the same title, leaving out the items `groups` deletes.
+///
+/// Different pages can share a title, so these are only listed, never deleted.
+pub fn similar_titles(
+    items: &[Item],
+    groups: &[Group],
+    min_similarity: f64,
+) -> Vec<Vec<GroupItem>> {
+    let deleted = groups
+        .iter()
+        .flat_map(|g| g.delete.iter())
+        .collect::<BTreeSet<_>>();
+    let items = items
+        .iter()
+        .filter(|i| !deleted.contains(&i.item_id))
+        .collect::<Vec<_>>();
+
+    let mut sets = DisjointSets::new(items.len());
+    let titles = items.iter().map(|i| title_words(i)).collect::<Vec<_>>();
+    for (i, a) in titles.iter().enumerate() {
+        for (j, b) in titles.iter().enumerate().skip(i + 1) {
+            if similarity(a, b) >= min_similarity {
+                sets.union(i, j);
+            }
+        }
+    }
+
+    sets.members(&items)
+        .into_iter()
+        .map(|items| items.into_iter().map(|i| group_item(i)).collect())
+        .collect()
+}
+
+/// What identifies the page an item was saved from.
+fn keys(item: &Item, policy: &CanonicalPolicy) -> Vec<String> {
+    let mut keys = item
+        .resolved_url
+        .iter()
+        .chain(item.given_url.iter())
+        .chain(item.amp_url.iter())
+        .map(|url| format!("url:{}", normalize(&policy.apply(url))))
+        .collect::<Vec<_>>();
+    if item.resolved_id != 0 {
+        keys.push(format!("id:{}", item.resolved_id));
+    }
+    keys
+}
+
+/// Favorites first, then the oldest.
+fn group(mut items: Vec<&Item>) -> Group {
+    items.sort_by_key(|i| {
+        (
+            !i.favorite.unwrap_or_default(),
+            i.time_added.is_none(),
+            i.time_added,
+            i.item_id,
+        )
+    });
+    let keep = items[0];
+    let kept_tags = keep.tag_names().into_iter().collect::<BTreeSet<_>>();
+    let tags_added = items[1..]
+        .iter()
+        .flat_map(|i| i.tag_names())
+        .filter(|t| !kept_tags.contains(t))
+        .collect::<BTreeSet<_>>();
+
+    Group {
+        keep: keep.item_id,
+        delete: items[1..].iter().map(|i| i.item_id).collect(),
+        tags_added: tags_added.into_iter().map(str::to_string).collect(),
+        items: items.into_iter().map(group_item).collect(),
+    }
+}
+
+fn group_item(item: &Item) -> GroupItem {
+    GroupItem {
+        item_id: item.item_id,
+        title: item.title().map(str::to_string),
+        url: item.url().cloned(),
+        time_added: item.time_added,
+        favorite: item.favorite.unwrap_or_default(),
+    }
+}
+
+fn title_words(item: &Item) -> BTreeSet<String> {
+    let title = item.title().unwrap_or_default().to_lowercase();
+    title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Words in common out of all words, when both titles are long enough to compare.
+fn similarity(a: &BTreeSet<String>, b: &BTreeSet<String>) -> f64 {
+    if a.len() < MIN_TITLE_WORDS || b.len() < MIN_TITLE_WORDS {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / a.union(b).count() as f64
+}
+
+struct DisjointSets(Vec<usize>);
+
+impl DisjointSets {
+    fn new(len: usize) -> DisjointSets {
+        DisjointSets((0..len).collect())
+    }
+
+    fn find(&mut self, i: usize) -> usize {
+        let parent = self.0[i];
+        if parent == i {
+            return i;
+        }
+        let root = self.find(parent);
+        self.0[i] = root;
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        self.0[a.max(b)] = a.min(b);
+    }
+
+    /// The sets with more than one element, in the order of their first element.
+    fn members<T: Copy>(&mut self, elements: impl IntoIterator<Item = T>) -> Vec<Vec<T>> {
+        let mut members: HashMap<usize, Vec<T>> = HashMap::new();
+        for (i, element) in elements.into_iter().enumerate() {
+            members.entry(self.find(i)).or_default().push(element);
+        }
+        let mut members = members
+            .into_iter()
+            .filter(|(_, set)| set.len() > 1)
+            .collect::<Vec<_>>();
+        members.sort_by_key(|(root, _)| *root);
+        members.into_iter().map(|(_, set)| set).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::{succeed, unused_add, unused_send, PocketMock};
+    use chrono::TimeZone;
+
+    fn opts(apply: bool) -> DedupeOpts {
+        DedupeOpts {
+            apply,
+            titles: true,
+            title_similarity: 0.9,
+        }
+    }
+
+    fn titled(item_id: u64, url: &str, title: &str) -> Item {
+        let mut item = Item::fixture(item_id, url);
+        item.given_title = Some(title.to_string());
+        item
+    }
+
+    fn group_ids(groups: &[Group]) -> Vec<Vec<u64>> {
+        groups
+            .iter()
+            .map(|g| g.items.iter().map(|i| i.item_id).collect())
+            .collect()
+    }
+
+    #[test]
+    fn groups_match_canonical_urls_resolved_ids_and_amp_urls() {
+        let mut amp = Item::fixture(4, "https://amp.example.com/a");
+        amp.amp_url = Some(Url::parse("https://example.com/a/").unwrap());
+        let mut resolved = Item::fixture(6, "https://short.link/x");
+        resolved.resolved_id = 5;
+        let items = vec![
+            Item::fixture(1, "https://example.com/a"),
+            Item::fixture(2, "http://www.example.com/a/"),
+            Item::fixture(3, "https://example.com/b"),
+            amp,
+            Item::fixture(5, "https://example.com/c"),
+            resolved,
+            Item::fixture(7, "https://m.example.com/a?utm_source=feed"),
+        ];
+
+        let groups = groups(&items, &CanonicalPolicy::default());
+
+        assert_eq!(vec![vec![1, 2, 4, 7], vec![5, 6]], group_ids(&groups));
+    }
+
+    #[test]
+    fn similar_titles_lists_items_groups_keep() {
+        let items = vec![
+            titled(1, "https://a.com/1", "The Rust Programming Language"),
+            titled(2, "https://b.com/2", "The Rust programming language!"),
+            titled(3, "https://c.com/3", "The Go Programming Language"),
+            titled(4, "https://d.com/4", "Home"),
+            titled(5, "https://e.com/5", "Home"),
+            titled(6, "https://a.com/1", "The Rust Programming Language"),
+        ];
+        let groups = groups(&items, &CanonicalPolicy::default());
+
+        assert_eq!(vec![vec![1, 6]], group_ids(&groups));
+        let similar = similar_titles(&items, &groups, 0.9)
+            .iter()
+            .map(|items| items.iter().map(|i| i.item_id).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![vec![1, 2]], similar);
+    }
+
+    #[test]
+    fn dedupe_never_deletes_items_only_sharing_a_title() {
+        let pocket = PocketMock {
+            get_mock: |_| {
+                Ok(vec![
+                    titled(1, "https://a.com/1", "The Rust Programming Language"),
+                    titled(2, "https://b.com/2", "The Rust Programming Language"),
+                ])
+            },
+            send_mock: unused_send,
+            add_mock: unused_add,
+        };
+
+        let deduped = dedupe(&pocket, &opts(true), &CanonicalPolicy::default()).unwrap();
+
+        assert!(deduped.groups.is_empty());
+        assert_eq!(1, deduped.similar_titles.len());
+        assert!(deduped.actions.is_empty());
+    }
+
+    #[test]
+    fn group_keeps_favorite_then_oldest() {
+        let mut old = Item::fixture(1, "https://example.com/a");
+        old.time_added = Some(Utc.ymd(2019, 1, 1).and_hms(0, 0, 0));
+        let mut new = Item::fixture(2, "https://example.com/a");
+        new.time_added = Some(Utc.ymd(2020, 1, 1).and_hms(0, 0, 0));
+        let mut favorite = Item::fixture(3, "https://example.com/a");
+        favorite.favorite = Some(true);
+
+        assert_eq!(1, group(vec![&new, &old]).keep);
+        assert_eq!(3, group(vec![&new, &old, &favorite]).keep);
+    }
+
+    #[test]
+    fn dedupe_applies_tags_and_deletes_in_one_send() {
+        let pocket = PocketMock {
+            get_mock: |_| {
+                Ok(vec![
+                    Item::fixture(1, "https://example.com/a").with_tags(&["rust"]),
+                    Item::fixture(2, "https://example.com/a").with_tags(&["rust", "later"]),
+                ])
+            },
+            send_mock: |request| {
+                assert!(matches!(
+                    request.actions,
+                    [
+                        PocketSendAction::TagsAdd { item_id: 1, tags, .. },
+                        PocketSendAction::Delete { item_id: 2, .. }
+                    ] if tags == "later"
+                ));
+                Ok(succeed(request))
+            },
+            add_mock: unused_add,
+        };
+
+        let deduped = dedupe(&pocket, &opts(true), &CanonicalPolicy::default()).unwrap();
+
+        assert_eq!(vec!["later"], deduped.groups[0].tags_added);
+        assert_eq!(2, deduped.actions.len());
+    }
+}
@@ -0,0 +1,279 @@
+use crate::models::Item;
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::fmt::Write;
+use std::str::FromStr;
+use url::Url;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FeedFormat {
+    Atom,
+    Rss,
+}
+
+impl FromStr for FeedFormat {
+    type Err = ParseFeedFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "atom" => Ok(FeedFormat::Atom),
+            "rss" => Ok(FeedFormat::Rss),
+            s => Err(ParseFeedFormatError(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseFeedFormatError(String);
+
+impl fmt::Display for ParseFeedFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "provided invalid feed format: {}", self.0)
+    }
+}
+
+pub struct Channel<'a> {
+    pub title: &'a str,
+    pub link: &'a Url,
+    pub description: &'a str,
+}
+
+pub fn render(channel: &Channel, items: &[Item], format: FeedFormat) -> String {
+    let mut items = items.iter().collect::<Vec<_>>();
+    items.sort_by(|a, b| updated(b).cmp(&updated(a)));
+
+    match format {
+        FeedFormat::Atom => atom(channel, &items),
+        FeedFormat::Rss => rss(channel, &items),
+    }
+}
+
+fn atom(channel: &Channel, items: &[&Item]) -> String {
+    let mut xml = String::new();
+    let feed_updated = items.first().map(|i| updated(i)).unwrap_or_else(Utc::now);
+
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    writeln!(xml, "  <title>{}</title>", escape(channel.title)).unwrap();
+    writeln!(
+        xml,
+        "  <subtitle>{}</subtitle>",
+        escape(channel.description)
+    )
+    .unwrap();
+    writeln!(xml, "  <link href=\"{}\"/>", escape(channel.link.as_str())).unwrap();
+    writeln!(xml, "  <id>{}</id>", escape(channel.link.as_str())).unwrap();
+    writeln!(xml, "  <updated>{}</updated>", feed_updated.to_rfc3339()).unwrap();
+    // Atom requires an author on the feed when an entry has none
+    writeln!(
+        xml,
+        "  <author><name>{}</name></author>",
+        escape(channel.title)
+    )
+    .unwrap();
+
+    for item in items {
+        xml.push_str("  <entry>\n");
+        writeln!(xml, "    <title>{}</title>", escape(title(item))).unwrap();
+        if let Some(url) = item.url() {
+            writeln!(xml, "    <link href=\"{}\"/>", escape(url.as_str())).unwrap();
+        }
+        writeln!(xml, "    <id>{}</id>", id(item)).unwrap();
+        writeln!(xml, "    <updated>{}</updated>", updated(item).to_rfc3339()).unwrap();
+        if let Some(added) = item.time_added {
+            writeln!(xml, "    <published>{}</published>", added.to_rfc3339()).unwrap();
+        }
+        writeln!(
+            xml,
+            "    <summary>{}</summary>",
+            escape(item.excerpt.trim())
+        )
+        .unwrap();
+        for author in item.author_names() {
+            writeln!(xml, "    <author><name>{}</name></author>", escape(author)).unwrap();
+        }
+        for tag in item.tag_names() {
+            writeln!(xml, "    <category term=\"{}\"/>", escape(tag)).unwrap();
+        }
+        xml.push_str("  </entry>\n");
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn rss(channel: &Channel, items: &[&Item]) -> String {
+    let mut xml = String::new();
+    let build_date = items.first().map(|i| updated(i)).unwrap_or_else(Utc::now);
+
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str(
+        "<rss version=\"2.0\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n  <channel>\n",
+    );
+    writeln!(xml, "    <title>{}</title>", escape(channel.title)).unwrap();
+    writeln!(xml, "    <link>{}</link>", escape(channel.link.as_str())).unwrap();
+    writeln!(
+        xml,
+        "    <description>{}</description>",
+        escape(channel.description)
+    )
+    .unwrap();
+    writeln!(
+        xml,
+        "    <lastBuildDate>{}</lastBuildDate>",
+        build_date.to_rfc2822()
+    )
+    .unwrap();
+
+    for item in items {
+        xml.push_str("    <item>\n");
+        writeln!(xml, "      <title>{}</title>", escape(title(item))).unwrap();
+        if let Some(url) = item.url() {
+            writeln!(xml, "      <link>{}</link>", escape(url.as_str())).unwrap();
+        }
+        writeln!(
+            xml,
+            "      <description>{}</description>",
+            escape(item.excerpt.trim())
+        )
+        .unwrap();
+        // RSS `author` must be an email address, so names go in `dc:creator`
+        for author in item.author_names() {
+            writeln!(xml, "      <dc:creator>{}</dc:creator>", escape(author)).unwrap();
+        }
+        for tag in item.tag_names() {
+            writeln!(xml, "      <category>{}</category>", escape(tag)).unwrap();
+        }
+        writeln!(xml, "      <guid isPermaLink=\"false\">{}</guid>", id(item)).unwrap();
+        writeln!(
+            xml,
+            "      <pubDate>{}</pubDate>",
+            updated(item).to_rfc2822()
+        )
+        .unwrap();
+        xml.push_str("    </item>\n");
+    }
+
+    xml.push_str("  </channel>\n</rss>\n");
+    xml
+}
+
+fn title(item: &Item) -> &str {
+    item.title()
+        .or_else(|| item.url().map(|u| u.as_str()))
+        .unwrap_or_default()
+}
+
+fn id(item: &Item) -> String {
+    format!("urn:pocket:item:{}", item.item_id)
+}
+
+fn updated(item: &Item) -> DateTime<Utc> {
+    item.time_updated
+        .or(item.time_added)
+        .unwrap_or_else(|| DateTime::from(std::time::UNIX_EPOCH))
+}
+
+/// Escape `s` for XML, dropping characters XML 1.0 does not allow, such as control
+/// characters left in titles and excerpts by the page they were read from.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars().filter(|c| allowed(*c)) {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Whether `c` is in the XML 1.0 `Char` production.
+fn allowed(c: char) -> bool {
+    matches!(
+        c,
+        '\t' | '\n' | '\r' | '\u{20}'..='\u{D7FF}' | '\u{E000}'..='\u{FFFD}' | '\u{10000}'..='\u{10FFFF}'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Author, Tag};
+
+    fn item() -> Item {
+        let mut item = Item::fixture(7, "https://example.com/?a=1&b=2");
+        item.resolved_title = Some("Fish & Chips".to_string());
+        item.excerpt = "<tasty>".to_string();
+        item.tags = Some(vec![Tag("share".to_string())]);
+        item.authors = Some(vec![Author {
+            item_id: 7,
+            author_id: 1,
+            name: "Jane".to_string(),
+            url: "".to_string(),
+        }]);
+        item
+    }
+
+    fn channel_link() -> Url {
+        Url::parse("https://example.org/feed").unwrap()
+    }
+
+    #[test]
+    fn atom_contains_escaped_entry() {
+        let link = channel_link();
+        let channel = Channel {
+            title: "Team",
+            link: &link,
+            description: "Reading",
+        };
+
+        let xml = render(&channel, &[item()], FeedFormat::Atom);
+
+        assert!(xml.contains("<title>Fish &amp; Chips</title>"));
+        assert!(xml.contains("<link href=\"https://example.com/?a=1&amp;b=2\"/>"));
+        assert!(xml.contains("<summary>&lt;tasty&gt;</summary>"));
+        assert!(xml.contains("<author><name>Jane</name></author>"));
+        assert!(xml.contains("<category term=\"share\"/>"));
+    }
+
+    #[test]
+    fn feed_drops_characters_illegal_in_xml() {
+        let link = channel_link();
+        let channel = Channel {
+            title: "Team",
+            link: &link,
+            description: "Reading",
+        };
+        let mut item = item();
+        item.resolved_title = Some("Fish\u{1} & Chips\u{FFFE}".to_string());
+        item.excerpt = "tasty\u{0}\u{1F}\tfood".to_string();
+
+        for format in &[FeedFormat::Atom, FeedFormat::Rss] {
+            let xml = render(&channel, std::slice::from_ref(&item), *format);
+
+            assert!(xml.contains("<title>Fish &amp; Chips</title>"));
+            assert!(xml.contains("tasty\tfood"));
+            assert!(!xml.contains('\u{0}') && !xml.contains('\u{1}'));
+        }
+    }
+
+    #[test]
+    fn rss_contains_item() {
+        let link = channel_link();
+        let channel = Channel {
+            title: "Team",
+            link: &link,
+            description: "Reading",
+        };
+
+        let xml = render(&channel, &[item()], FeedFormat::Rss);
+
+        assert!(xml.contains("<guid isPermaLink=\"false\">urn:pocket:item:7</guid>"));
+        assert!(xml.contains("<dc:creator>Jane</dc:creator>"));
+        assert!(xml.contains("<category>share</category>"));
+    }
+}
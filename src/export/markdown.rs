@@ -0,0 +1,311 @@
+use crate::models::{Item, ItemStatus};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const EXCERPT_START: &str = "<!-- pocket:excerpt:start -->";
+const EXCERPT_END: &str = "<!-- pocket:excerpt:end -->";
+
+#[derive(Debug, Serialize)]
+pub struct Note {
+    pub item_id: u64,
+    pub path: PathBuf,
+    pub action: NoteAction,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NoteAction {
+    Created,
+    Updated,
+    Unchanged,
+    /// The note's frontmatter could not be read, so it was left alone
+    Skipped,
+}
+
+#[derive(Serialize)]
+struct Frontmatter<'a> {
+    item_id: u64,
+    resolved_url: Option<String>,
+    tags: Vec<&'a str>,
+    authors: Vec<&'a str>,
+    time_added: Option<DateTime<Utc>>,
+    time_read: Option<DateTime<Utc>>,
+    favorite: Option<bool>,
+    status: Option<&'a ItemStatus>,
+    word_count: usize,
+}
+
+/// Write one note per item into `dir`. Notes already in the vault are found by the
+/// `item_id` in their frontmatter, or else by their file name, and only have the
+/// fields and excerpt owned by pocket rewritten, so anything the user added is left
+/// in place.
+pub fn export(items: &[Item], dir: &Path) -> io::Result<Vec<Note>> {
+    fs::create_dir_all(dir)?;
+    let mut index = HashMap::new();
+    index_notes(dir, &mut index)?;
+
+    items
+        .iter()
+        .map(|item| {
+            let path = match index.get(&item.item_id) {
+                Some(path) => path.clone(),
+                None => dir.join(file_name(item)),
+            };
+            // A note named for the item but not indexed has frontmatter that can't be read
+            let action = if path.exists() {
+                let existing = fs::read_to_string(&path)?;
+                match update(&existing, item) {
+                    None => NoteAction::Skipped,
+                    Some(updated) if updated == existing => NoteAction::Unchanged,
+                    Some(updated) => {
+                        fs::write(&path, updated)?;
+                        NoteAction::Updated
+                    }
+                }
+            } else {
+                fs::write(&path, render(item))?;
+                index.insert(item.item_id, path.clone());
+                NoteAction::Created
+            };
+            Ok(Note {
+                item_id: item.item_id,
+                path,
+                action,
+            })
+        })
+        .collect()
+}
+
+fn index_notes(dir: &Path, index: &mut HashMap<u64, PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            index_notes(&path, index)?;
+        } else if path.extension().map_or(false, |e| e == "md") {
+            // Notes that are not valid UTF-8 cannot be pocket notes
+            if let Ok(content) = fs::read_to_string(&path) {
+                if let Some(item_id) = note_item_id(&content) {
+                    index.insert(item_id, path);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn note_item_id(content: &str) -> Option<u64> {
+    let (yaml, _) = split_frontmatter(content)?;
+    let mapping: Mapping = serde_yaml::from_str(yaml).ok()?;
+    mapping.get(&Value::from("item_id"))?.as_u64()
+}
+
+fn render(item: &Item) -> String {
+    let body = format!(
+        "\n# {}\n\n{}\n",
+        item.title().unwrap_or_default(),
+        excerpt_block(item)
+    );
+    format!("---\n{}\n---\n{}", yaml(&frontmatter(item)), body)
+}
+
+/// The note with the fields and excerpt owned by pocket rewritten, or `None` when
+/// its frontmatter is not a YAML mapping, as rewriting it would lose what it holds.
+fn update(existing: &str, item: &Item) -> Option<String> {
+    let (mapping, body) = match split_frontmatter(existing) {
+        Some((yaml, body)) => {
+            let mut mapping: Mapping = serde_yaml::from_str(yaml).ok()?;
+            for (key, value) in frontmatter(item) {
+                match mapping.get_mut(&key) {
+                    Some(v) => *v = value,
+                    None => {
+                        mapping.insert(key, value);
+                    }
+                }
+            }
+            (mapping, body)
+        }
+        None => (frontmatter(item), existing),
+    };
+
+    Some(format!(
+        "---\n{}\n---\n{}",
+        yaml(&mapping),
+        replace_excerpt(body, item)
+    ))
+}
+
+fn frontmatter(item: &Item) -> Mapping {
+    let frontmatter = Frontmatter {
+        item_id: item.item_id,
+        resolved_url: item.resolved_url.as_ref().map(|u| u.to_string()),
+        tags: item.tag_names(),
+        authors: item.author_names(),
+        time_added: item.time_added,
+        time_read: item.time_read,
+        favorite: item.favorite,
+        status: item.status.as_ref(),
+        word_count: item.word_count,
+    };
+
+    match serde_yaml::to_value(frontmatter).unwrap() {
+        Value::Mapping(mapping) => mapping,
+        _ => unreachable!("frontmatter serializes to a mapping"),
+    }
+}
+
+fn yaml(mapping: &Mapping) -> String {
+    let yaml = serde_yaml::to_string(mapping).unwrap();
+    yaml.trim_start_matches("---").trim().to_string()
+}
+
+/// The YAML between the opening `---` line and the next line that is only `---`,
+/// and the body after it. Lines may end in `\r\n`.
+fn split_frontmatter(content: &str) -> Option<(&str, &str)> {
+    let rest = content
+        .strip_prefix("---\n")
+        .or_else(|| content.strip_prefix("---\r\n"))?;
+    let mut start = 0;
+    while start < rest.len() {
+        let end = rest[start..]
+            .find('\n')
+            .map_or(rest.len(), |i| start + i + 1);
+        if rest[start..end].trim_end_matches(|c: char| c == '\n' || c == '\r') == "---" {
+            return Some((&rest[..start], &rest[end..]));
+        }
+        start = end;
+    }
+    None
+}
+
+fn excerpt_block(item: &Item) -> String {
+    format!(
+        "{}\n{}\n{}",
+        EXCERPT_START,
+        item.excerpt.trim(),
+        EXCERPT_END
+    )
+}
+
+/// Notes whose excerpt markers were removed are left alone.
+fn replace_excerpt(body: &str, item: &Item) -> String {
+    match (body.find(EXCERPT_START), body.find(EXCERPT_END)) {
+        (Some(start), Some(end)) if start < end => format!(
+            "{}{}{}",
+            &body[..start],
+            excerpt_block(item),
+            &body[end + EXCERPT_END.len()..]
+        ),
+        _ => body.to_string(),
+    }
+}
+
+fn file_name(item: &Item) -> String {
+    let slug = slug(item.title().unwrap_or_default());
+    if slug.is_empty() {
+        format!("{}.md", item.item_id)
+    } else {
+        format!("{}-{}.md", slug, item.item_id)
+    }
+}
+
+fn slug(title: &str) -> String {
+    let slug = title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    slug.chars()
+        .take(60)
+        .collect::<String>()
+        .trim_end_matches('-')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Tag;
+
+    fn item() -> Item {
+        let mut item = Item::fixture(42, "https://example.com/post");
+        item.resolved_title = Some("Hello, World!".to_string());
+        item.excerpt = "An excerpt.".to_string();
+        item.tags = Some(vec![Tag("rust".to_string())]);
+        item
+    }
+
+    #[test]
+    fn render_is_unchanged_by_update() {
+        let note = render(&item());
+
+        assert_eq!(Some(note.clone()), update(&note, &item()));
+        assert_eq!(Some(42), note_item_id(&note));
+    }
+
+    #[test]
+    fn update_keeps_user_content() {
+        let note = render(&item()).replace("---\n\n#", "---\n\nMy notes\n\n#");
+        let note = note.replacen("item_id", "rating: 5\nitem_id", 1);
+        let mut changed = item();
+        changed.excerpt = "A new excerpt.".to_string();
+
+        let updated = update(&note, &changed).unwrap();
+
+        assert!(updated.contains("rating: 5"));
+        assert!(updated.contains("My notes"));
+        assert!(updated.contains("A new excerpt."));
+        assert!(!updated.contains("An excerpt."));
+    }
+
+    #[test]
+    fn update_leaves_unreadable_frontmatter_alone() {
+        let note = "---\nitem_id: 42\nrating: [5\n---\n\nMy notes\n";
+
+        assert_eq!(None, update(note, &item()));
+    }
+
+    #[test]
+    fn split_frontmatter_ends_at_a_line_of_only_dashes() {
+        let note = "---\nitem_id: 42\n---- not the end\n---\n---more\n";
+
+        assert_eq!(
+            Some(("item_id: 42\n---- not the end\n", "---more\n")),
+            split_frontmatter(note)
+        );
+    }
+
+    #[test]
+    fn note_item_id_reads_crlf_notes() {
+        assert_eq!(
+            Some(42),
+            note_item_id("---\r\nitem_id: 42\r\n---\r\n\r\nMy notes\r\n")
+        );
+    }
+
+    #[test]
+    fn export_skips_named_note_with_unreadable_frontmatter() {
+        let dir = std::env::temp_dir().join(format!("pocket-markdown-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(file_name(&item()));
+        let note = "---\nitem_id: 42\nrating: [5\n---\n\nMy notes\n";
+        fs::write(&path, note).unwrap();
+
+        let notes = export(&[item()], &dir).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(NoteAction::Skipped, notes[0].action);
+        assert_eq!(note, content);
+    }
+
+    #[test]
+    fn file_name_uses_title_slug() {
+        assert_eq!("hello-world-42.md", file_name(&item()));
+    }
+}
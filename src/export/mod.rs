@@ -0,0 +1,185 @@
+use crate::backend::{BackendResult, PocketGet};
+use crate::get::{self, GetOpts};
+use crate::models::Item;
+use crate::output::Output;
+use pocket::{PocketGetDetail, PocketGetState};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+use url::Url;
+
+pub mod feed;
+pub mod markdown;
+pub mod opml;
+pub mod org;
+
+#[derive(Debug, StructOpt)]
+pub enum Export {
+    /// Atom or RSS feed
+    Feed {
+        /// Feed format, `atom` or `rss`
+        #[structopt(long, default_value = "atom")]
+        format: feed::FeedFormat,
+        /// Feed title
+        #[structopt(long, default_value = "Pocket")]
+        title: String,
+        /// Feed description
+        #[structopt(long, default_value = "Saved items")]
+        description: String,
+        /// Link to the site or feed location
+        #[structopt(long, default_value = "https://getpocket.com/")]
+        link: Url,
+        /// File to write to instead of stdout
+        #[structopt(long, parse(from_os_str))]
+        file: Option<PathBuf>,
+        #[structopt(flatten)]
+        opts: GetOpts,
+    },
+    /// Markdown notes with YAML frontmatter, one per item
+    Markdown {
+        /// Vault directory to write notes to
+        #[structopt(long, parse(from_os_str))]
+        dir: PathBuf,
+        #[structopt(flatten)]
+        opts: GetOpts,
+    },
+    /// OPML outline of the sites items were saved from
+    Opml {
+        /// File to write to instead of stdout
+        #[structopt(long, parse(from_os_str))]
+        file: Option<PathBuf>,
+        #[structopt(flatten)]
+        opts: GetOpts,
+    },
+    /// Org-mode outline
+    Org {
+        /// Group items under `tag` or `domain` headings, or `none`
+        #[structopt(long, default_value = "none")]
+        group_by: org::GroupBy,
+        /// File to write to instead of stdout
+        #[structopt(long, parse(from_os_str))]
+        file: Option<PathBuf>,
+        #[structopt(flatten)]
+        opts: GetOpts,
+    },
+}
+
+pub fn handle<W: Write>(pocket: &impl PocketGet, opts: &Export, output: &mut Output<W>) {
+    match export(pocket, opts).unwrap() {
+        Exported::Text(text) => write_text(opts.file(), &text, output),
+        Exported::Notes(notes) => output.write(notes).unwrap(),
+    }
+}
+
+/// What an export produced: rendered text, or the notes written to the vault.
+#[derive(Debug)]
+pub enum Exported {
+    Text(String),
+    Notes(Vec<markdown::Note>),
+}
+
+pub fn export(pocket: &impl PocketGet, opts: &Export) -> BackendResult<Exported> {
+    Ok(match opts {
+        Export::Feed {
+            format,
+            title,
+            description,
+            link,
+            opts,
+            ..
+        } => {
+            let channel = feed::Channel {
+                title,
+                link,
+                description,
+            };
+            Exported::Text(feed::render(
+                &channel,
+                &items(pocket, opts, PocketGetState::Unread)?,
+                *format,
+            ))
+        }
+        Export::Markdown { dir, opts } => Exported::Notes(markdown::export(
+            &items(pocket, opts, PocketGetState::All)?,
+            dir,
+        )?),
+        Export::Opml { opts, .. } => {
+            Exported::Text(opml::render(&items(pocket, opts, PocketGetState::All)?))
+        }
+        Export::Org { group_by, opts, .. } => Exported::Text(org::render(
+            &items(pocket, opts, PocketGetState::All)?,
+            *group_by,
+        )),
+    })
+}
+
+impl Export {
+    fn file(&self) -> Option<&Path> {
+        match self {
+            Export::Feed { file, .. } | Export::Opml { file, .. } | Export::Org { file, .. } => {
+                file.as_deref()
+            }
+            Export::Markdown { .. } => None,
+        }
+    }
+}
+
+fn write_text<W: Write>(file: Option<&Path>, text: &str, output: &mut Output<W>) {
+    match file {
+        Some(path) => {
+            fs::write(path, text).unwrap();
+            output.write("Success").unwrap();
+        }
+        None => output.write_text(text).unwrap(),
+    }
+}
+
+/// Exports cover items in `state` with complete detail unless the filter says otherwise.
+/// Feeds follow `get` and show unread items, the other exports cover every item.
+fn items(
+    pocket: &impl PocketGet,
+    opts: &GetOpts,
+    state: PocketGetState,
+) -> BackendResult<Vec<Item>> {
+    let mut opts = opts.clone();
+    opts.state = opts.state.or(Some(state));
+    opts.detail_type = opts.detail_type.or(Some(PocketGetDetail::Complete));
+    get::get(pocket, &opts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::{unused_add, unused_send, PocketMock};
+
+    #[test]
+    fn feed_defaults_to_unread_items() {
+        let pocket = PocketMock {
+            get_mock: |opts: &GetOpts| {
+                assert!(matches!(opts.state, Some(PocketGetState::Unread)));
+                Ok(vec![])
+            },
+            send_mock: unused_send,
+            add_mock: unused_add,
+        };
+        let opts = Export::from_iter(&["export", "feed"]);
+
+        export(&pocket, &opts).unwrap();
+    }
+
+    #[test]
+    fn opml_defaults_to_every_item() {
+        let pocket = PocketMock {
+            get_mock: |opts: &GetOpts| {
+                assert!(matches!(opts.state, Some(PocketGetState::All)));
+                Ok(vec![])
+            },
+            send_mock: unused_send,
+            add_mock: unused_add,
+        };
+        let opts = Export::from_iter(&["export", "opml"]);
+
+        export(&pocket, &opts).unwrap();
+    }
+}
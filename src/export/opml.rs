@@ -0,0 +1,88 @@
+use crate::models::Item;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+#[derive(Debug)]
+struct Site<'a> {
+    name: &'a str,
+    url: String,
+    count: usize,
+}
+
+/// Render the sites items were saved from as OPML outlines, most saved first.
+pub fn render(items: &[Item]) -> String {
+    let mut opml = String::new();
+
+    opml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    opml.push_str("<opml version=\"2.0\">\n");
+    opml.push_str("  <head>\n    <title>Pocket sites</title>\n  </head>\n");
+    opml.push_str("  <body>\n");
+    for site in sites(items) {
+        writeln!(
+            opml,
+            "    <outline text=\"{name}\" title=\"{name}\" htmlUrl=\"{url}\" count=\"{count}\"/>",
+            name = escape(site.name),
+            url = escape(&site.url),
+            count = site.count
+        )
+        .unwrap();
+    }
+    opml.push_str("  </body>\n</opml>\n");
+    opml
+}
+
+fn sites(items: &[Item]) -> Vec<Site> {
+    let mut sites: HashMap<&str, Site> = HashMap::new();
+
+    for item in items {
+        let (domain, url) = match (item.domain(), item.url()) {
+            (Some(domain), Some(url)) => (domain, url),
+            _ => continue,
+        };
+        let name = item
+            .domain_metadata
+            .as_ref()
+            .and_then(|d| d.name.as_deref())
+            .unwrap_or(domain);
+        sites
+            .entry(domain)
+            .or_insert_with(|| Site {
+                name,
+                url: format!("{}://{}/", url.scheme(), url.host_str().unwrap_or(domain)),
+                count: 0,
+            })
+            .count += 1;
+    }
+
+    let mut sites = sites.into_iter().map(|(_, s)| s).collect::<Vec<_>>();
+    sites.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.url.cmp(&b.url)));
+    sites
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sites_are_counted_by_domain() {
+        let items = [
+            Item::fixture(1, "https://www.example.com/a"),
+            Item::fixture(2, "https://example.com/b"),
+            Item::fixture(3, "http://other.org/c"),
+        ];
+
+        let opml = render(&items);
+
+        assert!(opml.contains(
+            "<outline text=\"example.com\" title=\"example.com\" htmlUrl=\"https://www.example.com/\" count=\"2\"/>"
+        ));
+        assert!(opml.contains("htmlUrl=\"http://other.org/\" count=\"1\""));
+    }
+}
@@ -0,0 +1,178 @@
+use crate::models::{Item, ItemStatus};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Write;
+use std::str::FromStr;
+
+const UNTAGGED: &str = "untagged";
+const UNKNOWN_DOMAIN: &str = "unknown";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GroupBy {
+    None,
+    Tag,
+    Domain,
+}
+
+impl FromStr for GroupBy {
+    type Err = ParseGroupByError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(GroupBy::None),
+            "tag" => Ok(GroupBy::Tag),
+            "domain" => Ok(GroupBy::Domain),
+            s => Err(ParseGroupByError(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseGroupByError(String);
+
+impl fmt::Display for ParseGroupByError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "provided invalid group: {}", self.0)
+    }
+}
+
+pub fn render(items: &[Item], group_by: GroupBy) -> String {
+    let mut org = String::from("#+TITLE: Pocket\n");
+
+    match group_by {
+        GroupBy::None => items.iter().for_each(|item| heading(&mut org, 1, item)),
+        GroupBy::Tag => {
+            let mut groups: BTreeMap<&str, Vec<&Item>> = BTreeMap::new();
+            for item in items {
+                let tags = item.tag_names();
+                if tags.is_empty() {
+                    groups.entry(UNTAGGED).or_default().push(item);
+                }
+                for tag in tags {
+                    groups.entry(tag).or_default().push(item);
+                }
+            }
+            groups_to_org(&mut org, groups);
+        }
+        GroupBy::Domain => {
+            let mut groups: BTreeMap<&str, Vec<&Item>> = BTreeMap::new();
+            for item in items {
+                let domain = item.domain().unwrap_or(UNKNOWN_DOMAIN);
+                groups.entry(domain).or_default().push(item);
+            }
+            groups_to_org(&mut org, groups);
+        }
+    }
+
+    org
+}
+
+fn groups_to_org(org: &mut String, groups: BTreeMap<&str, Vec<&Item>>) {
+    for (group, items) in groups {
+        writeln!(org, "* {}", group).unwrap();
+        items.into_iter().for_each(|item| heading(org, 2, item));
+    }
+}
+
+fn heading(org: &mut String, level: usize, item: &Item) {
+    let keyword = match item.status {
+        Some(ItemStatus::Archived) => "DONE",
+        _ => "TODO",
+    };
+    let title = item.title().unwrap_or_default();
+    let link = match item.url() {
+        Some(url) => format!("[[{}][{}]]", url, escape_link(title)),
+        None => title.to_string(),
+    };
+    let tags = item
+        .tag_names()
+        .into_iter()
+        .map(org_tag)
+        .collect::<Vec<_>>();
+
+    write!(org, "{} {} {}", "*".repeat(level), keyword, link).unwrap();
+    if !tags.is_empty() {
+        write!(org, " :{}:", tags.join(":")).unwrap();
+    }
+    org.push('\n');
+
+    let indent = " ".repeat(level + 1);
+    writeln!(org, "{}:PROPERTIES:", indent).unwrap();
+    let mut property = |name: &str, value: Option<String>| {
+        if let Some(value) = value {
+            writeln!(org, "{}:{}: {}", indent, name, value).unwrap();
+        }
+    };
+    property("ITEM_ID", Some(item.item_id.to_string()));
+    property("RESOLVED_ID", Some(item.resolved_id.to_string()));
+    property("URL", item.resolved_url.as_ref().map(|u| u.to_string()));
+    property("GIVEN_URL", item.given_url.as_ref().map(|u| u.to_string()));
+    property("AMP_URL", item.amp_url.as_ref().map(|u| u.to_string()));
+    property("ADDED", item.time_added.map(timestamp));
+    property("UPDATED", item.time_updated.map(timestamp));
+    property("READ", item.time_read.map(timestamp));
+    property("FAVORITED", item.time_favorited.map(timestamp));
+    property("FAVORITE", item.favorite.map(|f| f.to_string()));
+    property("WORD_COUNT", Some(item.word_count.to_string()));
+    writeln!(org, "{}:END:", indent).unwrap();
+
+    let excerpt = item.excerpt.trim();
+    if !excerpt.is_empty() {
+        writeln!(
+            org,
+            "{}{}",
+            indent,
+            excerpt.replace('\n', &format!("\n{}", indent))
+        )
+        .unwrap();
+    }
+}
+
+fn timestamp(t: DateTime<Utc>) -> String {
+    t.format("[%Y-%m-%d %a %H:%M]").to_string()
+}
+
+/// Org tags only allow alphanumerics, `_`, `@`, `#` and `%`.
+fn org_tag(tag: &str) -> String {
+    tag.chars()
+        .map(|c| match c {
+            c if c.is_alphanumeric() => c,
+            '@' | '#' | '%' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+fn escape_link(title: &str) -> String {
+    title.replace('[', "{").replace(']', "}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Tag;
+
+    #[test]
+    fn render_groups_by_tag() {
+        let mut archived = Item::fixture(1, "https://example.com/a");
+        archived.status = Some(ItemStatus::Archived);
+        archived.tags = Some(vec![Tag("rust-lang".to_string())]);
+        let unread = Item::fixture(2, "https://example.com/b");
+
+        let org = render(&[archived, unread], GroupBy::Tag);
+
+        assert!(org.contains("* rust-lang\n** DONE [[https://example.com/a][]] :rust_lang:\n"));
+        assert!(org.contains("* untagged\n** TODO [[https://example.com/b][]]\n"));
+        assert!(org.contains("   :ITEM_ID: 2\n"));
+    }
+
+    #[test]
+    fn render_groups_by_domain() {
+        let items = [Item::fixture(1, "https://www.example.com/a")];
+
+        let org = render(&items, GroupBy::Domain);
+
+        assert!(org.contains("* example.com\n** TODO"));
+    }
+}
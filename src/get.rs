@@ -4,103 +4,73 @@ use std::io;
 use std::io::{ErrorKind, Write};
 use structopt::StructOpt;
 
+use crate::backend::{BackendResult, PocketGet};
 use crate::models::{Image, Item};
 use crate::output::Output;
+use crate::tag;
 
-#[derive(Debug, StructOpt)]
+#[derive(Clone, Debug, Default, StructOpt)]
 pub struct GetOpts {
     #[structopt(long)]
-    search: Option<String>,
+    pub search: Option<String>,
     #[structopt(long)]
-    domain: Option<String>,
+    pub domain: Option<String>,
+    /// Tag of the items; ending in `/`, such as `work/`, also matches the tags below it
+    #[structopt(long, conflicts_with = "untagged")]
+    pub tag: Option<String>,
     #[structopt(long)]
-    tag: Option<String>,
-    #[structopt(long)]
-    untagged: bool,
+    pub untagged: bool,
     #[structopt(long, parse(try_from_str = parse_get_state))]
-    state: Option<PocketGetState>,
+    pub state: Option<PocketGetState>,
     #[structopt(long, parse(try_from_str = parse_get_content_type))]
-    content_type: Option<PocketGetType>,
+    pub content_type: Option<PocketGetType>,
     #[structopt(long, parse(try_from_str = parse_get_detail_type))]
-    detail_type: Option<PocketGetDetail>,
+    pub detail_type: Option<PocketGetDetail>,
     #[structopt(long)]
-    favorite: Option<bool>,
+    pub favorite: Option<bool>,
     #[structopt(long)]
-    since: Option<DateTime<Utc>>,
+    pub since: Option<DateTime<Utc>>,
     #[structopt(long, parse(try_from_str = parse_get_sort))]
-    sort: Option<PocketGetSort>,
+    pub sort: Option<PocketGetSort>,
     #[structopt(long)]
-    count: Option<usize>,
+    pub count: Option<usize>,
     #[structopt(long)]
-    offset: Option<usize>,
+    pub offset: Option<usize>,
 }
 
 pub fn handle<W: Write>(pocket: &impl PocketGet, opts: &GetOpts, output: &mut Output<W>) {
-    let items: Vec<Item> = {
-        let mut f = pocket.filter();
-
-        if let Some(search) = &opts.search {
-            f.search(search);
-        }
-
-        // domain
-        if let Some(domain) = &opts.domain {
-            f.domain(domain);
-        }
-
-        // tag match
-        match (&opts.tag, opts.untagged) {
-            (Some(_), true) => panic!("Cannot set tag and untagged"),
-            (Some(tag), false) => {
-                f.tag(PocketGetTag::Tagged(tag));
-            }
-            (None, true) => {
-                f.tag(PocketGetTag::Untagged);
-            }
-            (None, false) => {}
-        }
-
-        // state
-        if let Some(state) = opts.state {
-            f.state(state);
-        }
-
-        // content_type
-        if let Some(content_type) = opts.content_type {
-            f.content_type(content_type);
-        }
-
-        // detail_type
-        if let Some(detail_type) = opts.detail_type {
-            f.detail_type(detail_type);
-        }
-
-        // favorite
-        if let Some(favorite) = opts.favorite {
-            f.favorite(favorite);
-        }
-
-        // sort
-        if let Some(sort) = opts.sort {
-            f.sort(sort);
-        }
-
-        // offset
-        if let Some(offset) = opts.offset {
-            f.offset(offset);
-        }
+    let items = get(pocket, opts).unwrap();
+    output.write(&items).unwrap();
+}
 
-        // count
-        if let Some(count) = opts.count {
-            f.count(count);
-        }
+/// Items matching `opts`. Commands taking a user's `GetOpts` fetch through this rather
+/// than the backend, so that `--tag work/` matches the tags below `work`.
+pub fn get(pocket: &impl PocketGet, opts: &GetOpts) -> BackendResult<Vec<Item>> {
+    match opts.tag.as_deref().and_then(|t| t.strip_suffix('/')) {
+        Some(parent) => get_below(pocket, opts, parent),
+        None => pocket.get(opts),
+    }
+}
 
-        pocket
-            .get(&f)
-            .map(|v| v.into_iter().map(|i| i.into()).collect())
+/// Items tagged `parent` or a tag below it. Backends only match whole tags, so tags
+/// are matched here, and the page is taken from the items that match.
+fn get_below(pocket: &impl PocketGet, opts: &GetOpts, parent: &str) -> BackendResult<Vec<Item>> {
+    if opts.untagged {
+        panic!("Cannot set tag and untagged");
     }
-    .unwrap();
-    output.write(&items).unwrap();
+    let items = pocket.get(&GetOpts {
+        tag: None,
+        detail_type: Some(PocketGetDetail::Complete),
+        count: None,
+        offset: None,
+        ..opts.clone()
+    })?;
+    Ok(items
+        .into_iter()
+        .filter(|i| i.tag_names().iter().any(|t| tag::within(t, parent)))
+        .skip(opts.offset.unwrap_or(0))
+        .take(opts.count.unwrap_or(usize::MAX))
+        .collect())
 }
 
 impl From<PocketItem> for Item {
@@ -154,51 +124,14 @@ impl From<PocketImage> for Image {
     }
 }
 
-pub trait PocketGet {
-    fn filter(&self) -> PocketGetRequest;
-    fn get(&self, request: &PocketGetRequest) -> PocketResult<Vec<PocketItem>>;
-}
-
-impl PocketGet for Pocket {
-    fn filter(&self) -> PocketGetRequest {
-        self.filter()
-    }
-
-    fn get(&self, request: &PocketGetRequest) -> PocketResult<Vec<PocketItem>> {
-        self.get(request)
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::backend::mock::{unused_add, unused_send, PocketMock};
     use crate::output::OutputFormat;
     use std::io;
     use std::io::stdout;
 
-    struct PocketGetMock<'a, F, G>
-    where
-        F: Fn() -> PocketGetRequest<'a>,
-        G: Fn(&PocketGetRequest) -> PocketResult<Vec<PocketItem>>,
-    {
-        filter_mock: F,
-        get_mock: G,
-    }
-
-    impl<'a, F, G> PocketGet for PocketGetMock<'a, F, G>
-    where
-        F: Fn() -> PocketGetRequest<'a>,
-        G: Fn(&PocketGetRequest) -> PocketResult<Vec<PocketItem>>,
-    {
-        fn filter(&self) -> PocketGetRequest {
-            (self.filter_mock)()
-        }
-
-        fn get(&self, request: &PocketGetRequest) -> PocketResult<Vec<PocketItem>> {
-            (self.get_mock)(request)
-        }
-    }
-
     struct WriteMock<W, F>
     where
         W: Fn(&[u8]) -> io::Result<usize>,
@@ -224,9 +157,10 @@ mod tests {
 
     #[test]
     fn get_writes_items() {
-        let pocket = PocketGetMock {
-            filter_mock: || PocketGetRequest::new(),
+        let pocket = PocketMock {
             get_mock: |_| Ok(vec![]),
+            send_mock: unused_send,
+            add_mock: unused_add,
         };
         let opts = GetOpts {
             search: None,
@@ -253,9 +187,10 @@ mod tests {
     #[test]
     #[should_panic]
     fn get_panics_when_pocket_error() {
-        let pocket = PocketGetMock {
-            filter_mock: || PocketGetRequest::new(),
-            get_mock: |_| Err(PocketError::Proto(1, "".to_string())),
+        let pocket = PocketMock {
+            get_mock: |_| Err(PocketError::Proto(1, "".to_string()).into()),
+            send_mock: unused_send,
+            add_mock: unused_add,
         };
         let opts = GetOpts {
             search: None,
@@ -279,9 +214,10 @@ mod tests {
     #[test]
     #[should_panic]
     fn get_panics_when_write_error() {
-        let pocket = PocketGetMock {
-            filter_mock: || PocketGetRequest::new(),
+        let pocket = PocketMock {
             get_mock: |_| Ok(vec![]),
+            send_mock: unused_send,
+            add_mock: unused_add,
         };
         let opts = GetOpts {
             search: None,
@@ -305,9 +241,57 @@ mod tests {
 
         handle(&pocket, &opts, &mut output);
     }
+
+    #[test]
+    fn get_matches_tags_below_parent() {
+        let pocket = PocketMock {
+            get_mock: |opts| {
+                assert_eq!(None, opts.tag);
+                assert_eq!(None, opts.count);
+                let tagged =
+                    |item_id, tag| Item::fixture(item_id, "https://example.com/").with_tags(&[tag]);
+                Ok(vec![
+                    tagged(1, "work"),
+                    tagged(2, "workshop"),
+                    tagged(3, "work/reports"),
+                    tagged(4, "work/reports/q1"),
+                ])
+            },
+            send_mock: unused_send,
+            add_mock: unused_add,
+        };
+        let opts = GetOpts {
+            tag: Some("work/".to_string()),
+            count: Some(2),
+            ..Default::default()
+        };
+
+        let items = get(&pocket, &opts).unwrap();
+
+        assert_eq!(
+            vec![1, 3],
+            items.iter().map(|i| i.item_id).collect::<Vec<_>>()
+        );
+    }
+    #[test]
+    #[should_panic(expected = "Cannot set tag and untagged")]
+    fn get_rejects_parent_tag_with_untagged() {
+        let pocket = PocketMock {
+            get_mock: |_| Ok(vec![]),
+            send_mock: unused_send,
+            add_mock: unused_add,
+        };
+        let opts = GetOpts {
+            tag: Some("work/".to_string()),
+            untagged: true,
+            ..Default::default()
+        };
+
+        let _ = get(&pocket, &opts);
+    }
 }
 
-fn parse_get_state(s: &str) -> Result<PocketGetState, io::Error> {
+pub fn parse_get_state(s: &str) -> Result<PocketGetState, io::Error> {
     match s {
         "unread" => Ok(PocketGetState::Unread),
         "archive" => Ok(PocketGetState::Archive),
@@ -319,7 +303,7 @@ fn parse_get_state(s: &str) -> Result<PocketGetState, io::Error> {
     }
 }
 
-fn parse_get_content_type(s: &str) -> Result<PocketGetType, io::Error> {
+pub fn parse_get_content_type(s: &str) -> Result<PocketGetType, io::Error> {
     match s {
         "article" => Ok(PocketGetType::Article),
         "video" => Ok(PocketGetType::Video),
@@ -331,7 +315,7 @@ fn parse_get_content_type(s: &str) -> Result<PocketGetType, io::Error> {
     }
 }
 
-fn parse_get_detail_type(s: &str) -> Result<PocketGetDetail, io::Error> {
+pub fn parse_get_detail_type(s: &str) -> Result<PocketGetDetail, io::Error> {
     match s {
         "simple" => Ok(PocketGetDetail::Simple),
         "complete" => Ok(PocketGetDetail::Complete),
@@ -342,7 +326,7 @@ fn parse_get_detail_type(s: &str) -> Result<PocketGetDetail, io::Error> {
     }
 }
 
-fn parse_get_sort(s: &str) -> Result<PocketGetSort, io::Error> {
+pub fn parse_get_sort(s: &str) -> Result<PocketGetSort, io::Error> {
     match s {
         "newest" => Ok(PocketGetSort::Newest),
         "oldest" => Ok(PocketGetSort::Oldest),
@@ -0,0 +1,132 @@
+use crate::backend::{BackendResult, PocketAdd, PocketGet};
+use crate::canonical::{self, CanonicalPolicy};
+use crate::get::GetOpts;
+use crate::models::Item;
+use crate::output::Output;
+use pocket::{PocketAddRequest, PocketGetState};
+use serde::Serialize;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use structopt::StructOpt;
+use url::Url;
+
+pub mod opml;
+
+#[derive(Debug, StructOpt)]
+pub enum Import {
+    /// OPML outlines with an `htmlUrl`, tagged with their category
+    Opml {
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        /// Tag added to every imported item
+        #[structopt(long = "tag")]
+        tags: Option<Vec<String>>,
+        /// Show what would be added without adding it
+        #[structopt(long)]
+        dry_run: bool,
+        /// Add URLs as they are, without canonicalizing them
+        #[structopt(long)]
+        raw: bool,
+    },
+}
+
+/// An item read from an import source, not yet added.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Entry {
+    #[serde(with = "url_serde")]
+    pub url: Url,
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// Import the entries, warning on stderr about those already saved unless they are
+/// imported raw.
+pub fn handle<W: Write>(
+    pocket: &(impl PocketAdd + PocketGet),
+    opts: &Import,
+    policy: &CanonicalPolicy,
+    output: &mut Output<W>,
+) {
+    let entries = entries(opts, policy).unwrap();
+    let Import::Opml { raw, .. } = opts;
+    if !raw {
+        let saved = pocket
+            .get(&GetOpts {
+                state: Some(PocketGetState::All),
+                ..Default::default()
+            })
+            .unwrap();
+        for entry in &entries {
+            canonical::warn_saved(&entry.url, &canonical::saved_as(&saved, policy, &entry.url));
+        }
+    }
+    match imported(pocket, opts, entries).unwrap() {
+        Imported::Planned(entries) => output.write(entries).unwrap(),
+        Imported::Added(items) => output.write(items).unwrap(),
+    }
+}
+
+/// What an import produced: the entries a dry run would add, or the items added.
+#[derive(Debug)]
+pub enum Imported {
+    Planned(Vec<Entry>),
+    Added(Vec<Item>),
+}
+
+pub fn import(
+    pocket: &impl PocketAdd,
+    opts: &Import,
+    policy: &CanonicalPolicy,
+) -> BackendResult<Imported> {
+    imported(pocket, opts, entries(opts, policy)?)
+}
+
+fn imported(
+    pocket: &impl PocketAdd,
+    opts: &Import,
+    entries: Vec<Entry>,
+) -> BackendResult<Imported> {
+    match opts {
+        Import::Opml { dry_run: true, .. } => Ok(Imported::Planned(entries)),
+        Import::Opml { .. } => Ok(Imported::Added(add(pocket, &entries)?)),
+    }
+}
+
+/// The entries of the import source, with their URLs canonicalized unless raw.
+fn entries(opts: &Import, policy: &CanonicalPolicy) -> BackendResult<Vec<Entry>> {
+    match opts {
+        Import::Opml {
+            file, tags, raw, ..
+        } => {
+            let mut entries = opml::parse(&fs::read_to_string(file)?)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            for entry in entries.iter_mut() {
+                entry.tags.extend(tags.iter().flatten().cloned());
+                if !raw {
+                    entry.url = policy.apply(&entry.url);
+                }
+            }
+            Ok(entries)
+        }
+    }
+}
+
+fn add(pocket: &impl PocketAdd, entries: &[Entry]) -> BackendResult<Vec<Item>> {
+    entries
+        .iter()
+        .map(|entry| {
+            let tags = entry.tags.iter().map(|t| t.as_str()).collect::<Vec<_>>();
+            pocket.add(&PocketAddRequest {
+                url: &entry.url,
+                title: entry.title.as_deref(),
+                tags: if tags.is_empty() {
+                    None
+                } else {
+                    Some(tags.as_slice())
+                },
+                tweet_id: None,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,256 @@
+use super::Entry;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use url::Url;
+
+#[derive(Debug)]
+pub struct ParseOpmlError(String);
+
+impl Error for ParseOpmlError {}
+
+impl fmt::Display for ParseOpmlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid OPML: {}", self.0)
+    }
+}
+
+/// Read every outline with an `htmlUrl`. Its tags come from its `category` attribute,
+/// or, without one, from the text of the outline it is nested in.
+pub fn parse(opml: &str) -> Result<Vec<Entry>, ParseOpmlError> {
+    let mut entries = Vec::new();
+    // Text of each open, non self-closing outline
+    let mut parents: Vec<Option<String>> = Vec::new();
+    let mut rest = opml;
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        // Comments and CDATA may hold markup that isn't part of the document
+        if let Some((open, close)) = [("<!--", "-->"), ("<![CDATA[", "]]>")]
+            .iter()
+            .find(|(open, _)| rest.starts_with(open))
+        {
+            let end = rest[open.len()..]
+                .find(close)
+                .ok_or_else(|| ParseOpmlError(format!("unterminated {}", open)))?;
+            rest = &rest[open.len() + end + close.len()..];
+            continue;
+        }
+        let end = tag_end(rest).ok_or_else(|| ParseOpmlError("unterminated tag".to_string()))?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        if named(tag, "/outline").is_some() {
+            parents.pop();
+            continue;
+        }
+        let tag = match named(tag, "outline") {
+            Some(tag) => tag,
+            None => continue,
+        };
+
+        let self_closing = tag.ends_with('/');
+        let attributes = attributes(tag.trim_end_matches('/'))?;
+
+        if let Some(html_url) = attributes.get("htmlUrl") {
+            let url =
+                Url::parse(html_url).map_err(|e| ParseOpmlError(format!("{}: {}", html_url, e)))?;
+            let tags = match attributes.get("category") {
+                Some(category) => category
+                    .split(',')
+                    .map(|c| c.trim().trim_matches('/').to_string())
+                    .filter(|c| !c.is_empty())
+                    .collect(),
+                None => parents.iter().rev().flatten().take(1).cloned().collect(),
+            };
+            entries.push(Entry {
+                url,
+                title: attributes
+                    .get("title")
+                    .or_else(|| attributes.get("text"))
+                    .cloned(),
+                tags,
+            });
+        }
+
+        if !self_closing {
+            parents.push(
+                attributes
+                    .get("text")
+                    .or_else(|| attributes.get("title"))
+                    .cloned(),
+            );
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Index of the `>` closing the tag `s` starts with, skipping those in quoted values.
+fn tag_end(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (None, '>') => return Some(i),
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// What follows the name of a `name` tag, such as its attributes, or `None` for
+/// other tags, including those whose name only starts with `name`.
+fn named<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let rest = tag.strip_prefix(name)?;
+    match rest.chars().next() {
+        None | Some('/') => Some(rest),
+        Some(c) if c.is_whitespace() => Some(rest),
+        Some(_) => None,
+    }
+}
+
+fn attributes(s: &str) -> Result<HashMap<String, String>, ParseOpmlError> {
+    let mut attributes = HashMap::new();
+    let mut rest = s.trim_start();
+
+    while !rest.is_empty() {
+        let eq = rest
+            .find('=')
+            .ok_or_else(|| ParseOpmlError(format!("invalid attribute: {}", rest)))?;
+        let name = rest[..eq].trim();
+        let value = rest[eq + 1..].trim_start();
+        let quote = value
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')
+            .ok_or_else(|| ParseOpmlError(format!("unquoted attribute: {}", name)))?;
+        let close = value[1..]
+            .find(quote)
+            .ok_or_else(|| ParseOpmlError(format!("unterminated attribute: {}", name)))?;
+        attributes.insert(name.to_string(), unescape(&value[1..close + 1]));
+        rest = value[close + 2..].trim_start();
+    }
+
+    Ok(attributes)
+}
+
+fn unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(amp) = rest.find('&') {
+        unescaped.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let entity = rest.find(';').map(|semi| &rest[1..semi]);
+        let c = entity.and_then(|e| match e {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            e => match (e.strip_prefix("#x"), e.strip_prefix('#')) {
+                (Some(hex), _) => u32::from_str_radix(hex, 16).ok(),
+                (None, Some(dec)) => dec.parse().ok(),
+                _ => None,
+            }
+            .and_then(std::char::from_u32),
+        });
+        match (c, entity) {
+            (Some(c), Some(entity)) => {
+                unescaped.push(c);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    unescaped.push_str(rest);
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPML: &str = r#"<?xml version="1.0"?>
+<opml version="2.0">
+  <body>
+    <outline text="Tech">
+      <outline text="Rust &amp; Co" htmlUrl="https://blog.rust-lang.org/" xmlUrl="x"/>
+      <outline text="LWN" htmlUrl='https://lwn.net/' category="/news,linux"/>
+    </outline>
+    <outline text="No site" xmlUrl="https://example.com/feed"/>
+    <outline text="Loose" htmlUrl="https://example.com/"/>
+  </body>
+</opml>"#;
+
+    #[test]
+    fn parse_reads_outlines_with_html_url() {
+        let entries = parse(OPML).unwrap();
+
+        assert_eq!(
+            vec![
+                Entry {
+                    url: Url::parse("https://blog.rust-lang.org/").unwrap(),
+                    title: Some("Rust & Co".to_string()),
+                    tags: vec!["Tech".to_string()],
+                },
+                Entry {
+                    url: Url::parse("https://lwn.net/").unwrap(),
+                    title: Some("LWN".to_string()),
+                    tags: vec!["news".to_string(), "linux".to_string()],
+                },
+                Entry {
+                    url: Url::parse("https://example.com/").unwrap(),
+                    title: Some("Loose".to_string()),
+                    tags: vec![],
+                },
+            ],
+            entries
+        );
+    }
+
+    #[test]
+    fn parse_fails_on_unterminated_attribute() {
+        assert!(parse(r#"<outline text="oops/>"#).is_err());
+    }
+
+    #[test]
+    fn parse_reads_gt_in_attribute_values() {
+        let entries = parse(r#"<outline title="a > b" htmlUrl="https://a.com/"/>"#).unwrap();
+
+        assert_eq!(Some("a > b".to_string()), entries[0].title);
+    }
+
+    #[test]
+    fn parse_skips_comments_and_cdata() {
+        let entries = parse(
+            r#"<!-- <outline htmlUrl="https://a.com/"/> -->
+            <![CDATA[<outline htmlUrl="https://b.com/"/>]]>
+            <outline htmlUrl="https://c.com/"/>"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec![Url::parse("https://c.com/").unwrap()],
+            entries.into_iter().map(|e| e.url).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn parse_skips_tags_only_starting_with_outline() {
+        let entries =
+            parse(r#"<outlines htmlUrl="https://a.com/"/><outline htmlUrl="https://b.com/"/>"#)
+                .unwrap();
+
+        assert_eq!(
+            vec![Url::parse("https://b.com/").unwrap()],
+            entries.into_iter().map(|e| e.url).collect::<Vec<_>>()
+        );
+    }
+}
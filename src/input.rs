@@ -0,0 +1,111 @@
+use crate::models::Item;
+use crate::output::OutputFormat;
+use serde::Deserialize;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read};
+
+/// Output of a command read back in, as a list or a single item.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Items {
+    List(Vec<Item>),
+    Item(Box<Item>),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TomlItems {
+    /// A list, written as the `items` array of a table
+    Table {
+        items: Vec<Item>,
+    },
+    Item(Box<Item>),
+}
+
+/// Read items written by a command such as `pocket get` in `format`.
+pub fn read_items(
+    format: OutputFormat,
+    mut reader: impl Read,
+) -> Result<Vec<Item>, ParseInputError> {
+    let mut input = String::new();
+    reader.read_to_string(&mut input)?;
+
+    let items = match format {
+        OutputFormat::Json => serde_json::from_str(&input).map_err(ParseInputError::new)?,
+        OutputFormat::Yaml => serde_yaml::from_str(&input).map_err(ParseInputError::new)?,
+        OutputFormat::Toml => match toml::from_str(&input).map_err(ParseInputError::new)? {
+            TomlItems::Table { items } => Items::List(items),
+            TomlItems::Item(item) => Items::Item(item),
+        },
+    };
+
+    Ok(match items {
+        Items::List(items) => items,
+        Items::Item(item) => vec![*item],
+    })
+}
+
+#[derive(Debug)]
+pub struct ParseInputError(String);
+
+impl ParseInputError {
+    fn new(e: impl fmt::Display) -> ParseInputError {
+        ParseInputError(e.to_string())
+    }
+}
+
+impl Error for ParseInputError {}
+
+impl fmt::Display for ParseInputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid input: {}", self.0)
+    }
+}
+
+impl From<io::Error> for ParseInputError {
+    fn from(e: io::Error) -> Self {
+        ParseInputError::new(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Tag;
+    use crate::output::Output;
+
+    fn round_trip(format: OutputFormat, items: &[Item]) -> Vec<Item> {
+        let mut output = Output::new(format, Vec::new());
+        output.write(items).unwrap();
+        read_items(format, output.into_vec().as_slice()).unwrap()
+    }
+
+    #[test]
+    fn read_items_reads_get_output_in_every_format() {
+        let mut item = Item::fixture(1, "https://example.com/a");
+        item.tags = Some(vec![Tag("rust".to_string())]);
+        let items = [item, Item::fixture(2, "https://example.com/b")];
+
+        for format in &[OutputFormat::Json, OutputFormat::Yaml, OutputFormat::Toml] {
+            let read = round_trip(*format, &items);
+
+            assert_eq!(
+                vec![1, 2],
+                read.iter().map(|i| i.item_id).collect::<Vec<_>>()
+            );
+            assert_eq!(vec!["rust"], read[0].tag_names());
+            assert_eq!(items[1].given_url, read[1].given_url);
+        }
+    }
+
+    #[test]
+    fn read_items_reads_single_item() {
+        let input = serde_json::to_string(&Item::fixture(7, "https://example.com/")).unwrap();
+
+        let items = read_items(OutputFormat::Json, input.as_bytes()).unwrap();
+
+        assert_eq!(1, items.len());
+        assert_eq!(7, items[0].item_id);
+    }
+}
@@ -0,0 +1,263 @@
+use crate::backend::{BackendError, PocketGet};
+use crate::get::GetOpts;
+use crate::input::{self, ParseInputError};
+use crate::models::Item;
+use crate::output::OutputFormat;
+use crate::picker;
+use pocket::{PocketGetDetail, PocketGetSort, PocketGetState};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use url::Url;
+
+/// An item named on the command line, by Pocket's id or by the URL it was saved with.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ItemRef {
+    Id(u64),
+    Url(Url),
+}
+
+impl FromStr for ItemRef {
+    type Err = ParseItemRefError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(item_id) = s.parse() {
+            return Ok(ItemRef::Id(item_id));
+        }
+        Url::parse(s)
+            .map(ItemRef::Url)
+            .map_err(|_| ParseItemRefError(s.to_string()))
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseItemRefError(String);
+
+impl fmt::Display for ParseItemRefError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "provided invalid item id or URL: {}", self.0)
+    }
+}
+
+#[derive(Debug)]
+pub enum ResolveError {
+    Backend(BackendError),
+    Input(ParseInputError),
+    Picker(crossterm::ErrorKind),
+    NotFound(Url),
+    Ambiguous(Url, Vec<u64>),
+    NoItem,
+}
+
+impl Error for ResolveError {}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolveError::Backend(e) => fmt::Display::fmt(e, f),
+            ResolveError::Input(e) => fmt::Display::fmt(e, f),
+            ResolveError::Picker(e) => write!(f, "picker failed: {}", e),
+            ResolveError::NotFound(url) => write!(f, "no item matches {}", url),
+            ResolveError::Ambiguous(url, item_ids) => write!(
+                f,
+                "{} items match {}: {}; pass an item id or --first",
+                item_ids.len(),
+                url,
+                item_ids
+                    .iter()
+                    .map(|id| id.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            ResolveError::NoItem => write!(
+                f,
+                "no item given and stdin is not a terminal to pick one in; pass an item or --stdin"
+            ),
+        }
+    }
+}
+
+impl From<BackendError> for ResolveError {
+    fn from(e: BackendError) -> Self {
+        ResolveError::Backend(e)
+    }
+}
+
+impl From<ParseInputError> for ResolveError {
+    fn from(e: ParseInputError) -> Self {
+        ResolveError::Input(e)
+    }
+}
+
+/// The ids of the item named on the command line, of every item read from stdin when
+/// `stdin` is set, or else of the items picked interactively.
+pub fn item_ids(
+    pocket: &impl PocketGet,
+    item: Option<&ItemRef>,
+    first: bool,
+    stdin: bool,
+    format: OutputFormat,
+) -> Result<Vec<u64>, ResolveError> {
+    match item {
+        Some(item) => Ok(vec![resolve(pocket, item, first)?]),
+        None if stdin => Ok(input::read_items(format, std::io::stdin().lock())?
+            .iter()
+            .map(|i| i.item_id)
+            .collect()),
+        None if picker::is_interactive() => pick(pocket),
+        None => Err(ResolveError::NoItem),
+    }
+}
+
+/// Let the user pick from every item, newest first, with tags to match against.
+fn pick(pocket: &impl PocketGet) -> Result<Vec<u64>, ResolveError> {
+    let items = pocket.get(&GetOpts {
+        state: Some(PocketGetState::All),
+        detail_type: Some(PocketGetDetail::Complete),
+        sort: Some(PocketGetSort::Newest),
+        ..Default::default()
+    })?;
+    picker::pick(&items).map_err(ResolveError::Picker)
+}
+
+/// Find the id of `item`. A URL is looked up with a search and matched against the
+/// given and resolved URL of each result, newest first when `first` is set.
+pub fn resolve(pocket: &impl PocketGet, item: &ItemRef, first: bool) -> Result<u64, ResolveError> {
+    let url = match item {
+        ItemRef::Id(item_id) => return Ok(*item_id),
+        ItemRef::Url(url) => url,
+    };
+
+    let key = normalize(url);
+    let items = pocket.get(&GetOpts {
+        search: Some(search_term(url)),
+        state: Some(PocketGetState::All),
+        sort: Some(PocketGetSort::Newest),
+        ..Default::default()
+    })?;
+    let item_ids = items
+        .iter()
+        .filter(|i| has_url(i, &key))
+        .map(|i| i.item_id)
+        .collect::<Vec<_>>();
+
+    match item_ids.as_slice() {
+        [] => Err(ResolveError::NotFound(url.clone())),
+        [item_id] => Ok(*item_id),
+        [item_id, ..] if first => Ok(*item_id),
+        _ => Err(ResolveError::Ambiguous(url.clone(), item_ids.clone())),
+    }
+}
+
+/// A URL without what commonly differs between saves of the same page: the scheme,
+/// a `www.` prefix, letter case of the host, a trailing slash and the fragment.
+pub fn normalize(url: &Url) -> String {
+    let mut key = host(url);
+    if let Some(port) = url.port() {
+        key.push_str(&format!(":{}", port));
+    }
+    key.push_str(url.path().trim_end_matches('/'));
+    if let Some(query) = url.query() {
+        key.push('?');
+        key.push_str(query);
+    }
+    key
+}
+
+fn host(url: &Url) -> String {
+    let host = url.host_str().unwrap_or_default().to_lowercase();
+    host.trim_start_matches("www.").to_string()
+}
+
+/// Searches match URLs by substring, so leave out the parts `normalize` ignores.
+pub fn search_term(url: &Url) -> String {
+    format!("{}{}", host(url), url.path().trim_end_matches('/'))
+}
+
+fn has_url(item: &Item, key: &str) -> bool {
+    item.given_url
+        .iter()
+        .chain(item.resolved_url.iter())
+        .any(|url| normalize(url) == key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::{unused_add, unused_send, PocketMock};
+
+    fn url_ref(url: &str) -> ItemRef {
+        ItemRef::Url(Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn item_ref_parses_ids_and_urls() {
+        assert_eq!(ItemRef::Id(42), "42".parse().unwrap());
+        assert_eq!(
+            url_ref("https://example.com/a"),
+            "https://example.com/a".parse().unwrap()
+        );
+        assert!("not an item".parse::<ItemRef>().is_err());
+    }
+
+    #[test]
+    fn normalize_ignores_scheme_www_slash_and_fragment() {
+        let a = Url::parse("http://www.Example.com/post/#top").unwrap();
+        let b = Url::parse("https://example.com/post").unwrap();
+
+        assert_eq!(normalize(&a), normalize(&b));
+    }
+
+    #[test]
+    fn resolve_searches_and_matches_normalized_urls() {
+        let pocket = PocketMock {
+            get_mock: |opts| {
+                assert_eq!(Some("example.com/post"), opts.search.as_deref());
+                Ok(vec![
+                    Item::fixture(1, "https://example.com/post/other"),
+                    Item::fixture(2, "http://www.example.com/post/"),
+                ])
+            },
+            send_mock: unused_send,
+            add_mock: unused_add,
+        };
+
+        let item_id = resolve(&pocket, &url_ref("https://example.com/post"), false).unwrap();
+
+        assert_eq!(2, item_id);
+    }
+
+    #[test]
+    fn resolve_requires_first_when_several_items_match() {
+        let pocket = PocketMock {
+            get_mock: |_| {
+                Ok(vec![
+                    Item::fixture(3, "https://example.com/post"),
+                    Item::fixture(2, "http://example.com/post"),
+                ])
+            },
+            send_mock: unused_send,
+            add_mock: unused_add,
+        };
+        let item = url_ref("https://example.com/post");
+
+        let ambiguous = resolve(&pocket, &item, false);
+        let first = resolve(&pocket, &item, true);
+
+        assert!(matches!(ambiguous, Err(ResolveError::Ambiguous(_, ids)) if ids == vec![3, 2]));
+        assert_eq!(3, first.unwrap());
+    }
+
+    #[test]
+    fn resolve_reports_missing_url() {
+        let pocket = PocketMock {
+            get_mock: |_| Ok(vec![]),
+            send_mock: unused_send,
+            add_mock: unused_add,
+        };
+
+        let result = resolve(&pocket, &url_ref("https://example.com/post"), false);
+
+        assert!(matches!(result, Err(ResolveError::NotFound(_))));
+    }
+}
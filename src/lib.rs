@@ -0,0 +1,34 @@
+//! The commands of the `pocket` CLI as a library.
+//!
+//! Each command module has a `handle` function that writes to an [`Output`], the
+//! way the CLI runs it, next to a function returning the typed result so other
+//! tools can run the same command without going through a shell.
+//!
+//! [`Output`]: output/struct.Output.html
+
+pub mod add;
+pub mod auth;
+pub mod autotag;
+pub mod backend;
+pub mod backup;
+pub mod canonical;
+pub mod config;
+pub mod dedupe;
+pub mod export;
+pub mod get;
+pub mod import;
+pub mod input;
+pub mod item_ref;
+pub mod models;
+pub mod next;
+pub mod open;
+pub mod output;
+pub mod picker;
+pub mod rng;
+pub mod send;
+pub mod serve_mock;
+pub mod stats;
+pub mod tag;
+pub mod tag_policy;
+pub mod tags;
+pub mod tui;
@@ -1,20 +1,13 @@
 extern crate pocket;
 extern crate structopt;
 
-use crate::output::Output;
-use pocket::*;
+use pocket_cli::output::Output;
+use pocket_cli::{
+    add, auth, autotag, backend, backup, config, dedupe, export, get, import, next, open, output,
+    send, serve_mock, stats, tag, tags, tui,
+};
 use structopt::StructOpt;
 
-mod add;
-mod auth;
-mod config;
-mod get;
-mod models;
-mod output;
-mod send;
-mod tag;
-mod tags;
-
 #[derive(Debug, StructOpt)]
 /// Interact with the Pocket API.
 struct Opts {
@@ -24,6 +17,12 @@ struct Opts {
     /// Pocket access token
     #[structopt(long, env = "POCKET_ACCESS_TOKEN")]
     access_token: Option<String>,
+    /// Backend to store items in
+    #[structopt(long, env = "POCKET_BACKEND")]
+    backend: Option<backend::BackendKind>,
+    /// Base URL of a Pocket-compatible API to use instead of getpocket.com
+    #[structopt(long, env = "POCKET_API_BASE_URL")]
+    api_base_url: Option<url::Url>,
     /// Subcommand
     #[structopt(subcommand)]
     command: Commands,
@@ -41,6 +40,11 @@ enum Commands {
         #[structopt(flatten)]
         opts: add::AddOpts,
     },
+    /// Tag items by the rules of the autotag rules file
+    Autotag {
+        #[structopt(flatten)]
+        opts: autotag::AutotagOpts,
+    },
     /// Get
     Get {
         #[structopt(flatten)]
@@ -91,70 +95,139 @@ enum Commands {
         #[structopt(flatten)]
         opts: tags::TagsOpts,
     },
+    /// Show statistics about saved and read items
+    Stats {
+        #[structopt(flatten)]
+        opts: stats::StatsOpts,
+    },
     /// Tag
     Tag(tag::Tag),
     /// Config
     Config(config::ConfigOpts),
+    /// Back up every item to an archive
+    Backup {
+        #[structopt(flatten)]
+        opts: backup::BackupOpts,
+    },
+    /// Restore items from a backup archive
+    Restore {
+        #[structopt(flatten)]
+        opts: backup::RestoreOpts,
+    },
+    /// Find items saved more than once and merge them
+    Dedupe {
+        #[structopt(flatten)]
+        opts: dedupe::DedupeOpts,
+    },
+    /// Export
+    Export(export::Export),
+    /// Import
+    Import(import::Import),
+    /// Pick what to read next
+    Next {
+        #[structopt(flatten)]
+        opts: next::NextOpts,
+    },
+    /// Open an item in the browser
+    Open {
+        #[structopt(flatten)]
+        opts: open::OpenOpts,
+    },
+    /// Browse and triage items in a terminal interface
+    Tui {
+        #[structopt(flatten)]
+        opts: tui::TuiOpts,
+    },
+    /// Serve a Pocket-compatible API from memory, for testing
+    ServeMock {
+        #[structopt(flatten)]
+        opts: serve_mock::ServeMockOpts,
+    },
 }
 
 fn main() {
     let Opts {
         consumer_key: opt_consumer_key,
         access_token: opt_access_token,
+        backend: opt_backend,
+        api_base_url: opt_api_base_url,
         command,
         output,
     } = Opts::from_args();
-    let config::Config {
-        consumer_key: cfg_consumer_key,
-        access_token: cfg_access_token,
-    } = config::load();
+    let cfg = config::load();
     let consumer_key = || {
         opt_consumer_key
-            .or(cfg_consumer_key)
+            .clone()
+            .or_else(|| cfg.consumer_key.clone())
             .expect("Consumer key missing.")
     };
-    let access_token = opt_access_token.or(cfg_access_token);
-    let pocket =
-        |consumer_key| Pocket::new(consumer_key, &access_token.expect("Access token missing."));
+    let canonical = cfg.canonical.clone().unwrap_or_default();
+    let tag_policy = cfg.tag_policy.clone().unwrap_or_default();
+    let rules = || {
+        let path = cfg
+            .autotag_rules
+            .clone()
+            .unwrap_or_else(autotag::Rules::default_path);
+        autotag::Rules::load(&path).unwrap()
+    };
+    let access_token = opt_access_token.or_else(|| cfg.access_token.clone());
+    let api_base_url = opt_api_base_url.or_else(|| cfg.api_base_url.clone());
+    let backend = || {
+        backend::connect(
+            opt_backend.or(cfg.backend).unwrap_or_default(),
+            &cfg,
+            api_base_url.clone(),
+            &consumer_key,
+            access_token,
+        )
+    };
     let writer = std::io::stdout();
     let mut output = Output::new(output, writer);
 
     match command {
-        Commands::Add { opts: ref add_opts } => {
-            add::handle(&pocket(&consumer_key()), add_opts, &mut output)
+        Commands::Add { opts: ref add_opts } => add::handle(
+            &backend(),
+            add_opts,
+            &canonical,
+            &tag_policy,
+            &rules(),
+            &mut output,
+        ),
+        Commands::Archive { ref opts } => send::archive::handle(&backend(), opts, &mut output),
+        Commands::Auth(ref sc) => {
+            auth::handle(sc, &consumer_key(), api_base_url.clone(), &mut output)
         }
-        Commands::Archive { ref opts } => {
-            send::archive::handle(&pocket(&consumer_key()), opts, &mut output)
+        Commands::Autotag { ref opts } => {
+            autotag::handle(&backend(), opts, &rules(), &tag_policy, &mut output)
         }
-        Commands::Auth(ref sc) => auth::handle(sc, &consumer_key(), &mut output),
+        Commands::Backup { ref opts } => backup::handle_backup(&backend(), opts, &mut output),
         Commands::Config(ref opts) => config::handle(opts, &mut output),
-        Commands::Delete { ref opts } => {
-            send::delete::handle(&pocket(&consumer_key()), opts, &mut output)
-        }
-        Commands::Favorite { ref opts } => {
-            send::favorite::handle(&pocket(&consumer_key()), opts, &mut output)
-        }
-        Commands::Get { opts: ref get_opts } => {
-            get::handle(&pocket(&consumer_key()), get_opts, &mut output)
-        }
-        Commands::Readd { ref opts } => {
-            send::readd::handle(&pocket(&consumer_key()), opts, &mut output)
-        }
-        Commands::Tag(ref tag) => tag::handle(&pocket(&consumer_key()), tag, &mut output),
+        Commands::Dedupe { ref opts } => dedupe::handle(&backend(), opts, &canonical, &mut output),
+        Commands::Delete { ref opts } => send::delete::handle(&backend(), opts, &mut output),
+        Commands::Export(ref export) => export::handle(&backend(), export, &mut output),
+        Commands::Favorite { ref opts } => send::favorite::handle(&backend(), opts, &mut output),
+        Commands::Get { opts: ref get_opts } => get::handle(&backend(), get_opts, &mut output),
+        Commands::Import(ref import) => import::handle(&backend(), import, &canonical, &mut output),
+        Commands::Next { ref opts } => next::handle(&backend(), opts, &mut output),
+        Commands::Open { ref opts } => open::handle(&backend(), opts, &mut output),
+        Commands::Readd { ref opts } => send::readd::handle(&backend(), opts, &mut output),
+        Commands::Restore { ref opts } => backup::handle_restore(&backend(), opts, &mut output),
+        Commands::ServeMock { ref opts } => serve_mock::handle(opts, &mut output),
+        Commands::Stats { ref opts } => stats::handle(&backend(), opts, &mut output),
+        Commands::Tag(ref tag) => tag::handle(&backend(), tag, &tag_policy, &mut output),
         Commands::TagsAdd { ref opts } => {
-            tags::tags_add::handle(&pocket(&consumer_key()), opts, &mut output)
-        }
-        Commands::TagsClear { ref opts } => {
-            send::tags_clear::handle(&pocket(&consumer_key()), opts, &mut output)
+            tags::tags_add::handle(&backend(), opts, &tag_policy, &mut output)
         }
+        Commands::TagsClear { ref opts } => send::tags_clear::handle(&backend(), opts, &mut output),
         Commands::TagsRemove { ref opts } => {
-            tags::tags_remove::handle(&pocket(&consumer_key()), opts, &mut output)
+            tags::tags_remove::handle(&backend(), opts, &tag_policy, &mut output)
         }
         Commands::TagsReplace { ref opts } => {
-            tags::tags_replace::handle(&pocket(&consumer_key()), opts, &mut output)
+            tags::tags_replace::handle(&backend(), opts, &tag_policy, &mut output)
         }
+        Commands::Tui { ref opts } => tui::handle(&backend(), opts, &mut output),
         Commands::Unfavorite { ref opts } => {
-            send::unfavorite::handle(&pocket(&consumer_key()), opts, &mut output)
+            send::unfavorite::handle(&backend(), opts, &mut output)
         }
     }
 }
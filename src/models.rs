@@ -1,12 +1,13 @@
+use crate::backend::SendResponse;
 use chrono::{DateTime, Utc};
 use pocket::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use url::Url;
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct Item {
     pub item_id: u64,
-    #[serde(with = "url_serde")]
+    #[serde(default, with = "url_serde")]
     pub given_url: Option<Url>,
     pub given_title: Option<String>,
     pub word_count: usize,
@@ -22,7 +23,7 @@ pub struct Item {
     pub has_video: ItemHas,
     pub resolved_id: u64,
     pub resolved_title: Option<String>,
-    #[serde(with = "url_serde")]
+    #[serde(default, with = "url_serde")]
     pub resolved_url: Option<Url>,
     pub sort_id: Option<u64>,
     pub status: Option<ItemStatus>,
@@ -35,17 +36,101 @@ pub struct Item {
     pub domain_metadata: Option<DomainMetadata>,
     pub listen_duration_estimate: Option<u64>,
     pub image: Option<Image>,
-    #[serde(with = "url_serde")]
+    #[serde(default, with = "url_serde")]
     pub amp_url: Option<Url>,
-    #[serde(with = "url_serde")]
+    #[serde(default, with = "url_serde")]
     pub top_image_url: Option<Url>,
 }
 
-#[derive(Debug, Serialize)]
+impl Item {
+    /// Resolved title, falling back to the given title.
+    pub fn title(&self) -> Option<&str> {
+        self.resolved_title
+            .as_deref()
+            .filter(|t| !t.is_empty())
+            .or_else(|| self.given_title.as_deref().filter(|t| !t.is_empty()))
+    }
+
+    /// Resolved URL, falling back to the given URL.
+    pub fn url(&self) -> Option<&Url> {
+        self.resolved_url
+            .as_ref()
+            .or_else(|| self.given_url.as_ref())
+    }
+
+    /// Host of the item's URL without a leading `www.`.
+    pub fn domain(&self) -> Option<&str> {
+        self.url()
+            .and_then(|u| u.host_str())
+            .map(|h| h.trim_start_matches("www."))
+    }
+
+    /// Whether the item has a video in it or is a video.
+    pub fn has_video(&self) -> bool {
+        matches!(self.has_video, ItemHas::Yes | ItemHas::Is)
+    }
+
+    pub fn tag_names(&self) -> Vec<&str> {
+        self.tags.iter().flatten().map(|t| t.0.as_str()).collect()
+    }
+
+    pub fn author_names(&self) -> Vec<&str> {
+        self.authors
+            .iter()
+            .flatten()
+            .map(|a| a.name.as_str())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+impl Item {
+    pub fn fixture(item_id: u64, url: &str) -> Item {
+        Item {
+            item_id,
+            given_url: Some(Url::parse(url).unwrap()),
+            given_title: None,
+            word_count: 0,
+            excerpt: "".to_string(),
+            time_added: None,
+            time_read: None,
+            time_updated: None,
+            time_favorited: None,
+            favorite: None,
+            is_index: false,
+            is_article: false,
+            has_image: ItemHas::No,
+            has_video: ItemHas::No,
+            resolved_id: item_id,
+            resolved_title: None,
+            resolved_url: None,
+            sort_id: None,
+            status: None,
+            tags: None,
+            images: None,
+            videos: None,
+            authors: None,
+            lang: None,
+            time_to_read: None,
+            domain_metadata: None,
+            listen_duration_estimate: None,
+            image: None,
+            amp_url: None,
+            top_image_url: None,
+        }
+    }
+
+    pub fn with_tags(mut self, tags: &[&str]) -> Item {
+        self.tags = Some(tags.iter().map(|t| Tag(t.to_string())).collect());
+        self
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Image {
     pub item_id: u64,
     pub image_id: Option<u64>,
-    #[serde(with = "url_serde")]
+    #[serde(default, with = "url_serde")]
     pub src: Option<Url>,
     pub width: u16,
     pub height: u16,
@@ -67,7 +152,7 @@ impl From<ItemImage> for Image {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DomainMetadata {
     pub name: Option<String>,
     pub logo: String,
@@ -84,8 +169,8 @@ impl From<DomainMetaData> for DomainMetadata {
     }
 }
 
-#[derive(Debug, Serialize)]
-pub struct Tag(String);
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Tag(pub String);
 
 impl From<ItemTag> for Tag {
     fn from(t: ItemTag) -> Self {
@@ -93,11 +178,11 @@ impl From<ItemTag> for Tag {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Video {
     pub item_id: u64,
     pub video_id: u64,
-    #[serde(with = "url_serde")]
+    #[serde(default, with = "url_serde")]
     pub src: Option<Url>,
     pub width: u16,
     pub height: u16,
@@ -122,7 +207,7 @@ impl From<ItemVideo> for Video {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Author {
     pub item_id: u64,
     pub author_id: u64,
@@ -141,7 +226,7 @@ impl From<ItemAuthor> for Author {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum ItemHas {
     No,
     Yes,
@@ -158,7 +243,7 @@ impl From<PocketItemHas> for ItemHas {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum ItemStatus {
     Normal,
     Archived,
@@ -175,14 +260,26 @@ impl From<PocketItemStatus> for ItemStatus {
     }
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct IndividualSendResponse {
     pub status: u16,
     pub success: bool,
 }
 
-impl From<PocketSendResponse> for IndividualSendResponse {
-    fn from(p: PocketSendResponse) -> Self {
+impl IndividualSendResponse {
+    /// One response per action sent.
+    pub fn each(response: SendResponse) -> Vec<IndividualSendResponse> {
+        let status = response.status;
+        response
+            .action_results
+            .into_iter()
+            .map(|success| IndividualSendResponse { status, success })
+            .collect()
+    }
+}
+
+impl From<SendResponse> for IndividualSendResponse {
+    fn from(p: SendResponse) -> Self {
         IndividualSendResponse {
             status: p.status,
             success: p.action_results[0],
@@ -0,0 +1,322 @@
+use crate::backend::{BackendResult, PocketGet};
+use crate::get::GetOpts;
+use crate::models::Item;
+use crate::output::Output;
+use crate::rng::Rng;
+use chrono::{DateTime, Utc};
+use pocket::{PocketGetDetail, PocketGetSort, PocketGetState};
+use serde::Serialize;
+use std::io::Write;
+use structopt::StructOpt;
+use url::Url;
+
+#[derive(Debug, StructOpt)]
+pub struct NextOpts {
+    /// Minutes to spend reading; the picks add up to at most this
+    #[structopt(long)]
+    pub minutes: Option<u64>,
+    /// Most items to pick; one without --minutes, as many as fit with it
+    #[structopt(long)]
+    pub count: Option<usize>,
+    /// Seed for the jitter between scores, to repeat the same picks
+    #[structopt(long)]
+    pub seed: Option<u64>,
+    /// Reading speed for items without a time to read
+    #[structopt(long, default_value = "200")]
+    pub words_per_minute: u64,
+    #[structopt(flatten)]
+    pub scoring: Scoring,
+}
+
+/// Weights of what makes an item a good pick. Negative weights count against it.
+#[derive(Debug, StructOpt)]
+pub struct Scoring {
+    /// Score of an item saved a year or more ago, scaled down for newer items
+    #[structopt(long, default_value = "1", allow_hyphen_values = true)]
+    pub age_weight: f64,
+    /// Score of a favorite
+    #[structopt(long, default_value = "1", allow_hyphen_values = true)]
+    pub favorite_weight: f64,
+    /// Tag to prefer
+    #[structopt(long = "prefer-tag")]
+    pub prefer_tags: Vec<String>,
+    /// Score of each preferred tag an item has
+    #[structopt(long, default_value = "1", allow_hyphen_values = true)]
+    pub tag_weight: f64,
+    /// Domain to prefer
+    #[structopt(long = "prefer-domain")]
+    pub prefer_domains: Vec<String>,
+    /// Score of an item from a preferred domain
+    #[structopt(long, default_value = "1", allow_hyphen_values = true)]
+    pub domain_weight: f64,
+    /// Score of an article
+    #[structopt(long, default_value = "0.5", allow_hyphen_values = true)]
+    pub article_weight: f64,
+    /// Score of an item with a video
+    #[structopt(long, default_value = "0", allow_hyphen_values = true)]
+    pub video_weight: f64,
+    /// Largest random score added to break ties and vary picks
+    #[structopt(long, default_value = "0.1")]
+    pub jitter: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Pick {
+    pub item_id: u64,
+    pub title: Option<String>,
+    #[serde(with = "url_serde")]
+    pub url: Option<Url>,
+    pub minutes: Option<u64>,
+    pub score: f64,
+    /// What the score is made of
+    pub explanation: String,
+}
+
+pub fn handle<W: Write>(pocket: &impl PocketGet, opts: &NextOpts, output: &mut Output<W>) {
+    let picks = next(pocket, opts, Utc::now()).unwrap();
+    output.write(picks).unwrap();
+}
+
+/// The best scoring unread items that fit in the time budget, best first.
+pub fn next(
+    pocket: &impl PocketGet,
+    opts: &NextOpts,
+    now: DateTime<Utc>,
+) -> BackendResult<Vec<Pick>> {
+    let items = pocket.get(&GetOpts {
+        state: Some(PocketGetState::Unread),
+        detail_type: Some(PocketGetDetail::Complete),
+        sort: Some(PocketGetSort::Newest),
+        ..Default::default()
+    })?;
+    let mut rng = opts.seed.map_or_else(Rng::from_time, Rng::new);
+    let picks = rank(&items, opts, now, &mut rng);
+
+    let limit = match (opts.count, opts.minutes) {
+        (Some(count), _) => count,
+        (None, Some(_)) => usize::MAX,
+        (None, None) => 1,
+    };
+    let mut left = opts.minutes;
+    Ok(picks
+        .into_iter()
+        .filter(|pick| match (left, pick.minutes) {
+            (None, _) => true,
+            (Some(budget), Some(minutes)) if minutes <= budget => {
+                left = Some(budget - minutes);
+                true
+            }
+            // Without a length, an item can't be known to fit
+            (Some(_), _) => false,
+        })
+        .take(limit)
+        .collect())
+}
+
+/// The best scoring of `items` by the default weights, which `open --next` opens.
+pub fn best(items: &[Item], now: DateTime<Utc>, rng: &mut Rng) -> Option<u64> {
+    let opts = NextOpts::from_iter(&["next"]);
+    rank(items, &opts, now, rng).first().map(|p| p.item_id)
+}
+
+/// `items` scored, best first.
+fn rank(items: &[Item], opts: &NextOpts, now: DateTime<Utc>, rng: &mut Rng) -> Vec<Pick> {
+    let mut picks = items
+        .iter()
+        .map(|item| pick(item, opts, now, rng))
+        .collect::<Vec<_>>();
+    // Stable, so ties keep the order of `items`. A NaN score, say from a NaN weight, sorts last
+    picks.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or_else(|| a.score.is_nan().cmp(&b.score.is_nan()))
+    });
+    picks
+}
+
+fn pick(item: &Item, opts: &NextOpts, now: DateTime<Utc>, rng: &mut Rng) -> Pick {
+    let scoring = &opts.scoring;
+    let mut score = 0.0;
+    let mut reasons = Vec::new();
+    let mut add = |points: f64, reason: String| {
+        if points != 0.0 {
+            score += points;
+            reasons.push(format!("{} {:+.2}", reason, points));
+        }
+    };
+
+    if let Some(added) = item.time_added {
+        let days = (now - added).num_days().max(0);
+        add(
+            scoring.age_weight * (days as f64 / 365.0).min(1.0),
+            format!("saved {} days ago", days),
+        );
+    }
+    if item.favorite.unwrap_or_default() {
+        add(scoring.favorite_weight, "favorite".to_string());
+    }
+    for tag in item.tag_names() {
+        if scoring.prefer_tags.iter().any(|t| t == tag) {
+            add(scoring.tag_weight, format!("tag {}", tag));
+        }
+    }
+    if let Some(domain) = item.domain() {
+        if scoring.prefer_domains.iter().any(|d| d == domain) {
+            add(scoring.domain_weight, format!("from {}", domain));
+        }
+    }
+    if item.is_article {
+        add(scoring.article_weight, "article".to_string());
+    }
+    if item.has_video() {
+        add(scoring.video_weight, "video".to_string());
+    }
+    add(scoring.jitter * rng.unit(), "jitter".to_string());
+
+    Pick {
+        item_id: item.item_id,
+        title: item.title().map(str::to_string),
+        url: item.url().cloned(),
+        minutes: minutes(item, opts.words_per_minute),
+        score,
+        explanation: reasons.join(", "),
+    }
+}
+
+/// Minutes to read or listen to `item`, from Pocket's estimate when there is one.
+fn minutes(item: &Item, words_per_minute: u64) -> Option<u64> {
+    let ceil_div = |a: u64, b: u64| (a + b - 1) / b;
+    item.time_to_read
+        .filter(|m| *m > 0)
+        .or_else(|| match item.word_count as u64 {
+            0 => None,
+            words => Some(ceil_div(words, words_per_minute.max(1))),
+        })
+        .or_else(|| {
+            item.listen_duration_estimate
+                .filter(|s| *s > 0)
+                .map(|s| ceil_div(s, 60))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::{unused_add, unused_send, PocketMock};
+    use chrono::{Duration, TimeZone};
+
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2020, 6, 1).and_hms(0, 0, 0)
+    }
+
+    fn opts() -> NextOpts {
+        NextOpts {
+            minutes: None,
+            count: None,
+            seed: Some(1),
+            words_per_minute: 200,
+            scoring: Scoring {
+                age_weight: 1.0,
+                favorite_weight: 1.0,
+                prefer_tags: vec![],
+                tag_weight: 1.0,
+                prefer_domains: vec![],
+                domain_weight: 1.0,
+                article_weight: 0.5,
+                video_weight: 0.0,
+                jitter: 0.0,
+            },
+        }
+    }
+
+    fn item(item_id: u64, minutes: u64, days_old: i64) -> Item {
+        let mut item = Item::fixture(item_id, &format!("https://example.com/{}", item_id));
+        item.time_to_read = Some(minutes);
+        item.time_added = Some(now() - Duration::days(days_old));
+        item
+    }
+
+    fn next_ids(items: fn() -> Vec<Item>, opts: &NextOpts) -> Vec<u64> {
+        let pocket = PocketMock {
+            get_mock: |_| Ok(items()),
+            send_mock: unused_send,
+            add_mock: unused_add,
+        };
+        next(&pocket, opts, now())
+            .unwrap()
+            .iter()
+            .map(|p| p.item_id)
+            .collect()
+    }
+
+    #[test]
+    fn next_picks_best_scoring_item() {
+        fn items() -> Vec<Item> {
+            let mut favorite = item(2, 5, 73);
+            favorite.favorite = Some(true);
+            vec![item(1, 5, 30), favorite, item(3, 5, 365)]
+        }
+
+        assert_eq!(vec![2], next_ids(items, &opts()));
+    }
+
+    #[test]
+    fn next_fills_minutes_with_items_that_fit() {
+        fn items() -> Vec<Item> {
+            vec![item(1, 15, 300), item(2, 10, 200), item(3, 5, 100)]
+        }
+        let mut opts = opts();
+        opts.minutes = Some(20);
+
+        assert_eq!(vec![1, 3], next_ids(items, &opts));
+    }
+
+    #[test]
+    fn next_prefers_tags_and_domains() {
+        fn items() -> Vec<Item> {
+            vec![item(1, 5, 100), item(2, 5, 0).with_tags(&["rust"])]
+        }
+        let mut opts = opts();
+        opts.scoring.prefer_tags = vec!["rust".to_string()];
+
+        assert_eq!(vec![2], next_ids(items, &opts));
+    }
+
+    #[test]
+    fn next_sorts_nan_scores_last() {
+        fn items() -> Vec<Item> {
+            let mut favorite = item(2, 5, 0);
+            favorite.favorite = Some(true);
+            vec![favorite, item(1, 5, 0), item(3, 5, 0)]
+        }
+        let mut opts = opts();
+        opts.scoring.favorite_weight = f64::NAN;
+        opts.count = Some(3);
+
+        assert_eq!(vec![1, 3, 2], next_ids(items, &opts));
+    }
+
+    #[test]
+    fn next_is_reproducible_with_seed() {
+        fn items() -> Vec<Item> {
+            (1..=10).map(|i| item(i, 5, 0)).collect()
+        }
+        let mut opts = opts();
+        opts.scoring.jitter = 1.0;
+        opts.count = Some(3);
+
+        assert_eq!(next_ids(items, &opts), next_ids(items, &opts));
+    }
+
+    #[test]
+    fn pick_explains_score() {
+        let mut item = item(1, 0, 73);
+        item.is_article = true;
+        item.word_count = 450;
+
+        let pick = pick(&item, &opts(), now(), &mut Rng::new(0));
+
+        assert_eq!("saved 73 days ago +0.20, article +0.50", pick.explanation);
+        assert_eq!(Some(3), pick.minutes);
+    }
+}
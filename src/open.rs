@@ -0,0 +1,340 @@
+use crate::backend::{send_all, BackendError, PocketGet, PocketSend};
+use crate::get::GetOpts;
+use crate::item_ref::{self, ItemRef, ResolveError};
+use crate::models::{IndividualSendResponse, Item};
+use crate::next;
+use crate::output::Output;
+use crate::rng::Rng;
+use chrono::Utc;
+use pocket::{PocketGetDetail, PocketGetSort, PocketGetState, PocketSendAction};
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+use structopt::StructOpt;
+use url::Url;
+
+#[derive(Debug, StructOpt)]
+pub struct OpenOpts {
+    /// Item id, URL or search query; every unread item when left out
+    pub item: Option<String>,
+    /// Open a random item of those matching
+    #[structopt(long, conflicts_with_all = &["oldest", "next"])]
+    pub random: bool,
+    /// Open the oldest item of those matching instead of the newest
+    #[structopt(long, conflicts_with = "next")]
+    pub oldest: bool,
+    /// Open the item of those matching that `pocket next` recommends
+    #[structopt(long)]
+    pub next: bool,
+    /// URL to open first: resolved, given or amp. The others are tried when the item has none
+    #[structopt(long, default_value = "resolved")]
+    pub url: UrlKind,
+    /// Archive the item after opening it
+    #[structopt(long)]
+    pub archive: bool,
+    /// Tag the item after opening it
+    #[structopt(long = "tag")]
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UrlKind {
+    Resolved,
+    Given,
+    Amp,
+}
+
+impl FromStr for UrlKind {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "resolved" => Ok(UrlKind::Resolved),
+            "given" => Ok(UrlKind::Given),
+            "amp" => Ok(UrlKind::Amp),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("Invalid URL kind: {}", s),
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Opened {
+    pub item_id: u64,
+    #[serde(with = "url_serde")]
+    pub url: Url,
+    /// Responses to archiving and tagging the item, when asked for
+    pub actions: Vec<IndividualSendResponse>,
+}
+
+#[derive(Debug)]
+pub enum OpenError {
+    Resolve(ResolveError),
+    Backend(BackendError),
+    NoMatch,
+    NoUrl(u64),
+    Browser(String),
+}
+
+impl Error for OpenError {}
+
+impl fmt::Display for OpenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpenError::Resolve(e) => fmt::Display::fmt(e, f),
+            OpenError::Backend(e) => fmt::Display::fmt(e, f),
+            OpenError::NoMatch => write!(f, "no item to open"),
+            OpenError::NoUrl(item_id) => write!(f, "item {} has no URL to open", item_id),
+            OpenError::Browser(e) => write!(f, "failed to open browser: {}", e),
+        }
+    }
+}
+
+impl From<ResolveError> for OpenError {
+    fn from(e: ResolveError) -> Self {
+        OpenError::Resolve(e)
+    }
+}
+
+impl From<BackendError> for OpenError {
+    fn from(e: BackendError) -> Self {
+        OpenError::Backend(e)
+    }
+}
+
+pub fn handle<W: Write>(
+    pocket: &(impl PocketGet + PocketSend),
+    opts: &OpenOpts,
+    output: &mut Output<W>,
+) {
+    let opened = open(pocket, opts, &mut Rng::from_time(), &open_browser)
+        .unwrap_or_else(|e| panic!("{}", e));
+    output.write(opened).unwrap();
+}
+
+/// Open the item picked by `opts` with `browser`, then archive or tag it if asked to.
+pub fn open(
+    pocket: &(impl PocketGet + PocketSend),
+    opts: &OpenOpts,
+    rng: &mut Rng,
+    browser: &dyn Fn(&Url) -> Result<(), Box<dyn Error>>,
+) -> Result<Opened, OpenError> {
+    let item = pick(pocket, opts, rng)?;
+    let url = url(&item, opts.url).ok_or(OpenError::NoUrl(item.item_id))?;
+    browser(url).map_err(|e| OpenError::Browser(e.to_string()))?;
+
+    let mut actions = Vec::new();
+    if let Some(tags) = &opts.tags {
+        actions.push(PocketSendAction::TagsAdd {
+            item_id: item.item_id,
+            tags: tags.join(","),
+            time: None,
+        });
+    }
+    if opts.archive {
+        actions.push(PocketSendAction::Archive {
+            item_id: item.item_id,
+            time: None,
+        });
+    }
+    let actions = if actions.is_empty() {
+        vec![]
+    } else {
+        IndividualSendResponse::each(send_all(pocket, &actions)?)
+    };
+
+    Ok(Opened {
+        item_id: item.item_id,
+        url: url.clone(),
+        actions,
+    })
+}
+
+/// The item named by id or URL, or else one of the unread items matching the query.
+fn pick(pocket: &impl PocketGet, opts: &OpenOpts, rng: &mut Rng) -> Result<Item, OpenError> {
+    let named = opts.item.as_deref().and_then(|s| s.parse::<ItemRef>().ok());
+    let mut items = match named {
+        Some(item) => {
+            let item_id = item_ref::resolve(pocket, &item, true)?;
+            let items = pocket.get(&GetOpts {
+                state: Some(PocketGetState::All),
+                ..Default::default()
+            })?;
+            items.into_iter().filter(|i| i.item_id == item_id).collect()
+        }
+        None => pocket.get(&GetOpts {
+            search: opts.item.clone(),
+            state: Some(PocketGetState::Unread),
+            // Scoring --next looks at tags and word counts
+            detail_type: if opts.next {
+                Some(PocketGetDetail::Complete)
+            } else {
+                None
+            },
+            sort: Some(if opts.oldest {
+                PocketGetSort::Oldest
+            } else {
+                PocketGetSort::Newest
+            }),
+            ..Default::default()
+        })?,
+    };
+
+    if items.is_empty() {
+        return Err(OpenError::NoMatch);
+    }
+    let index = if opts.random {
+        rng.below(items.len())
+    } else if opts.next {
+        let best = next::best(&items, Utc::now(), rng);
+        items
+            .iter()
+            .position(|i| Some(i.item_id) == best)
+            .unwrap_or(0)
+    } else {
+        0
+    };
+    Ok(items.swap_remove(index))
+}
+
+/// The URL of `kind`, falling back to the resolved, given and AMP URL in that order.
+fn url(item: &Item, kind: UrlKind) -> Option<&Url> {
+    let of_kind = |kind| match kind {
+        UrlKind::Resolved => item.resolved_url.as_ref(),
+        UrlKind::Given => item.given_url.as_ref(),
+        UrlKind::Amp => item.amp_url.as_ref(),
+    };
+    of_kind(kind)
+        .or_else(|| of_kind(UrlKind::Resolved))
+        .or_else(|| of_kind(UrlKind::Given))
+        .or_else(|| of_kind(UrlKind::Amp))
+}
+
+fn open_browser(url: &Url) -> Result<(), Box<dyn Error>> {
+    webbrowser::open(url.as_str())
+        .map(|_| ())
+        .map_err(|e| e.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::{succeed, unused_add, unused_send, PocketMock};
+    use crate::backend::{BackendResult, SendResponse};
+    use pocket::PocketSendRequest;
+    use std::cell::RefCell;
+
+    fn opts(item: Option<&str>) -> OpenOpts {
+        OpenOpts {
+            item: item.map(|s| s.to_string()),
+            random: false,
+            oldest: false,
+            next: false,
+            url: UrlKind::Resolved,
+            archive: false,
+            tags: None,
+        }
+    }
+
+    fn queue() -> Vec<Item> {
+        let mut favorite = Item::fixture(2, "https://example.com/b");
+        favorite.favorite = Some(true);
+        vec![
+            Item::fixture(3, "https://example.com/c"),
+            favorite,
+            Item::fixture(1, "https://example.com/a"),
+        ]
+    }
+
+    fn open_with(
+        opts: &OpenOpts,
+        send_mock: impl Fn(&PocketSendRequest) -> BackendResult<SendResponse>,
+    ) -> (Opened, Vec<Url>) {
+        let pocket = PocketMock {
+            get_mock: |opts: &GetOpts| {
+                let mut items = queue();
+                if let Some(PocketGetSort::Oldest) = opts.sort {
+                    items.reverse();
+                }
+                Ok(items)
+            },
+            send_mock,
+            add_mock: unused_add,
+        };
+        let opened_urls = RefCell::new(Vec::new());
+        let browser = |url: &Url| {
+            opened_urls.borrow_mut().push(url.clone());
+            Ok(())
+        };
+
+        let opened = open(&pocket, opts, &mut Rng::new(1), &browser).unwrap();
+        (opened, opened_urls.into_inner())
+    }
+
+    #[test]
+    fn open_opens_newest_unread_item_by_default() {
+        let (opened, urls) = open_with(&opts(None), unused_send);
+
+        assert_eq!(3, opened.item_id);
+        assert_eq!(vec![Url::parse("https://example.com/c").unwrap()], urls);
+    }
+
+    #[test]
+    fn open_picks_oldest_and_random() {
+        let mut oldest = opts(Some("example"));
+        oldest.oldest = true;
+        let mut random = opts(None);
+        random.random = true;
+
+        assert_eq!(1, open_with(&oldest, unused_send).0.item_id);
+        assert!([1, 2, 3].contains(&open_with(&random, unused_send).0.item_id));
+    }
+
+    #[test]
+    fn open_next_picks_recommended_item() {
+        let mut next = opts(None);
+        next.next = true;
+
+        assert_eq!(2, open_with(&next, unused_send).0.item_id);
+    }
+
+    #[test]
+    fn open_archives_and_tags_after_opening() {
+        let mut opts = opts(Some("2"));
+        opts.archive = true;
+        opts.tags = Some(vec!["read".to_string()]);
+
+        let (opened, _) = open_with(&opts, |request| {
+            assert!(matches!(
+                request.actions,
+                [
+                    PocketSendAction::TagsAdd { item_id: 2, .. },
+                    PocketSendAction::Archive { item_id: 2, .. }
+                ]
+            ));
+            Ok(succeed(request))
+        });
+
+        assert_eq!(2, opened.item_id);
+        assert_eq!(2, opened.actions.len());
+    }
+
+    #[test]
+    fn url_falls_back_when_kind_is_missing() {
+        let mut item = Item::fixture(1, "https://example.com/given");
+        item.amp_url = Some(Url::parse("https://amp.example.com/").unwrap());
+
+        assert_eq!(
+            "https://amp.example.com/",
+            url(&item, UrlKind::Amp).unwrap().as_str()
+        );
+        assert_eq!(
+            "https://example.com/given",
+            url(&item, UrlKind::Resolved).unwrap().as_str()
+        );
+    }
+}
@@ -6,6 +6,8 @@ use std::fmt::{Debug, Display};
 use std::io::Write;
 use std::str::FromStr;
 
+const TOML_ITEMS_KEY: &str = "items";
+
 #[derive(Clone, Copy, Debug)]
 pub enum OutputFormat {
     Json,
@@ -77,6 +79,14 @@ where
         self.writer.flush().map_err(|_| OutputError {})
     }
 
+    /// Write preformatted text as is, regardless of the output format.
+    pub fn write_text(&mut self, text: &str) -> Result<(), OutputError> {
+        self.writer
+            .write_all(text.as_bytes())
+            .map_err(|_| OutputError {})?;
+        self.writer.flush().map_err(|_| OutputError {})
+    }
+
     fn json<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), OutputError> {
         serde_json::to_writer(&mut self.writer, value).map_err(|_| OutputError {})
     }
@@ -85,8 +95,17 @@ where
         serde_yaml::to_writer(&mut self.writer, &value).map_err(|_| OutputError {})
     }
 
+    /// TOML documents are tables, so a list is written as the `items` array of one.
     fn toml<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), OutputError> {
-        let s = toml::to_string(value).map_err(|_| OutputError {})?;
+        let value = match toml::Value::try_from(value).map_err(|_| OutputError {})? {
+            toml::Value::Array(items) => {
+                let mut table = toml::value::Table::new();
+                table.insert(TOML_ITEMS_KEY.to_string(), toml::Value::Array(items));
+                toml::Value::Table(table)
+            }
+            value => value,
+        };
+        let s = toml::to_string(&value).map_err(|_| OutputError {})?;
 
         write!(self.writer, "{}", s).map_err(|_| OutputError {})
     }
@@ -0,0 +1,330 @@
+use crate::models::Item;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use crossterm::style::Print;
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use url::Url;
+
+/// Whether there is a terminal to pick items in. Stdin must be one for keys to be
+/// read, and the picker draws on stderr to keep stdout for the command's output.
+pub fn is_interactive() -> bool {
+    atty::is(atty::Stream::Stdin) && atty::is(atty::Stream::Stderr)
+}
+
+/// Let the user pick any number of `items` with a fuzzy finder. Nothing is picked when
+/// the picker is cancelled.
+pub fn pick(items: &[Item]) -> crossterm::Result<Vec<u64>> {
+    let mut picker = Picker::new(items.iter().map(label).collect());
+    let mut out = io::stderr();
+
+    terminal::enable_raw_mode()?;
+    execute!(out, EnterAlternateScreen, Hide)?;
+    let picked = run(&mut picker, &mut out);
+    // Restore the terminal even when drawing or reading keys failed
+    execute!(out, Show, LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+
+    Ok(picked?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|i| items[i].item_id)
+        .collect())
+}
+
+/// Title, domain and tags, which is what the query is matched against.
+pub fn label(item: &Item) -> String {
+    let mut label = item
+        .title()
+        .or_else(|| item.url().map(Url::as_str))
+        .unwrap_or_default()
+        .to_string();
+    if let Some(domain) = item.domain() {
+        label.push_str("  ");
+        label.push_str(domain);
+    }
+    for tag in item.tag_names() {
+        label.push_str(" #");
+        label.push_str(tag);
+    }
+    label
+}
+
+/// How well `query` matches `text`, if every character of it appears in order.
+/// Matches at the start of a word and runs of adjacent characters score higher,
+/// and gaps between matched characters lower. Case is ignored, as are spaces in the query.
+pub fn score(query: &str, text: &str) -> Option<i64> {
+    let text = text.to_lowercase().chars().collect::<Vec<_>>();
+    let mut score = 0;
+    let mut from = 0;
+    let mut previous: Option<usize> = None;
+
+    for q in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let at = from + text[from..].iter().position(|c| *c == q)?;
+        score += 1;
+        if at == 0 || !text[at - 1].is_alphanumeric() {
+            score += 8;
+        }
+        if let Some(previous) = previous {
+            if previous + 1 == at {
+                score += 5;
+            } else {
+                score -= (at - previous - 1).min(10) as i64;
+            }
+        }
+        previous = Some(at);
+        from = at + 1;
+    }
+
+    Some(score)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Key {
+    Char(char),
+    Backspace,
+    Up,
+    Down,
+    Toggle,
+    Accept,
+    Cancel,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Continue,
+    /// Indexes of the picked labels, in their original order
+    Accept(Vec<usize>),
+    Cancel,
+}
+
+/// Query, matches and selection of the picker, apart from the terminal it is drawn in.
+pub struct Picker {
+    labels: Vec<String>,
+    query: String,
+    /// Indexes of the labels matching the query, best first
+    matches: Vec<usize>,
+    cursor: usize,
+    selected: BTreeSet<usize>,
+}
+
+impl Picker {
+    pub fn new(labels: Vec<String>) -> Picker {
+        let mut picker = Picker {
+            labels,
+            query: String::new(),
+            matches: Vec::new(),
+            cursor: 0,
+            selected: BTreeSet::new(),
+        };
+        picker.refilter();
+        picker
+    }
+
+    pub fn press(&mut self, key: Key) -> Outcome {
+        match key {
+            Key::Char(c) => {
+                self.query.push(c);
+                self.refilter();
+            }
+            Key::Backspace => {
+                self.query.pop();
+                self.refilter();
+            }
+            Key::Up => self.cursor = self.cursor.saturating_sub(1),
+            Key::Down => {
+                if self.cursor + 1 < self.matches.len() {
+                    self.cursor += 1;
+                }
+            }
+            Key::Toggle => {
+                if let Some(index) = self.current() {
+                    if !self.selected.remove(&index) {
+                        self.selected.insert(index);
+                    }
+                    return self.press(Key::Down);
+                }
+            }
+            // Without a selection, enter picks the item under the cursor
+            Key::Accept if self.selected.is_empty() => {
+                return Outcome::Accept(self.current().into_iter().collect());
+            }
+            Key::Accept => return Outcome::Accept(self.selected.iter().copied().collect()),
+            Key::Cancel => return Outcome::Cancel,
+        }
+        Outcome::Continue
+    }
+
+    fn current(&self) -> Option<usize> {
+        self.matches.get(self.cursor).copied()
+    }
+
+    fn refilter(&mut self) {
+        let mut scored = self
+            .labels
+            .iter()
+            .enumerate()
+            .filter_map(|(i, label)| score(&self.query, label).map(|s| (i, s)))
+            .collect::<Vec<_>>();
+        // Stable, so equal scores keep the order the items came in
+        scored.sort_by_key(|(_, s)| -s);
+        self.matches = scored.into_iter().map(|(i, _)| i).collect();
+        self.cursor = 0;
+    }
+}
+
+fn run(picker: &mut Picker, out: &mut impl Write) -> crossterm::Result<Option<Vec<usize>>> {
+    loop {
+        draw(picker, out)?;
+        let key = match event::read()? {
+            Event::Key(key) => key,
+            _ => continue,
+        };
+        let key = match (key.code, key.modifiers) {
+            (KeyCode::Esc, _) | (KeyCode::Char('c'), KeyModifiers::CONTROL) => Key::Cancel,
+            (KeyCode::Up, _) | (KeyCode::Char('p'), KeyModifiers::CONTROL) => Key::Up,
+            (KeyCode::Down, _) | (KeyCode::Char('n'), KeyModifiers::CONTROL) => Key::Down,
+            (KeyCode::Tab, _) => Key::Toggle,
+            (KeyCode::Enter, _) => Key::Accept,
+            (KeyCode::Backspace, _) => Key::Backspace,
+            (KeyCode::Char(c), _) => Key::Char(c),
+            _ => continue,
+        };
+
+        match picker.press(key) {
+            Outcome::Continue => {}
+            Outcome::Accept(picked) => return Ok(Some(picked)),
+            Outcome::Cancel => return Ok(None),
+        }
+    }
+}
+
+fn draw(picker: &Picker, out: &mut impl Write) -> crossterm::Result<()> {
+    let (width, height) = terminal::size()?;
+    let rows = height.saturating_sub(2) as usize;
+    let line = |s: String| s.chars().take(width as usize).collect::<String>();
+
+    queue!(
+        out,
+        Clear(ClearType::All),
+        MoveTo(0, 0),
+        Print(line(format!("> {}", picker.query)))
+    )?;
+
+    // Scroll just far enough to keep the cursor on screen
+    let first = (picker.cursor + 1).saturating_sub(rows);
+    for (row, (n, index)) in picker
+        .matches
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(rows)
+        .enumerate()
+    {
+        let pointer = if n == picker.cursor { '>' } else { ' ' };
+        let mark = if picker.selected.contains(index) {
+            '*'
+        } else {
+            ' '
+        };
+        queue!(
+            out,
+            MoveTo(0, row as u16 + 1),
+            Print(line(format!(
+                "{}{} {}",
+                pointer, mark, picker.labels[*index]
+            )))
+        )?;
+    }
+
+    queue!(
+        out,
+        MoveTo(0, height.saturating_sub(1)),
+        Print(line(format!(
+            "{}/{} ({} selected)  tab: select  enter: accept  esc: cancel",
+            picker.matches.len(),
+            picker.labels.len(),
+            picker.selected.len()
+        )))
+    )?;
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Tag;
+
+    fn picker(labels: &[&str]) -> Picker {
+        Picker::new(labels.iter().map(|l| l.to_string()).collect())
+    }
+
+    fn type_query(picker: &mut Picker, query: &str) {
+        for c in query.chars() {
+            picker.press(Key::Char(c));
+        }
+    }
+
+    #[test]
+    fn score_requires_every_character_in_order() {
+        assert!(score("rst", "Rust book").is_some());
+        assert!(score("tsr", "Rust book").is_none());
+        assert_eq!(Some(0), score("", "anything"));
+    }
+
+    #[test]
+    fn score_prefers_word_starts_and_runs() {
+        let word = score("rb", "rust book").unwrap();
+        let inside = score("rb", "curbs").unwrap();
+        let run = score("bo", "rust book").unwrap();
+        let gap = score("bk", "rust book").unwrap();
+
+        assert!(word > inside);
+        assert!(run > gap);
+    }
+
+    #[test]
+    fn label_has_title_domain_and_tags() {
+        let mut item = Item::fixture(1, "https://www.example.com/post");
+        item.given_title = Some("Post".to_string());
+        item.tags = Some(vec![Tag("rust".to_string())]);
+
+        assert_eq!("Post  example.com #rust", label(&item));
+    }
+
+    #[test]
+    fn picker_filters_and_accepts_item_under_cursor() {
+        let mut picker = picker(&["Rust book", "Go tour", "Rustonomicon"]);
+
+        type_query(&mut picker, "rust");
+        picker.press(Key::Down);
+
+        assert_eq!(Outcome::Accept(vec![2]), picker.press(Key::Accept));
+    }
+
+    #[test]
+    fn picker_accepts_selection_in_original_order() {
+        let mut picker = picker(&["a", "b", "c"]);
+
+        picker.press(Key::Down);
+        picker.press(Key::Toggle);
+        picker.press(Key::Up);
+        picker.press(Key::Up);
+        picker.press(Key::Toggle);
+
+        assert_eq!(Outcome::Accept(vec![0, 1]), picker.press(Key::Accept));
+    }
+
+    #[test]
+    fn picker_accepts_nothing_when_nothing_matches() {
+        let mut picker = picker(&["a"]);
+
+        type_query(&mut picker, "zzz");
+
+        assert_eq!(Outcome::Accept(vec![]), picker.press(Key::Accept));
+        assert_eq!(Outcome::Cancel, picker.press(Key::Cancel));
+    }
+}
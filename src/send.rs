@@ -1,31 +1,54 @@
+use crate::item_ref::ItemRef;
+use crate::output::OutputFormat;
 use chrono::{DateTime, Utc};
-use pocket::{Pocket, PocketResult, PocketSendRequest, PocketSendResponse};
 use structopt::StructOpt;
 
 macro_rules! send_item {
     ($command:ident, $action:ident) => {
         pub mod $command {
-            use super::{PocketSend, SendItemOpts};
+            use super::SendItemOpts;
+            use crate::backend::{send_all, BackendResult, PocketGet, PocketSend};
+            use crate::item_ref;
             use crate::models::IndividualSendResponse;
             use crate::output::Output;
-            use pocket::{PocketSendAction, PocketSendRequest};
+            use chrono::{DateTime, Utc};
+            use pocket::PocketSendAction;
             use std::io::Write;
 
             pub fn handle<W: Write>(
-                pocket: &impl PocketSend,
+                pocket: &(impl PocketGet + PocketSend),
                 opts: &SendItemOpts,
                 output: &mut Output<W>,
             ) {
-                let response: IndividualSendResponse = pocket
-                    .send(&PocketSendRequest {
-                        actions: &[&PocketSendAction::$action {
-                            item_id: opts.item_id,
-                            time: opts.time.map(|t| t.timestamp() as u64),
-                        }],
+                let item_ids = item_ref::item_ids(
+                    pocket,
+                    opts.item.as_ref(),
+                    opts.first,
+                    opts.stdin,
+                    opts.input_format.unwrap_or(output.format),
+                )
+                .unwrap_or_else(|e| panic!("{}", e));
+                let mut responses = send(pocket, &item_ids, opts.time).unwrap();
+                if opts.item.is_some() {
+                    output.write(responses.remove(0)).unwrap();
+                } else {
+                    output.write(responses).unwrap();
+                }
+            }
+
+            pub fn send(
+                pocket: &impl PocketSend,
+                item_ids: &[u64],
+                time: Option<DateTime<Utc>>,
+            ) -> BackendResult<Vec<IndividualSendResponse>> {
+                let actions = item_ids
+                    .iter()
+                    .map(|item_id| PocketSendAction::$action {
+                        item_id: *item_id,
+                        time: time.map(|t| t.timestamp() as u64),
                     })
-                    .unwrap()
-                    .into();
-                output.write(response).unwrap();
+                    .collect::<Vec<_>>();
+                Ok(IndividualSendResponse::each(send_all(pocket, &actions)?))
             }
         }
     };
@@ -40,16 +63,36 @@ send_item!(tags_clear, TagsClear);
 
 #[derive(Debug, StructOpt)]
 pub struct SendItemOpts {
-    item_id: u64,
+    /// Item id or URL; pick items interactively when left out
+    pub item: Option<ItemRef>,
+    /// Use the newest item when several match the URL
     #[structopt(long)]
-    time: Option<DateTime<Utc>>,
-}
-pub trait PocketSend {
-    fn send(&self, request: &PocketSendRequest) -> PocketResult<PocketSendResponse>;
+    pub first: bool,
+    /// Read the items to act on from stdin, such as `pocket get` output
+    #[structopt(long, conflicts_with = "item")]
+    pub stdin: bool,
+    /// Format of the items read with --stdin (json, yaml or toml), the output format by default
+    #[structopt(long, requires = "stdin")]
+    pub input_format: Option<OutputFormat>,
+    #[structopt(long)]
+    pub time: Option<DateTime<Utc>>,
 }
 
-impl PocketSend for Pocket {
-    fn send<'a>(&self, request: &PocketSendRequest<'a>) -> PocketResult<PocketSendResponse> {
-        self.send(request)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn input_format_is_read_apart_from_output_format() {
+        let opts = SendItemOpts::from_iter_safe(&["archive", "--stdin", "--input-format", "yaml"])
+            .unwrap();
+
+        assert!(opts.stdin);
+        assert!(matches!(opts.input_format, Some(OutputFormat::Yaml)));
+    }
+
+    #[test]
+    fn input_format_requires_stdin() {
+        assert!(SendItemOpts::from_iter_safe(&["archive", "1", "--input-format", "yaml"]).is_err());
     }
 }
@@ -0,0 +1,301 @@
+use crate::backend::local::Store;
+use crate::backend::v3::{
+    AddRequest, AddResponse, Authenticated, GetRequest, GetResponse, List, OAuthCode, OAuthRequest,
+    OAuthUser, SendRequest, SendResult, WireItem,
+};
+use crate::output::Output;
+use chrono::Utc;
+use hyper::header::{ContentType, Location};
+use hyper::server::{Listening, Request, Response, Server};
+use hyper::status::StatusCode;
+use hyper::uri::RequestUri;
+use pocket::{PocketAddRequest, PocketSendRequest};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::{Read, Write};
+use std::sync::Mutex;
+use structopt::StructOpt;
+use url::Url;
+
+const USERNAME: &str = "mock";
+
+#[derive(Debug, StructOpt)]
+pub struct ServeMockOpts {
+    /// Address to listen on
+    #[structopt(long, default_value = "127.0.0.1:8080")]
+    addr: String,
+    /// Access token to accept without signing in
+    #[structopt(long)]
+    access_token: Option<String>,
+}
+
+pub fn handle<W: Write>(opts: &ServeMockOpts, output: &mut Output<W>) {
+    let listening = MockPocket::new(opts.access_token.clone())
+        .listen(&opts.addr)
+        .unwrap();
+    output
+        .write(format!("Listening on http://{}", listening.socket))
+        .unwrap();
+    // Dropping the listener waits for the server, which runs until the process is killed
+    drop(listening);
+}
+
+/// The Pocket v3 API and its OAuth flow, served from an in-memory store.
+pub struct MockPocket {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    store: Store,
+    access_tokens: Vec<String>,
+    issued: u64,
+}
+
+enum Reply {
+    Json(u16, String),
+    Redirect(String),
+}
+
+impl Reply {
+    fn json<T: Serialize>(value: &T) -> Reply {
+        Reply::Json(200, serde_json::to_string(value).unwrap())
+    }
+
+    fn error(status: u16, message: &str) -> Reply {
+        Reply::Json(status, serde_json::json!({ "error": message }).to_string())
+    }
+}
+
+impl MockPocket {
+    pub fn new(access_token: Option<String>) -> MockPocket {
+        MockPocket {
+            state: Mutex::new(State {
+                access_tokens: access_token.into_iter().collect(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    pub fn listen(self, addr: &str) -> hyper::Result<Listening> {
+        Server::http(addr)?.handle(move |mut request: Request, mut response: Response| {
+            let path = match &request.uri {
+                RequestUri::AbsolutePath(path) => path.clone(),
+                uri => uri.to_string(),
+            };
+            let method = request.method.to_string();
+            let mut body = String::new();
+            let reply = match request.read_to_string(&mut body) {
+                Ok(_) => self.route(&method, &path, &body),
+                Err(e) => Reply::error(400, &e.to_string()),
+            };
+
+            let body = match reply {
+                Reply::Json(status, body) => {
+                    *response.status_mut() = StatusCode::from_u16(status);
+                    response.headers_mut().set(ContentType::json());
+                    body
+                }
+                Reply::Redirect(location) => {
+                    *response.status_mut() = StatusCode::Found;
+                    response.headers_mut().set(Location(location));
+                    String::new()
+                }
+            };
+            // The client may have gone away, which is no reason to stop serving
+            let _ = response.send(body.as_bytes());
+        })
+    }
+
+    fn route(&self, method: &str, path: &str, body: &str) -> Reply {
+        let (path, query) = match path.find('?') {
+            Some(i) => (&path[..i], &path[i + 1..]),
+            None => (path, ""),
+        };
+
+        match (method, path) {
+            ("POST", "/v3/oauth/request") => self.oauth_request(body),
+            ("POST", "/v3/oauth/authorize") => self.oauth_authorize(body),
+            ("GET", "/auth/authorize") => authorize_page(query),
+            ("POST", "/v3/add") => self.authenticated(body, add),
+            ("POST", "/v3/get") => self.authenticated(body, get),
+            ("POST", "/v3/send") => self.authenticated(body, send),
+            _ => Reply::error(404, "Not found"),
+        }
+    }
+
+    fn oauth_request(&self, body: &str) -> Reply {
+        let request: OAuthRequest = match serde_json::from_str(body) {
+            Ok(request) => request,
+            Err(e) => return Reply::error(400, &e.to_string()),
+        };
+        let mut state = self.state.lock().unwrap();
+        state.issued += 1;
+
+        Reply::json(&OAuthCode {
+            code: format!("code-{}", state.issued),
+            state: request.state,
+        })
+    }
+
+    /// Every request code is treated as approved, since there is no user to decline it.
+    fn oauth_authorize(&self, body: &str) -> Reply {
+        let request: OAuthRequest = match serde_json::from_str(body) {
+            Ok(request) => request,
+            Err(e) => return Reply::error(400, &e.to_string()),
+        };
+        if request.code.is_none() {
+            return Reply::error(400, "Missing code");
+        }
+        let mut state = self.state.lock().unwrap();
+        state.issued += 1;
+        let access_token = format!("token-{}", state.issued);
+        state.access_tokens.push(access_token.clone());
+
+        Reply::json(&OAuthUser {
+            access_token,
+            username: USERNAME.to_string(),
+            state: request.state,
+        })
+    }
+
+    fn authenticated<T, R>(
+        &self,
+        body: &str,
+        f: impl FnOnce(&mut Store, T) -> Result<R, Reply>,
+    ) -> Reply
+    where
+        T: DeserializeOwned,
+        R: Serialize,
+    {
+        let request: Authenticated<T> = match serde_json::from_str(body) {
+            Ok(request) => request,
+            Err(e) => return Reply::error(400, &e.to_string()),
+        };
+        let mut state = self.state.lock().unwrap();
+        if !state.access_tokens.contains(&request.access_token) {
+            return Reply::error(401, "Invalid access token");
+        }
+
+        match f(&mut state.store, request.request) {
+            Ok(response) => Reply::json(&response),
+            Err(reply) => reply,
+        }
+    }
+}
+
+/// Sends the browser straight back, as if the user had approved the app.
+fn authorize_page(query: &str) -> Reply {
+    url::form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _)| key == "redirect_uri")
+        .map_or_else(
+            || Reply::error(400, "Missing redirect_uri"),
+            |(_, uri)| Reply::Redirect(uri.into_owned()),
+        )
+}
+
+fn add(store: &mut Store, request: AddRequest) -> Result<AddResponse, Reply> {
+    let url = Url::parse(&request.url).map_err(|e| Reply::error(400, &e.to_string()))?;
+    let tags = request
+        .tags
+        .as_deref()
+        .map(|t| t.split(',').map(str::trim).collect::<Vec<_>>());
+    let item = store.add(
+        &PocketAddRequest {
+            url: &url,
+            title: request.title.as_deref(),
+            tags: tags.as_deref(),
+            tweet_id: request.tweet_id.as_deref(),
+        },
+        Utc::now(),
+    );
+
+    Ok(AddResponse {
+        item: WireItem::new(&item, 0),
+        status: 1,
+    })
+}
+
+fn get(store: &mut Store, request: GetRequest) -> Result<GetResponse, Reply> {
+    let opts = request
+        .to_opts()
+        .map_err(|e| Reply::error(400, &e.to_string()))?;
+    let list = store
+        .get(&opts)
+        .iter()
+        .enumerate()
+        .map(|(i, item)| (item.item_id.to_string(), WireItem::new(item, i as u64)))
+        .collect();
+
+    Ok(GetResponse {
+        status: 1,
+        list: List::Map(list),
+    })
+}
+
+fn send(store: &mut Store, request: SendRequest) -> Result<SendResult, Reply> {
+    let now = Utc::now();
+    let action_results = request
+        .actions
+        .iter()
+        .map(|action| {
+            if action.action == "add" {
+                let url = action.url.as_deref().and_then(|u| Url::parse(u).ok());
+                return url.map_or(false, |url| {
+                    let tags = action.tag_list();
+                    store.add(
+                        &PocketAddRequest {
+                            url: &url,
+                            title: action.title.as_deref(),
+                            tags: Some(tags.as_slice()).filter(|t| !t.is_empty()),
+                            tweet_id: None,
+                        },
+                        now,
+                    );
+                    true
+                });
+            }
+            action.to_action().map_or(false, |action| {
+                store
+                    .send(
+                        &PocketSendRequest {
+                            actions: &[&action],
+                        },
+                        now,
+                    )
+                    .action_results[0]
+            })
+        })
+        .map(serde_json::Value::Bool)
+        .collect();
+
+    Ok(SendResult {
+        status: 1,
+        action_results,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn route_rejects_unknown_access_token() {
+        let pocket = MockPocket::new(Some("token".to_string()));
+
+        let reply = pocket.route(
+            "POST",
+            "/v3/get",
+            r#"{"consumer_key":"key","access_token":"other"}"#,
+        );
+
+        assert!(matches!(reply, Reply::Json(401, _)));
+    }
+
+    #[test]
+    fn authorize_page_redirects_back() {
+        let reply = authorize_page("request_token=code&redirect_uri=http%3A%2F%2F127.0.0.1%3A1%2F");
+
+        assert!(matches!(reply, Reply::Redirect(uri) if uri == "http://127.0.0.1:1/"));
+    }
+}
@@ -0,0 +1,427 @@
+use crate::backend::{BackendResult, PocketGet};
+use crate::get::GetOpts;
+use crate::models::{Item, ItemStatus};
+use crate::output::Output;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use pocket::{PocketGetDetail, PocketGetState};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::str::FromStr;
+use structopt::StructOpt;
+
+/// Levels of a sparkline, from nothing to the largest value
+const SPARK_LEVELS: &[char] = &['_', '.', '-', ':', '=', '+', '*', '#', '@'];
+
+#[derive(Debug, StructOpt)]
+pub struct StatsOpts {
+    /// Length of the periods items added and read are counted in: week or month
+    #[structopt(long, default_value = "month")]
+    pub period: Period,
+    /// Number of periods to count, ending with the current one
+    #[structopt(long, default_value = "12")]
+    pub periods: usize,
+    /// Number of domains and tags to list
+    #[structopt(long, default_value = "10")]
+    pub top: usize,
+    /// Write a report to read instead of data in the output format
+    #[structopt(long)]
+    pub report: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Period {
+    Week,
+    Month,
+}
+
+impl FromStr for Period {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "week" => Ok(Period::Week),
+            "month" => Ok(Period::Month),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("Invalid period: {}", s),
+            )),
+        }
+    }
+}
+
+impl Period {
+    /// First day of the period `date` is in; weeks start on Monday.
+    fn start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+            Period::Month => NaiveDate::from_ymd(date.year(), date.month(), 1),
+        }
+    }
+
+    fn previous(self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Week => start - Duration::days(7),
+            Period::Month if start.month() == 1 => NaiveDate::from_ymd(start.year() - 1, 12, 1),
+            Period::Month => NaiveDate::from_ymd(start.year(), start.month() - 1, 1),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    pub total: usize,
+    pub unread: usize,
+    pub archived: usize,
+    pub deleted: usize,
+    pub favorites: usize,
+    pub favorite_ratio: f64,
+    /// Items added and read per period, oldest first
+    pub activity: Vec<Activity>,
+    pub queue: Queue,
+    pub top_domains: Vec<Count>,
+    pub top_tags: Vec<Count>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Activity {
+    pub start: NaiveDate,
+    pub added: usize,
+    pub read: usize,
+}
+
+/// Length of the unread items.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Queue {
+    pub word_count: Summary,
+    pub time_to_read: Summary,
+}
+
+/// Total and median of the items a value is known for.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Summary {
+    pub total: u64,
+    pub median: u64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Count {
+    pub name: String,
+    pub count: usize,
+}
+
+pub fn handle<W: Write>(pocket: &impl PocketGet, opts: &StatsOpts, output: &mut Output<W>) {
+    let stats = stats(pocket, opts, Utc::now()).unwrap();
+    if opts.report {
+        output.write_text(&report(&stats)).unwrap();
+    } else {
+        output.write(stats).unwrap();
+    }
+}
+
+pub fn stats(
+    pocket: &impl PocketGet,
+    opts: &StatsOpts,
+    now: DateTime<Utc>,
+) -> BackendResult<Stats> {
+    let items = pocket.get(&GetOpts {
+        state: Some(PocketGetState::All),
+        detail_type: Some(PocketGetDetail::Complete),
+        ..Default::default()
+    })?;
+
+    let count = |status: fn(&Option<ItemStatus>) -> bool| {
+        items.iter().filter(|i| status(&i.status)).count()
+    };
+    let favorites = items
+        .iter()
+        .filter(|i| i.favorite.unwrap_or_default())
+        .count();
+    let unread = items
+        .iter()
+        .filter(|i| matches!(i.status, None | Some(ItemStatus::Normal)))
+        .collect::<Vec<_>>();
+
+    Ok(Stats {
+        total: items.len(),
+        unread: unread.len(),
+        archived: count(|s| matches!(s, Some(ItemStatus::Archived))),
+        deleted: count(|s| matches!(s, Some(ItemStatus::Deleted))),
+        favorites,
+        favorite_ratio: if items.is_empty() {
+            0.0
+        } else {
+            favorites as f64 / items.len() as f64
+        },
+        activity: activity(&items, opts.period, opts.periods, now),
+        queue: Queue {
+            word_count: summary(
+                unread
+                    .iter()
+                    .map(|i| i.word_count as u64)
+                    .filter(|w| *w > 0),
+            ),
+            time_to_read: summary(unread.iter().filter_map(|i| i.time_to_read)),
+        },
+        top_domains: top(items.iter().filter_map(Item::domain), opts.top),
+        top_tags: top(items.iter().flat_map(Item::tag_names), opts.top),
+    })
+}
+
+fn activity(items: &[Item], period: Period, periods: usize, now: DateTime<Utc>) -> Vec<Activity> {
+    let mut starts = vec![period.start(now.date().naive_utc())];
+    while starts.len() < periods {
+        starts.push(period.previous(*starts.last().unwrap()));
+    }
+    starts.reverse();
+
+    let count = |time: fn(&Item) -> Option<DateTime<Utc>>, start: NaiveDate| {
+        items
+            .iter()
+            .filter_map(time)
+            .filter(|t| period.start(t.date().naive_utc()) == start)
+            .count()
+    };
+    starts
+        .into_iter()
+        .map(|start| Activity {
+            start,
+            added: count(|i| i.time_added, start),
+            read: count(|i| i.time_read, start),
+        })
+        .collect()
+}
+
+fn summary(values: impl Iterator<Item = u64>) -> Summary {
+    let mut values = values.collect::<Vec<_>>();
+    values.sort_unstable();
+    let median = match values.len() {
+        0 => 0,
+        n if n % 2 == 0 => (values[n / 2 - 1] + values[n / 2]) / 2,
+        n => values[n / 2],
+    };
+    Summary {
+        total: values.iter().sum(),
+        median,
+    }
+}
+
+/// The most common names, ties in alphabetical order.
+fn top<'a>(names: impl Iterator<Item = &'a str>, limit: usize) -> Vec<Count> {
+    let mut counts = HashMap::new();
+    for name in names {
+        *counts.entry(name).or_insert(0) += 1;
+    }
+    let mut counts = counts
+        .into_iter()
+        .map(|(name, count)| Count {
+            name: name.to_string(),
+            count,
+        })
+        .collect::<Vec<_>>();
+    counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+    counts.truncate(limit);
+    counts
+}
+
+/// One character per value, scaled to the largest.
+pub fn sparkline(values: &[usize]) -> String {
+    let max = values.iter().copied().max().unwrap_or_default();
+    let top = SPARK_LEVELS.len() - 1;
+    values
+        .iter()
+        .map(|v| match v {
+            0 => SPARK_LEVELS[0],
+            v => SPARK_LEVELS[1 + ((v * top - 1) / max).min(top - 1)],
+        })
+        .collect()
+}
+
+pub fn report(stats: &Stats) -> String {
+    let mut report = String::new();
+    let added = stats.activity.iter().map(|a| a.added).collect::<Vec<_>>();
+    let read = stats.activity.iter().map(|a| a.read).collect::<Vec<_>>();
+
+    // Writing to a String can't fail
+    let _ = writeln!(
+        report,
+        "Items      {} ({} unread, {} archived, {} deleted)",
+        stats.total, stats.unread, stats.archived, stats.deleted
+    );
+    let _ = writeln!(
+        report,
+        "Favorites  {} ({:.0}%)",
+        stats.favorites,
+        stats.favorite_ratio * 100.0
+    );
+    let _ = writeln!(
+        report,
+        "Queue      {} words (median {}), {} minutes (median {})",
+        stats.queue.word_count.total,
+        stats.queue.word_count.median,
+        stats.queue.time_to_read.total,
+        stats.queue.time_to_read.median
+    );
+    if let (Some(first), Some(last)) = (stats.activity.first(), stats.activity.last()) {
+        let _ = writeln!(
+            report,
+            "\nActivity since {} through {}",
+            first.start, last.start
+        );
+        let _ = writeln!(
+            report,
+            "  added  {}  {}",
+            sparkline(&added),
+            added.iter().sum::<usize>()
+        );
+        let _ = writeln!(
+            report,
+            "  read   {}  {}",
+            sparkline(&read),
+            read.iter().sum::<usize>()
+        );
+    }
+    for (title, counts) in &[
+        ("Top domains", &stats.top_domains),
+        ("Top tags", &stats.top_tags),
+    ] {
+        if counts.is_empty() {
+            continue;
+        }
+        let _ = writeln!(report, "\n{}", title);
+        let width = counts
+            .iter()
+            .map(|c| c.name.len())
+            .max()
+            .unwrap_or_default();
+        for count in counts.iter() {
+            let _ = writeln!(
+                report,
+                "  {:width$}  {}",
+                count.name,
+                count.count,
+                width = width
+            );
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::{unused_add, unused_send, PocketMock};
+    use chrono::TimeZone;
+
+    fn opts() -> StatsOpts {
+        StatsOpts {
+            period: Period::Month,
+            periods: 3,
+            top: 1,
+            report: false,
+        }
+    }
+
+    fn items() -> Vec<Item> {
+        let mut unread = Item::fixture(1, "https://example.com/a").with_tags(&["rust"]);
+        unread.status = Some(ItemStatus::Normal);
+        unread.word_count = 1000;
+        unread.time_to_read = Some(5);
+        unread.time_added = Some(Utc.ymd(2020, 5, 20).and_hms(0, 0, 0));
+
+        let mut archived = Item::fixture(2, "https://www.example.com/b");
+        archived.status = Some(ItemStatus::Archived);
+        archived.favorite = Some(true);
+        archived.time_added = Some(Utc.ymd(2020, 4, 2).and_hms(0, 0, 0));
+        archived.time_read = Some(Utc.ymd(2020, 6, 1).and_hms(0, 0, 0));
+
+        let mut other = Item::fixture(3, "https://other.com/c");
+        other.status = Some(ItemStatus::Normal);
+        other.word_count = 3000;
+        other.time_to_read = Some(15);
+
+        vec![unread, archived, other]
+    }
+
+    #[test]
+    fn stats_counts_items() {
+        let pocket = PocketMock {
+            get_mock: |_| Ok(items()),
+            send_mock: unused_send,
+            add_mock: unused_add,
+        };
+
+        let stats = stats(&pocket, &opts(), Utc.ymd(2020, 6, 15).and_hms(0, 0, 0)).unwrap();
+
+        assert_eq!(
+            (3, 2, 1, 0, 1),
+            (
+                stats.total,
+                stats.unread,
+                stats.archived,
+                stats.deleted,
+                stats.favorites
+            )
+        );
+        assert_eq!(
+            Summary {
+                total: 4000,
+                median: 2000
+            },
+            stats.queue.word_count
+        );
+        assert_eq!(
+            vec![(1, 0), (1, 0), (0, 1)],
+            stats
+                .activity
+                .iter()
+                .map(|a| (a.added, a.read))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(NaiveDate::from_ymd(2020, 4, 1), stats.activity[0].start);
+        assert_eq!(
+            vec![Count {
+                name: "example.com".to_string(),
+                count: 2
+            }],
+            stats.top_domains
+        );
+    }
+
+    #[test]
+    fn weeks_start_on_monday_and_cross_years() {
+        let sunday = NaiveDate::from_ymd(2020, 1, 5);
+
+        assert_eq!(
+            NaiveDate::from_ymd(2019, 12, 30),
+            Period::Week.start(sunday)
+        );
+        assert_eq!(
+            NaiveDate::from_ymd(2019, 12, 1),
+            Period::Month.previous(Period::Month.start(sunday))
+        );
+    }
+
+    #[test]
+    fn sparkline_scales_to_largest_value() {
+        assert_eq!("_.:@", sparkline(&[0, 1, 3, 8]));
+        assert_eq!("__", sparkline(&[0, 0]));
+    }
+
+    #[test]
+    fn report_lists_counts_and_activity() {
+        let pocket = PocketMock {
+            get_mock: |_| Ok(items()),
+            send_mock: unused_send,
+            add_mock: unused_add,
+        };
+        let stats = stats(&pocket, &opts(), Utc.ymd(2020, 6, 15).and_hms(0, 0, 0)).unwrap();
+
+        let report = report(&stats);
+
+        assert!(report.starts_with("Items      3 (2 unread, 1 archived, 0 deleted)\n"));
+        assert!(report.contains("  added  @@_  2\n"));
+        assert!(report.contains("\nTop tags\n  rust  1\n"));
+    }
+}
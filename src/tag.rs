@@ -1,16 +1,46 @@
-use crate::models::IndividualSendResponse;
+use crate::backend::{send_all, BackendResult, PocketGet, PocketSend};
+use crate::get::GetOpts;
+use crate::models::{IndividualSendResponse, Item, ItemStatus};
 use crate::output::Output;
+use crate::tag_policy::TagPolicy;
 use chrono::{DateTime, Utc};
 use pocket::*;
-use std::io::Write;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::str::FromStr;
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
 pub enum Tag {
     /// Rename
     Rename {
+        /// Tag, renamed along with the tags below it, or with --regex a pattern
+        /// matching the tags to rename
         old_tag: String,
+        /// New name, or with --regex a replacement that may refer to groups as `$1`
         new_tag: String,
+        /// Rename every tag matching a regular expression
+        #[structopt(long)]
+        regex: bool,
+        /// Show which tags would be renamed without renaming them
+        #[structopt(long)]
+        dry_run: bool,
+        #[structopt(long)]
+        time: Option<DateTime<Utc>>,
+    },
+    /// Merge tags into one
+    Merge {
+        #[structopt(required = true)]
+        tags: Vec<String>,
+        /// Tag to merge into
+        #[structopt(long)]
+        into: String,
+        /// Show which tags would be merged without merging them
+        #[structopt(long)]
+        dry_run: bool,
         #[structopt(long)]
         time: Option<DateTime<Utc>>,
     },
@@ -20,40 +50,599 @@ pub enum Tag {
         #[structopt(long)]
         time: Option<DateTime<Utc>>,
     },
+    /// Rewrite every tag to follow the tag policy of the config
+    Normalize {
+        /// Show which tags would be rewritten without rewriting them
+        #[structopt(long)]
+        dry_run: bool,
+        #[structopt(long)]
+        time: Option<DateTime<Utc>>,
+    },
+    /// List tags with the number of items carrying them
+    List {
+        #[structopt(flatten)]
+        opts: TagListOpts,
+    },
 }
 
-pub fn handle<W: Write>(pocket: &impl PocketSend, opts: &Tag, output: &mut Output<W>) {
-    let action = match opts {
+#[derive(Debug, StructOpt)]
+pub struct TagListOpts {
+    /// Order of the tags: name, count or recent
+    #[structopt(long, default_value = "name")]
+    pub sort: TagSort,
+    /// Only list tags containing this text
+    #[structopt(long = "match")]
+    pub matching: Option<String>,
+    /// Only list tags on at least this many items
+    #[structopt(long, default_value = "1")]
+    pub min_count: usize,
+    /// Show `/`-separated tags as a tree, counting the items below each tag
+    #[structopt(long)]
+    pub tree: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TagSort {
+    Name,
+    Count,
+    Recent,
+}
+
+impl FromStr for TagSort {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "name" => Ok(TagSort::Name),
+            "count" => Ok(TagSort::Count),
+            "recent" => Ok(TagSort::Recent),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("Invalid sort: {}", s),
+            )),
+        }
+    }
+}
+
+/// A tag and the tag it becomes.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TagMapping {
+    pub from: String,
+    pub to: String,
+}
+
+/// What a rename, merge or normalization did, or would do on a dry run.
+#[derive(Debug, Serialize)]
+pub struct Retagged {
+    pub mapping: Vec<TagMapping>,
+    pub actions: Vec<IndividualSendResponse>,
+}
+
+/// A tag and the items carrying it.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub items: usize,
+    pub unread: usize,
+    pub archived: usize,
+    pub favorite: usize,
+    /// When an item with the tag was last added or updated
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+pub fn handle<W: Write>(
+    pocket: &(impl PocketGet + PocketSend),
+    opts: &Tag,
+    policy: &TagPolicy,
+    output: &mut Output<W>,
+) {
+    match opts {
+        Tag::List { opts } if opts.tree => output
+            .write_text(&tree(&list(pocket, opts).unwrap()))
+            .unwrap(),
+        Tag::List { opts } => output.write(list(pocket, opts).unwrap()).unwrap(),
+        Tag::Delete { .. } => output.write(tag(pocket, opts).unwrap()).unwrap(),
+        _ => output.write(retag(pocket, opts, policy).unwrap()).unwrap(),
+    }
+}
+
+/// Whether `tag` is `parent` or below it, such as `work/reports` below `work`.
+pub fn within(tag: &str, parent: &str) -> bool {
+    tag.strip_prefix(parent)
+        .map_or(false, |rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Rename tags, merge them or normalize them by `policy`, across all items.
+pub fn retag(
+    pocket: &(impl PocketGet + PocketSend),
+    opts: &Tag,
+    policy: &TagPolicy,
+) -> BackendResult<Retagged> {
+    let items = pocket.get(&GetOpts {
+        state: Some(PocketGetState::All),
+        detail_type: Some(PocketGetDetail::Complete),
+        ..Default::default()
+    })?;
+    let tags = items
+        .iter()
+        .flat_map(Item::tag_names)
+        .collect::<BTreeSet<_>>();
+
+    let (mapping, dry_run, time) = match opts {
+        Tag::Merge {
+            tags: merged,
+            into,
+            dry_run,
+            time,
+        } => {
+            let mapping = merged
+                .iter()
+                .filter(|t| *t != into && tags.contains(t.as_str()))
+                .map(|t| TagMapping {
+                    from: t.clone(),
+                    to: into.clone(),
+                })
+                .collect();
+            (mapping, *dry_run, time)
+        }
         Tag::Rename {
             old_tag,
             new_tag,
+            regex: false,
+            dry_run,
             time,
-        } => PocketSendAction::TagRename {
-            old_tag: old_tag.clone(),
-            new_tag: new_tag.clone(),
-            time: time.map(|t| t.timestamp() as u64),
-        },
+        } => (rename_below(&tags, old_tag, new_tag), *dry_run, time),
+        Tag::Rename {
+            old_tag,
+            new_tag,
+            dry_run,
+            time,
+            ..
+        } => {
+            let pattern =
+                Regex::new(old_tag).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            (rename_matching(&tags, &pattern, new_tag), *dry_run, time)
+        }
+        Tag::Normalize { dry_run, time } => (normalize(&tags, policy), *dry_run, time),
+        Tag::List { .. } | Tag::Delete { .. } => unreachable!("only renames and merges retag"),
+    };
+
+    let actions = if dry_run {
+        vec![]
+    } else {
+        let actions = retag_actions(&items, &mapping, time.map(|t| t.timestamp() as u64));
+        if actions.is_empty() {
+            vec![]
+        } else {
+            IndividualSendResponse::each(send_all(pocket, &actions)?)
+        }
+    };
+    Ok(Retagged { mapping, actions })
+}
+
+/// `old_tag` and the tags below it, moved below `new_tag`.
+fn rename_below(tags: &BTreeSet<&str>, old_tag: &str, new_tag: &str) -> Vec<TagMapping> {
+    tags.iter()
+        .filter(|t| within(t, old_tag))
+        .map(|t| TagMapping {
+            from: t.to_string(),
+            to: format!("{}{}", new_tag, &t[old_tag.len()..]),
+        })
+        .filter(|m| m.to != m.from)
+        .collect()
+}
+
+fn rename_matching(tags: &BTreeSet<&str>, pattern: &Regex, replacement: &str) -> Vec<TagMapping> {
+    tags.iter()
+        .filter(|t| pattern.is_match(t))
+        .map(|t| TagMapping {
+            from: t.to_string(),
+            to: pattern.replace(t, replacement).into_owned(),
+        })
+        .filter(|m| !m.to.is_empty() && m.to != m.from)
+        .collect()
+}
+
+/// Tags that don't follow `policy`. Tags normalizing to nothing are left alone,
+/// as there is no tag to move their items to.
+fn normalize(tags: &BTreeSet<&str>, policy: &TagPolicy) -> Vec<TagMapping> {
+    tags.iter()
+        .map(|t| TagMapping {
+            from: t.to_string(),
+            to: policy.apply(t),
+        })
+        .filter(|m| !m.to.is_empty() && m.to != m.from)
+        .collect()
+}
+
+/// A `TagRename` for every tag that becomes a new tag of its own. Tags merged into
+/// one, or into a tag that is in use, are moved item by item with `TagsAdd` and
+/// `TagsRemove`, as renaming onto a tag in use is not supported by every backend.
+/// So are chained mappings, since renames would run before the moves onto their
+/// source tags and leave the items that had both tags without the moved one.
+fn retag_actions(
+    items: &[Item],
+    mapping: &[TagMapping],
+    time: Option<u64>,
+) -> Vec<PocketSendAction> {
+    let tags = items
+        .iter()
+        .flat_map(Item::tag_names)
+        .collect::<BTreeSet<_>>();
+    let sources = mapping
+        .iter()
+        .map(|m| m.from.as_str())
+        .collect::<BTreeSet<_>>();
+    let targets = mapping
+        .iter()
+        .map(|m| m.to.as_str())
+        .collect::<BTreeSet<_>>();
+    let (renamed, moved): (Vec<_>, Vec<_>) = mapping.iter().partition(|m| {
+        mapping.iter().filter(|other| other.to == m.to).count() == 1
+            && !tags.contains(m.to.as_str())
+            && !sources.contains(m.to.as_str())
+            && !targets.contains(m.from.as_str())
+    });
+
+    let mut actions = renamed
+        .iter()
+        .map(|m| PocketSendAction::TagRename {
+            old_tag: m.from.clone(),
+            new_tag: m.to.clone(),
+            time,
+        })
+        .collect::<Vec<_>>();
+
+    for item in items {
+        let old = item.tag_names().into_iter().collect::<BTreeSet<_>>();
+        let new = old
+            .iter()
+            .map(|t| {
+                moved
+                    .iter()
+                    .find(|m| m.from == *t)
+                    .map_or(*t, |m| m.to.as_str())
+            })
+            .collect::<BTreeSet<_>>();
+        let join = |tags: BTreeSet<&&str>| tags.into_iter().copied().collect::<Vec<_>>().join(",");
+
+        let added = new.difference(&old).collect::<BTreeSet<_>>();
+        let removed = old.difference(&new).collect::<BTreeSet<_>>();
+        if !added.is_empty() {
+            actions.push(PocketSendAction::TagsAdd {
+                item_id: item.item_id,
+                tags: join(added),
+                time,
+            });
+        }
+        if !removed.is_empty() {
+            actions.push(PocketSendAction::TagsRemove {
+                item_id: item.item_id,
+                tags: join(removed),
+                time,
+            });
+        }
+    }
+    actions
+}
+
+/// Every tag in use, aggregated over all items. With --tree, the tags above those
+/// in use are listed too, and every tag counts the items below it.
+pub fn list(pocket: &impl PocketGet, opts: &TagListOpts) -> BackendResult<Vec<TagCount>> {
+    let items = pocket.get(&GetOpts {
+        state: Some(PocketGetState::All),
+        detail_type: Some(PocketGetDetail::Complete),
+        ..Default::default()
+    })?;
+    Ok(count_tags(&items, opts))
+}
+
+fn count_tags(items: &[Item], opts: &TagListOpts) -> Vec<TagCount> {
+    let mut counts = BTreeMap::new();
+    for item in items {
+        let used = item.time_updated.max(item.time_added);
+        let tags = if opts.tree {
+            // An item below a tag twice still counts once for it
+            item.tag_names()
+                .into_iter()
+                .flat_map(ancestors)
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect()
+        } else {
+            item.tag_names()
+        };
+        for tag in tags {
+            let count = counts.entry(tag).or_insert_with(|| TagCount {
+                tag: tag.to_string(),
+                ..Default::default()
+            });
+            count.items += 1;
+            match item.status {
+                Some(ItemStatus::Archived) => count.archived += 1,
+                Some(ItemStatus::Deleted) => {}
+                _ => count.unread += 1,
+            }
+            if item.favorite.unwrap_or_default() {
+                count.favorite += 1;
+            }
+            count.last_used = count.last_used.max(used);
+        }
+    }
+
+    let mut counts = counts
+        .into_iter()
+        .map(|(_, count)| count)
+        .filter(|c| c.items >= opts.min_count)
+        .filter(|c| {
+            opts.matching
+                .as_ref()
+                .map_or(true, |m| c.tag.contains(m.as_str()))
+        })
+        .collect::<Vec<_>>();
+    // Sorting is stable, so equal tags stay in order of their name
+    match opts.sort {
+        _ if opts.tree => counts.sort_by(|a, b| a.tag.split('/').cmp(b.tag.split('/'))),
+        TagSort::Name => {}
+        TagSort::Count => counts.sort_by(|a, b| b.items.cmp(&a.items)),
+        TagSort::Recent => counts.sort_by(|a, b| b.last_used.cmp(&a.last_used)),
+    }
+    counts
+}
+
+/// `tag` and every tag above it, such as `work` and `work/reports` for `work/reports`.
+fn ancestors(tag: &str) -> impl Iterator<Item = &str> {
+    tag.match_indices('/')
+        .map(move |(i, _)| &tag[..i])
+        .chain(std::iter::once(tag))
+}
+
+/// Tags indented below the tag they are in, one a line, in the order of `counts`.
+pub fn tree(counts: &[TagCount]) -> String {
+    let mut tree = String::new();
+    let mut parents: Vec<&str> = Vec::new();
+    for count in counts {
+        while parents.last().map_or(false, |p| !within(&count.tag, p)) {
+            parents.pop();
+        }
+        let name = parents
+            .last()
+            .map_or(count.tag.as_str(), |p| &count.tag[p.len() + 1..]);
+        let _ = writeln!(
+            tree,
+            "{}{}  {} ({} unread, {} archived, {} favorite)",
+            "  ".repeat(parents.len()),
+            name,
+            count.items,
+            count.unread,
+            count.archived,
+            count.favorite
+        );
+        parents.push(&count.tag);
+    }
+    tree
+}
+
+/// Delete a tag; listing is done by `list`, and renaming, merging and normalizing
+/// by `retag`.
+pub fn tag(pocket: &impl PocketSend, opts: &Tag) -> BackendResult<IndividualSendResponse> {
+    let action = match opts {
         Tag::Delete { tag, time } => PocketSendAction::TagDelete {
             tag: tag.clone(),
             time: time.map(|t| t.timestamp() as u64),
         },
+        Tag::List { .. } | Tag::Rename { .. } | Tag::Merge { .. } | Tag::Normalize { .. } => {
+            unreachable!("tag has no single action")
+        }
     };
 
-    let response: IndividualSendResponse = pocket
-        .send(&PocketSendRequest {
-            actions: &[&action],
-        })
-        .unwrap()
-        .into();
-    output.write(response).unwrap();
+    let response = pocket.send(&PocketSendRequest {
+        actions: &[&action],
+    })?;
+    Ok(response.into())
 }
 
-pub trait PocketSend {
-    fn send(&self, request: &PocketSendRequest) -> PocketResult<PocketSendResponse>;
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn opts(sort: TagSort) -> TagListOpts {
+        TagListOpts {
+            sort,
+            matching: None,
+            min_count: 1,
+            tree: false,
+        }
+    }
+
+    fn tagged(item_id: u64, tags: &[&str], status: ItemStatus, day: u32) -> Item {
+        let mut item = Item::fixture(item_id, "https://example.com/").with_tags(tags);
+        item.status = Some(status);
+        item.time_added = Some(Utc.ymd(2020, 1, day).and_hms(0, 0, 0));
+        item
+    }
+
+    fn items() -> Vec<Item> {
+        let mut favorite = tagged(2, &["rust"], ItemStatus::Archived, 2);
+        favorite.favorite = Some(true);
+        vec![
+            tagged(1, &["rust", "web"], ItemStatus::Normal, 1),
+            favorite,
+            tagged(3, &["go"], ItemStatus::Normal, 3),
+        ]
+    }
+
+    #[test]
+    fn count_tags_splits_by_status_and_favorite() {
+        let counts = count_tags(&items(), &opts(TagSort::Name));
+
+        assert_eq!(
+            vec!["go", "rust", "web"],
+            counts.iter().map(|c| c.tag.as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            TagCount {
+                tag: "rust".to_string(),
+                items: 2,
+                unread: 1,
+                archived: 1,
+                favorite: 1,
+                last_used: Some(Utc.ymd(2020, 1, 2).and_hms(0, 0, 0)),
+            },
+            counts[1]
+        );
+    }
+
+    #[test]
+    fn count_tags_sorts_and_filters() {
+        let by_count = count_tags(&items(), &opts(TagSort::Count));
+        let by_recent = count_tags(&items(), &opts(TagSort::Recent));
+        let mut filtered = opts(TagSort::Name);
+        filtered.matching = Some("r".to_string());
+        filtered.min_count = 2;
+
+        assert_eq!("rust", by_count[0].tag);
+        assert_eq!("go", by_recent[0].tag);
+        assert_eq!(1, count_tags(&items(), &filtered).len());
+    }
+
+    fn action_names(actions: &[PocketSendAction]) -> Vec<String> {
+        actions
+            .iter()
+            .map(|a| match a {
+                PocketSendAction::TagRename {
+                    old_tag, new_tag, ..
+                } => format!("rename {} {}", old_tag, new_tag),
+                PocketSendAction::TagsAdd { item_id, tags, .. } => {
+                    format!("add {} {}", item_id, tags)
+                }
+                PocketSendAction::TagsRemove { item_id, tags, .. } => {
+                    format!("remove {} {}", item_id, tags)
+                }
+                _ => "other".to_string(),
+            })
+            .collect()
+    }
+
+    fn mapping(pairs: &[(&str, &str)]) -> Vec<TagMapping> {
+        pairs
+            .iter()
+            .map(|(from, to)| TagMapping {
+                from: from.to_string(),
+                to: to.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rename_matching_replaces_groups() {
+        let tags = ["proj-a", "proj-b", "other"].iter().copied().collect();
+        let pattern = Regex::new("^proj-(.*)$").unwrap();
+
+        assert_eq!(
+            mapping(&[("proj-a", "project/a"), ("proj-b", "project/b")]),
+            rename_matching(&tags, &pattern, "project/$1")
+        );
+    }
+
+    #[test]
+    fn retag_actions_rename_new_tags() {
+        let actions = retag_actions(&items(), &mapping(&[("web", "www")]), None);
+
+        assert_eq!(vec!["rename web www"], action_names(&actions));
+    }
+
+    #[test]
+    fn retag_actions_move_items_onto_tags_in_use() {
+        let actions = retag_actions(&items(), &mapping(&[("web", "go"), ("rust", "go")]), None);
+
+        assert_eq!(
+            vec!["add 1 go", "remove 1 rust,web", "add 2 go", "remove 2 rust"],
+            action_names(&actions)
+        );
+    }
+
+    #[test]
+    fn retag_actions_move_items_for_chained_mappings() {
+        let actions = retag_actions(
+            &items(),
+            &mapping(&[("web", "rust"), ("rust", "lang")]),
+            None,
+        );
+
+        assert_eq!(
+            vec!["add 1 lang", "remove 1 web", "add 2 lang", "remove 2 rust"],
+            action_names(&actions)
+        );
+    }
+
+    #[test]
+    fn normalize_maps_tags_not_following_policy() {
+        let tags = ["Rust", "rust", "read_later", " "]
+            .iter()
+            .copied()
+            .collect();
+
+        assert_eq!(
+            mapping(&[("Rust", "rust"), ("read_later", "read-later")]),
+            normalize(&tags, &TagPolicy::folding())
+        );
+    }
+
+    #[test]
+    fn within_matches_whole_segments() {
+        assert!(within("work", "work"));
+        assert!(within("work/reports", "work"));
+        assert!(!within("workshop", "work"));
+        assert!(!within("work", "work/reports"));
+    }
+
+    #[test]
+    fn rename_below_renames_descendants() {
+        let tags = ["work", "work/reports", "workshop"]
+            .iter()
+            .copied()
+            .collect();
+
+        assert_eq!(
+            mapping(&[("work", "job"), ("work/reports", "job/reports")]),
+            rename_below(&tags, "work", "job")
+        );
+    }
+
+    #[test]
+    fn count_tags_rolls_up_tree() {
+        let items = vec![
+            tagged(1, &["work/a", "work/b"], ItemStatus::Normal, 1),
+            tagged(2, &["work/a/x"], ItemStatus::Archived, 2),
+            tagged(3, &["work-log"], ItemStatus::Normal, 3),
+        ];
+        let mut opts = opts(TagSort::Count);
+        opts.tree = true;
+
+        let counts = count_tags(&items, &opts);
 
-impl PocketSend for Pocket {
-    fn send<'a>(&self, request: &PocketSendRequest<'a>) -> PocketResult<PocketSendResponse> {
-        self.send(request)
+        assert_eq!(
+            vec![
+                ("work", 2),
+                ("work/a", 2),
+                ("work/a/x", 1),
+                ("work/b", 1),
+                ("work-log", 1)
+            ],
+            counts
+                .iter()
+                .map(|c| (c.tag.as_str(), c.items))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "work  2 (1 unread, 1 archived, 0 favorite)\n\
+             \x20 a  2 (1 unread, 1 archived, 0 favorite)\n\
+             \x20   x  1 (0 unread, 1 archived, 0 favorite)\n\
+             \x20 b  1 (1 unread, 0 archived, 0 favorite)\n\
+             work-log  1 (1 unread, 0 archived, 0 favorite)\n",
+            tree(&counts)
+        );
     }
 }
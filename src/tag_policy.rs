@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// How tags are written before they are saved, so `Rust`, `rust ` and `rust-lang`
+/// end up as one tag. Read from the `tag_policy` table of the config; without one,
+/// tags are saved as they are given.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default = "TagPolicy::folding")]
+pub struct TagPolicy {
+    /// Lowercase tags
+    pub lowercase: bool,
+    /// Remove whitespace around tags
+    pub trim: bool,
+    /// Characters to write as `separator`; runs of them become one
+    pub separators: Vec<char>,
+    pub separator: String,
+    /// Tags to write as another tag, such as `rust-lang = "rust"`. Both sides are
+    /// normalized first, so an alias covers every spelling of its tag
+    pub aliases: BTreeMap<String, String>,
+}
+
+impl Default for TagPolicy {
+    fn default() -> Self {
+        TagPolicy {
+            lowercase: false,
+            trim: false,
+            separators: vec![],
+            separator: "-".to_string(),
+            aliases: BTreeMap::new(),
+        }
+    }
+}
+
+impl TagPolicy {
+    /// The settings a `tag_policy` table leaves out: trimmed, lowercased tags with
+    /// spaces and underscores written as `-`.
+    pub fn folding() -> Self {
+        TagPolicy {
+            lowercase: true,
+            trim: true,
+            separators: vec![' ', '_'],
+            ..Default::default()
+        }
+    }
+
+    /// The normalized form of `tag`, which is empty for a tag of only whitespace.
+    pub fn apply(&self, tag: &str) -> String {
+        let tag = self.spell(tag);
+        self.aliases
+            .iter()
+            .find(|(alias, _)| self.spell(alias) == tag)
+            .map_or(tag, |(_, to)| self.spell(to))
+    }
+
+    /// The normalized forms of `tags`, without empty tags and repeats.
+    pub fn apply_all(&self, tags: &[String]) -> Vec<String> {
+        let mut normalized = Vec::new();
+        for tag in tags.iter().map(|t| self.apply(t)) {
+            if !tag.is_empty() && !normalized.contains(&tag) {
+                normalized.push(tag);
+            }
+        }
+        normalized
+    }
+
+    fn spell(&self, tag: &str) -> String {
+        let tag = if self.trim { tag.trim() } else { tag };
+        let tag = if self.lowercase {
+            tag.to_lowercase()
+        } else {
+            tag.to_string()
+        };
+        if self.separators.is_empty() {
+            return tag;
+        }
+        tag.split(|c| self.separators.contains(&c))
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join(&self.separator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_policy_keeps_tags_as_given() {
+        let policy = TagPolicy::default();
+
+        assert_eq!(" Rust ", policy.apply(" Rust "));
+        assert_eq!("read_later", policy.apply("read_later"));
+    }
+
+    #[test]
+    fn folding_policy_folds_case_whitespace_and_separators() {
+        let policy = TagPolicy::folding();
+
+        assert_eq!("rust", policy.apply(" Rust "));
+        assert_eq!("read-later", policy.apply("Read  Later"));
+        assert_eq!("read-later", policy.apply("read_later"));
+        assert_eq!("", policy.apply("  "));
+    }
+
+    #[test]
+    fn aliases_match_every_spelling() {
+        let mut policy = TagPolicy::folding();
+        policy
+            .aliases
+            .insert("rust-lang".to_string(), "Rust".to_string());
+
+        assert_eq!("rust", policy.apply("Rust Lang"));
+        assert_eq!("rust", policy.apply("rust_lang"));
+    }
+
+    #[test]
+    fn apply_all_drops_empty_and_repeated_tags() {
+        let tags = ["Rust", "rust ", " ", "go"]
+            .iter()
+            .map(|t| t.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec!["rust", "go"], TagPolicy::folding().apply_all(&tags));
+    }
+
+    #[test]
+    fn policy_reads_partial_config() {
+        let policy: TagPolicy = toml::from_str("lowercase = false").unwrap();
+
+        assert!(!policy.lowercase);
+        assert_eq!(vec![' ', '_'], policy.separators);
+    }
+}
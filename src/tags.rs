@@ -1,59 +1,93 @@
+use crate::item_ref::ItemRef;
+use crate::output::OutputFormat;
 use chrono::{DateTime, Utc};
-use pocket::{Pocket, PocketResult, PocketSendRequest, PocketSendResponse};
 use structopt::StructOpt;
 
 macro_rules! tags {
-    ($command:ident, $action:ident) => {
+    ($command:ident, $action:ident, $normalize:expr) => {
         pub mod $command {
-            use super::{PocketSend, TagsOpts};
+            use super::TagsOpts;
+            use crate::backend::{send_all, BackendResult, PocketGet, PocketSend};
+            use crate::item_ref;
             use crate::models::IndividualSendResponse;
             use crate::output::Output;
-            use pocket::{PocketSendAction, PocketSendRequest};
+            use crate::tag_policy::TagPolicy;
+            use pocket::PocketSendAction;
             use std::io::Write;
 
             pub fn handle<W: Write>(
-                pocket: &impl PocketSend,
+                pocket: &(impl PocketGet + PocketSend),
                 opts: &TagsOpts,
+                policy: &TagPolicy,
                 output: &mut Output<W>,
             ) {
-                let response: IndividualSendResponse = pocket
-                    .send(&PocketSendRequest {
-                        actions: &[&PocketSendAction::$action {
-                            item_id: opts.item_id,
-                            tags: opts
-                                .tags
-                                .as_ref()
-                                .map(|tags| tags.join(","))
-                                .unwrap_or("".to_string()),
-                            time: opts.time.map(|t| t.timestamp() as u64),
-                        }],
+                let item_ids = item_ref::item_ids(
+                    pocket,
+                    opts.item.as_ref(),
+                    opts.first,
+                    opts.stdin,
+                    opts.input_format.unwrap_or(output.format),
+                )
+                .unwrap_or_else(|e| panic!("{}", e));
+                let mut responses = send(pocket, &item_ids, opts, policy).unwrap();
+                if opts.item.is_some() {
+                    output.write(responses.remove(0)).unwrap();
+                } else {
+                    output.write(responses).unwrap();
+                }
+            }
+
+            pub fn send(
+                pocket: &impl PocketSend,
+                item_ids: &[u64],
+                opts: &TagsOpts,
+                policy: &TagPolicy,
+            ) -> BackendResult<Vec<IndividualSendResponse>> {
+                let tags = opts
+                    .tags
+                    .as_ref()
+                    .map(|tags| {
+                        if $normalize {
+                            policy.apply_all(tags).join(",")
+                        } else {
+                            tags.join(",")
+                        }
                     })
-                    .unwrap()
-                    .into();
-                output.write(response).unwrap();
+                    .unwrap_or("".to_string());
+                let actions = item_ids
+                    .iter()
+                    .map(|item_id| PocketSendAction::$action {
+                        item_id: *item_id,
+                        tags: tags.clone(),
+                        time: opts.time.map(|t| t.timestamp() as u64),
+                    })
+                    .collect::<Vec<_>>();
+                Ok(IndividualSendResponse::each(send_all(pocket, &actions)?))
             }
         }
     };
 }
 
-tags!(tags_add, TagsAdd);
-tags!(tags_remove, TagsRemove);
-tags!(tags_replace, TagsReplace);
+tags!(tags_add, TagsAdd, true);
+// Tags to remove are left as given, so tags saved before the policy can be removed
+tags!(tags_remove, TagsRemove, false);
+tags!(tags_replace, TagsReplace, true);
 
 #[derive(Debug, StructOpt)]
 pub struct TagsOpts {
-    item_id: u64,
+    /// Item id or URL; pick items interactively when left out
+    pub item: Option<ItemRef>,
+    /// Use the newest item when several match the URL
+    #[structopt(long)]
+    pub first: bool,
+    /// Read the items to tag from stdin, such as `pocket get` output
+    #[structopt(long, conflicts_with = "item")]
+    pub stdin: bool,
+    /// Format of the items read with --stdin (json, yaml or toml), the output format by default
+    #[structopt(long, requires = "stdin")]
+    pub input_format: Option<OutputFormat>,
     #[structopt(long = "tag")]
-    tags: Option<Vec<String>>,
+    pub tags: Option<Vec<String>>,
     #[structopt(long)]
-    time: Option<DateTime<Utc>>,
-}
-pub trait PocketSend {
-    fn send(&self, request: &PocketSendRequest) -> PocketResult<PocketSendResponse>;
-}
-
-impl PocketSend for Pocket {
-    fn send<'a>(&self, request: &PocketSendRequest<'a>) -> PocketResult<PocketSendResponse> {
-        self.send(request)
-    }
+    pub time: Option<DateTime<Utc>>,
 }
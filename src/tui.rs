@@ -0,0 +1,602 @@
+use crate::backend::{send_all, BackendResult, PocketGet, PocketSend};
+use crate::get::{self, GetOpts};
+use crate::models::{IndividualSendResponse, Item, ItemStatus};
+use crate::output::Output;
+use crate::picker;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use crossterm::style::Print;
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use pocket::{PocketGetState, PocketGetType, PocketSendAction};
+use std::io::{self, Write};
+use structopt::StructOpt;
+use url::Url;
+
+/// Rows at the bottom of the screen showing the item under the cursor
+const DETAIL_ROWS: u16 = 8;
+
+#[derive(Debug, StructOpt)]
+pub struct TuiOpts {
+    /// Filters to start with
+    #[structopt(flatten)]
+    pub get: GetOpts,
+}
+
+/// Browse items and queue actions on them, which are sent when written with `w` and
+/// on quitting. Writes the responses to every action sent.
+pub fn handle<W: Write>(
+    pocket: &(impl PocketGet + PocketSend),
+    opts: &TuiOpts,
+    output: &mut Output<W>,
+) {
+    let items = get::get(pocket, &opts.get).unwrap();
+    let mut app = App::new(opts.get.clone(), items);
+    let mut out = io::stderr();
+
+    terminal::enable_raw_mode().unwrap();
+    execute!(out, EnterAlternateScreen, Hide).unwrap();
+    let responses = run(pocket, &mut app, &mut out);
+    // Restore the terminal before reporting anything that went wrong
+    execute!(out, Show, LeaveAlternateScreen).unwrap();
+    terminal::disable_raw_mode().unwrap();
+
+    output.write(responses.unwrap()).unwrap();
+}
+
+/// Send the queued actions in as few requests as the backend allows.
+pub fn send(
+    pocket: &impl PocketSend,
+    queued: &[Queued],
+) -> BackendResult<Vec<IndividualSendResponse>> {
+    if queued.is_empty() {
+        return Ok(vec![]);
+    }
+    let actions = queued.iter().map(Queued::to_action).collect::<Vec<_>>();
+    Ok(IndividualSendResponse::each(send_all(pocket, &actions)?))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    Archive,
+    Readd,
+    Favorite,
+    Unfavorite,
+    Delete,
+    TagsAdd(String),
+}
+
+impl Action {
+    /// Shown next to the item until the action is sent
+    fn mark(&self) -> char {
+        match self {
+            Action::Archive => 'A',
+            Action::Readd => 'R',
+            Action::Favorite => '*',
+            Action::Unfavorite => '-',
+            Action::Delete => 'D',
+            Action::TagsAdd(_) => '+',
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Queued {
+    pub item_id: u64,
+    pub action: Action,
+}
+
+impl Queued {
+    fn to_action(&self) -> PocketSendAction {
+        let item_id = self.item_id;
+        match &self.action {
+            Action::Archive => PocketSendAction::Archive {
+                item_id,
+                time: None,
+            },
+            Action::Readd => PocketSendAction::Readd {
+                item_id,
+                time: None,
+            },
+            Action::Favorite => PocketSendAction::Favorite {
+                item_id,
+                time: None,
+            },
+            Action::Unfavorite => PocketSendAction::Unfavorite {
+                item_id,
+                time: None,
+            },
+            Action::Delete => PocketSendAction::Delete {
+                item_id,
+                time: None,
+            },
+            Action::TagsAdd(tags) => PocketSendAction::TagsAdd {
+                item_id,
+                tags: tags.clone(),
+                time: None,
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Key {
+    Char(char),
+    Up,
+    Down,
+    Enter,
+    Backspace,
+    Esc,
+}
+
+/// What the screen loop has to do after a key press.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Continue,
+    /// Get the items again, as the filters changed
+    Reload,
+    /// Send the queued actions, then reload
+    Send,
+    /// Send the queued actions and leave
+    Quit,
+}
+
+#[derive(Debug, PartialEq)]
+enum Prompt {
+    TagFilter,
+    AddTags,
+}
+
+/// Items, filters and queued actions, apart from the terminal they are drawn in.
+pub struct App {
+    pub opts: GetOpts,
+    items: Vec<Item>,
+    cursor: usize,
+    queued: Vec<Queued>,
+    prompt: Option<(Prompt, String)>,
+    message: Option<String>,
+}
+
+impl App {
+    pub fn new(opts: GetOpts, items: Vec<Item>) -> App {
+        App {
+            opts,
+            items,
+            cursor: 0,
+            queued: Vec::new(),
+            prompt: None,
+            message: None,
+        }
+    }
+
+    pub fn queued(&self) -> &[Queued] {
+        &self.queued
+    }
+
+    /// Replace the items after a reload, keeping the cursor on screen.
+    pub fn set_items(&mut self, items: Vec<Item>) {
+        self.items = items;
+        self.cursor = self.cursor.min(self.items.len().saturating_sub(1));
+    }
+
+    /// Forget the queued actions once they have been sent.
+    pub fn sent(&mut self) {
+        self.message = Some(format!("sent {} actions", self.queued.len()));
+        self.queued.clear();
+    }
+
+    pub fn press(&mut self, key: Key) -> Command {
+        self.message = None;
+        if self.prompt.is_some() {
+            return self.press_in_prompt(key);
+        }
+
+        match key {
+            Key::Up | Key::Char('k') => self.cursor = self.cursor.saturating_sub(1),
+            Key::Down | Key::Char('j') => {
+                if self.cursor + 1 < self.items.len() {
+                    self.cursor += 1;
+                }
+            }
+            Key::Char('a') => {
+                let archived = self.current().map(|item| self.is_archived(item));
+                match archived {
+                    Some(true) => self.queue(Action::Readd),
+                    Some(false) => self.queue(Action::Archive),
+                    None => {}
+                }
+            }
+            Key::Char('f') => {
+                let favorite = self.current().map(|item| self.is_favorite(item));
+                match favorite {
+                    Some(true) => self.queue(Action::Unfavorite),
+                    Some(false) => self.queue(Action::Favorite),
+                    None => {}
+                }
+            }
+            Key::Char('d') => self.queue(Action::Delete),
+            Key::Char('t') => self.prompt = Some((Prompt::AddTags, String::new())),
+            Key::Char('u') => {
+                self.message = Some(match self.queued.pop() {
+                    Some(_) => "undone".to_string(),
+                    None => "nothing to undo".to_string(),
+                })
+            }
+            Key::Char('s') => {
+                self.opts.state = Some(match self.opts.state {
+                    None | Some(PocketGetState::Unread) => PocketGetState::Archive,
+                    Some(PocketGetState::Archive) => PocketGetState::All,
+                    Some(PocketGetState::All) => PocketGetState::Unread,
+                });
+                return Command::Reload;
+            }
+            Key::Char('F') => {
+                self.opts.favorite = match self.opts.favorite {
+                    None => Some(true),
+                    Some(true) => Some(false),
+                    Some(false) => None,
+                };
+                return Command::Reload;
+            }
+            Key::Char('c') => {
+                self.opts.content_type = match self.opts.content_type {
+                    None => Some(PocketGetType::Article),
+                    Some(PocketGetType::Article) => Some(PocketGetType::Video),
+                    Some(PocketGetType::Video) => Some(PocketGetType::Image),
+                    Some(PocketGetType::Image) => None,
+                };
+                return Command::Reload;
+            }
+            Key::Char('#') => {
+                let tag = self.opts.tag.clone().unwrap_or_default();
+                self.prompt = Some((Prompt::TagFilter, tag));
+            }
+            Key::Char('w') => return Command::Send,
+            Key::Char('q') | Key::Esc => return Command::Quit,
+            _ => {}
+        }
+        Command::Continue
+    }
+
+    fn press_in_prompt(&mut self, key: Key) -> Command {
+        let (prompt, mut input) = self.prompt.take().unwrap();
+        match key {
+            Key::Char(c) => input.push(c),
+            Key::Backspace => {
+                input.pop();
+            }
+            Key::Esc => return Command::Continue,
+            Key::Enter => {
+                let input = input.trim().to_string();
+                return match prompt {
+                    Prompt::AddTags if input.is_empty() => Command::Continue,
+                    Prompt::AddTags => {
+                        self.queue(Action::TagsAdd(input));
+                        Command::Continue
+                    }
+                    Prompt::TagFilter => {
+                        self.opts.tag = Some(input).filter(|t| !t.is_empty());
+                        Command::Reload
+                    }
+                };
+            }
+            Key::Up | Key::Down => {}
+        }
+        self.prompt = Some((prompt, input));
+        Command::Continue
+    }
+
+    fn current(&self) -> Option<&Item> {
+        self.items.get(self.cursor)
+    }
+
+    fn queue(&mut self, action: Action) {
+        if let Some(item) = self.current() {
+            self.queued.push(Queued {
+                item_id: item.item_id,
+                action,
+            });
+        }
+    }
+
+    /// The last queued action on `item`, which is what it will look like once sent
+    fn pending(&self, item: &Item) -> Option<&Action> {
+        self.queued
+            .iter()
+            .rev()
+            .find(|q| q.item_id == item.item_id)
+            .map(|q| &q.action)
+    }
+
+    fn is_archived(&self, item: &Item) -> bool {
+        let archiving = self.queued.iter().rev().find_map(|q| match q.action {
+            Action::Archive if q.item_id == item.item_id => Some(true),
+            Action::Readd if q.item_id == item.item_id => Some(false),
+            _ => None,
+        });
+        archiving.unwrap_or_else(|| matches!(item.status, Some(ItemStatus::Archived)))
+    }
+
+    fn is_favorite(&self, item: &Item) -> bool {
+        let favoriting = self.queued.iter().rev().find_map(|q| match q.action {
+            Action::Favorite if q.item_id == item.item_id => Some(true),
+            Action::Unfavorite if q.item_id == item.item_id => Some(false),
+            _ => None,
+        });
+        favoriting.unwrap_or_else(|| item.favorite.unwrap_or_default())
+    }
+
+    fn filters(&self) -> String {
+        let state = match self.opts.state {
+            None | Some(PocketGetState::Unread) => "unread",
+            Some(PocketGetState::Archive) => "archive",
+            Some(PocketGetState::All) => "all",
+        };
+        let favorite = match self.opts.favorite {
+            None => "any",
+            Some(true) => "yes",
+            Some(false) => "no",
+        };
+        let content_type = match self.opts.content_type {
+            None => "any",
+            Some(PocketGetType::Article) => "article",
+            Some(PocketGetType::Video) => "video",
+            Some(PocketGetType::Image) => "image",
+        };
+        format!(
+            "state: {}  tag: {}  favorite: {}  type: {}  ({} items, {} queued)",
+            state,
+            self.opts.tag.as_deref().unwrap_or("any"),
+            favorite,
+            content_type,
+            self.items.len(),
+            self.queued.len()
+        )
+    }
+}
+
+fn run(
+    pocket: &(impl PocketGet + PocketSend),
+    app: &mut App,
+    out: &mut impl Write,
+) -> BackendResult<Vec<IndividualSendResponse>> {
+    let mut responses = Vec::new();
+    loop {
+        draw(app, out).map_err(terminal_error)?;
+        let key = match event::read().map_err(terminal_error)? {
+            Event::Key(key) => key,
+            _ => continue,
+        };
+        let key = match (key.code, key.modifiers) {
+            (KeyCode::Char('c'), KeyModifiers::CONTROL) => Key::Char('q'),
+            (KeyCode::Char(c), _) => Key::Char(c),
+            (KeyCode::Up, _) => Key::Up,
+            (KeyCode::Down, _) => Key::Down,
+            (KeyCode::Enter, _) => Key::Enter,
+            (KeyCode::Backspace, _) => Key::Backspace,
+            (KeyCode::Esc, _) => Key::Esc,
+            _ => continue,
+        };
+
+        match app.press(key) {
+            Command::Continue => {}
+            Command::Reload => app.set_items(get::get(pocket, &app.opts)?),
+            Command::Send => {
+                responses.extend(send(pocket, app.queued())?);
+                app.sent();
+                app.set_items(get::get(pocket, &app.opts)?);
+            }
+            Command::Quit => {
+                responses.extend(send(pocket, app.queued())?);
+                return Ok(responses);
+            }
+        }
+    }
+}
+
+fn terminal_error(e: crossterm::ErrorKind) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e)
+}
+
+fn draw(app: &App, out: &mut impl Write) -> crossterm::Result<()> {
+    let (width, height) = terminal::size()?;
+    let line = |s: String| s.chars().take(width as usize).collect::<String>();
+    let list_rows = height.saturating_sub(DETAIL_ROWS + 2) as usize;
+
+    queue!(
+        out,
+        Clear(ClearType::All),
+        MoveTo(0, 0),
+        Print(line(app.filters()))
+    )?;
+
+    // Scroll just far enough to keep the cursor on screen
+    let first = (app.cursor + 1).saturating_sub(list_rows);
+    for (row, (n, item)) in app
+        .items
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(list_rows)
+        .enumerate()
+    {
+        let pointer = if n == app.cursor { '>' } else { ' ' };
+        let mark = app.pending(item).map_or(' ', Action::mark);
+        let favorite = if app.is_favorite(item) { '*' } else { ' ' };
+        queue!(
+            out,
+            MoveTo(0, row as u16 + 1),
+            Print(line(format!(
+                "{}{}{} {}",
+                pointer,
+                mark,
+                favorite,
+                picker::label(item)
+            )))
+        )?;
+    }
+
+    let detail_top = height.saturating_sub(DETAIL_ROWS + 1);
+    for (row, text) in app
+        .current()
+        .map(detail)
+        .unwrap_or_default()
+        .into_iter()
+        .enumerate()
+    {
+        queue!(out, MoveTo(0, detail_top + row as u16), Print(line(text)))?;
+    }
+
+    let status = match (&app.prompt, &app.message) {
+        (Some((Prompt::AddTags, input)), _) => format!("add tags: {}", input),
+        (Some((Prompt::TagFilter, input)), _) => format!("filter by tag: {}", input),
+        (None, Some(message)) => message.clone(),
+        (None, None) => "a: archive  f: favorite  d: delete  t: tag  u: undo  \
+            s/#/F/c: state/tag/favorite/type  w: send  q: quit"
+            .to_string(),
+    };
+    queue!(
+        out,
+        MoveTo(0, height.saturating_sub(1)),
+        Print(line(status))
+    )?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Lines of the detail pane, with the excerpt filling what is left.
+fn detail(item: &Item) -> Vec<String> {
+    let mut lines = vec![
+        "-".repeat(40),
+        item.title().unwrap_or_default().to_string(),
+        item.url().map(Url::to_string).unwrap_or_default(),
+        format!("by {}", item.author_names().join(", ")),
+        format!("tags: {}", item.tag_names().join(", ")),
+        match item.time_to_read {
+            Some(minutes) => format!("{} min, {} words", minutes, item.word_count),
+            None => format!("{} words", item.word_count),
+        },
+        item.excerpt.clone(),
+    ];
+    lines.truncate(DETAIL_ROWS as usize);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(items: Vec<Item>) -> App {
+        App::new(GetOpts::default(), items)
+    }
+
+    fn queued(item_id: u64, action: Action) -> Queued {
+        Queued { item_id, action }
+    }
+
+    #[test]
+    fn actions_queue_on_item_under_cursor() {
+        let mut app = app(vec![
+            Item::fixture(1, "https://example.com/a"),
+            Item::fixture(2, "https://example.com/b"),
+        ]);
+
+        app.press(Key::Char('a'));
+        app.press(Key::Down);
+        app.press(Key::Char('d'));
+
+        assert_eq!(
+            &[queued(1, Action::Archive), queued(2, Action::Delete)],
+            app.queued()
+        );
+    }
+
+    #[test]
+    fn archive_and_favorite_toggle_with_queued_actions() {
+        let mut item = Item::fixture(1, "https://example.com/a");
+        item.favorite = Some(true);
+        let mut app = app(vec![item]);
+
+        app.press(Key::Char('a'));
+        app.press(Key::Char('a'));
+        app.press(Key::Char('f'));
+
+        assert_eq!(
+            &[
+                queued(1, Action::Archive),
+                queued(1, Action::Readd),
+                queued(1, Action::Unfavorite)
+            ],
+            app.queued()
+        );
+    }
+
+    #[test]
+    fn undo_drops_last_queued_action() {
+        let mut app = app(vec![Item::fixture(1, "https://example.com/a")]);
+
+        app.press(Key::Char('a'));
+        app.press(Key::Char('d'));
+        app.press(Key::Char('u'));
+
+        assert_eq!(&[queued(1, Action::Archive)], app.queued());
+    }
+
+    #[test]
+    fn tag_prompt_queues_tags() {
+        let mut app = app(vec![Item::fixture(1, "https://example.com/a")]);
+
+        app.press(Key::Char('t'));
+        for c in "rust,later".chars() {
+            app.press(Key::Char(c));
+        }
+        let command = app.press(Key::Enter);
+
+        assert_eq!(Command::Continue, command);
+        assert_eq!(
+            &[queued(1, Action::TagsAdd("rust,later".to_string()))],
+            app.queued()
+        );
+    }
+
+    #[test]
+    fn filters_change_opts_and_reload() {
+        let mut app = app(vec![]);
+
+        assert_eq!(Command::Reload, app.press(Key::Char('s')));
+        assert_eq!(Command::Reload, app.press(Key::Char('F')));
+        app.press(Key::Char('#'));
+        app.press(Key::Char('x'));
+        assert_eq!(Command::Reload, app.press(Key::Enter));
+
+        assert!(matches!(app.opts.state, Some(PocketGetState::Archive)));
+        assert_eq!(Some(true), app.opts.favorite);
+        assert_eq!(Some("x"), app.opts.tag.as_deref());
+    }
+
+    #[test]
+    fn send_batches_queued_actions() {
+        struct PocketSendMock;
+
+        impl PocketSend for PocketSendMock {
+            fn send(
+                &self,
+                request: &pocket::PocketSendRequest,
+            ) -> BackendResult<crate::backend::SendResponse> {
+                Ok(crate::backend::SendResponse {
+                    status: 1,
+                    action_results: request.actions.iter().map(|_| true).collect(),
+                })
+            }
+        }
+
+        let responses = send(
+            &PocketSendMock,
+            &[queued(1, Action::Archive), queued(2, Action::Favorite)],
+        )
+        .unwrap();
+
+        assert_eq!(2, responses.len());
+        assert!(responses.iter().all(|r| r.success));
+    }
+}